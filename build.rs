@@ -0,0 +1,25 @@
+fn main() {
+    // The C header is generated into `OUT_DIR` from `src/capi.rs`, which only exposes functions with the `capi` feature. The
+    // checked-in `include/cdumay_sodium.h` is only refreshed when `CDUMAY_SODIUM_WRITE_HEADER` is set, as build scripts must not
+    // write to the source tree otherwise (docs.rs builds with every feature from a read-only copy).
+    #[cfg(feature = "capi")]
+    {
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        println!("cargo:rerun-if-env-changed=CDUMAY_SODIUM_WRITE_HEADER");
+        let crate_dir = std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        let header = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("cdumay_sodium.h");
+        let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).expect("invalid cbindgen.toml");
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(crate_dir.join("src/capi.rs"))
+            .generate()
+            .expect("unable to generate the C header")
+            .write_to_file(&header);
+        if std::env::var_os("CDUMAY_SODIUM_WRITE_HEADER").is_some() {
+            std::fs::copy(&header, crate_dir.join("include/cdumay_sodium.h")).expect("unable to copy the C header to include/");
+        }
+    }
+    #[cfg(not(feature = "capi"))]
+    println!("cargo:rerun-if-changed=build.rs");
+}
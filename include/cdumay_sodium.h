@@ -0,0 +1,171 @@
+/* C API of cdumay_sodium, see src/capi.rs. */
+
+#ifndef CDUMAY_SODIUM_H
+#define CDUMAY_SODIUM_H
+
+/* Generated by cbindgen when building with the `capi` feature; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The call succeeded.
+ */
+#define CDUMAY_SODIUM_OK 0
+
+/**
+ * A pointer is NULL, a string is not valid UTF-8 or the algorithm is unknown.
+ */
+#define CDUMAY_SODIUM_ERR_INVALID_ARGUMENT -1
+
+/**
+ * The output buffer is too small; nothing was written.
+ */
+#define CDUMAY_SODIUM_ERR_BUFFER_TOO_SMALL -2
+
+/**
+ * An error of kind `InvalidConfiguration`: invalid key length, key encryption key mismatch, unsupported algorithm.
+ */
+#define CDUMAY_SODIUM_ERR_INVALID_CONFIGURATION -3
+
+/**
+ * An error of kind `ValidationError`: malformed envelope, failed authentication.
+ */
+#define CDUMAY_SODIUM_ERR_VALIDATION -4
+
+/**
+ * An `InvalidBase64` error: a key or an envelope field is not valid base64.
+ */
+#define CDUMAY_SODIUM_ERR_BASE64 -5
+
+/**
+ * An error of kind `UnknownError` (libsodium initialization failure), or any other failure.
+ */
+#define CDUMAY_SODIUM_ERR_UNKNOWN -6
+
+/**
+ * XSalsa20-Poly1305 payloads: the `xsalsa20poly1305` algorithm of a `v3` envelope.
+ */
+#define CDUMAY_SODIUM_XSALSA20POLY1305 0
+
+/**
+ * XChaCha20-Poly1305 payloads: the `xchacha20poly1305` algorithm of a `v3` envelope.
+ */
+#define CDUMAY_SODIUM_XCHACHA20POLY1305 1
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Initializes libsodium (see [`crate::init`]). The other functions do it on their own; this one detects a failure early.
+ */
+int cdumay_sodium_init(void);
+
+/**
+ * Returns the description of the last failure of the calling thread, or NULL if no call failed yet.
+ *
+ * The string is owned by the library and stays valid until the next failing call on the same thread.
+ */
+const char *cdumay_sodium_last_error(void);
+
+/**
+ * Returns the buffer size, terminating NUL included, of the envelope sealing `data_len` bytes with a secretbox key
+ * encryption key whose identifier is `kek_id_len` bytes long, or 0 if the algorithm is unknown.
+ */
+size_t cdumay_sodium_secretbox_sealed_len(size_t kek_id_len,
+                                          uint32_t algorithm,
+                                          size_t data_len);
+
+/**
+ * Returns the buffer size, terminating NUL included, of the envelope sealing `data_len` bytes with a sealed-box key
+ * encryption key whose identifier is `kek_id_len` bytes long, or 0 if the algorithm is unknown.
+ */
+size_t cdumay_sodium_sealedbox_sealed_len(size_t kek_id_len,
+                                          uint32_t algorithm,
+                                          size_t data_len);
+
+/**
+ * Stores in `len` the length of the plaintext sealed in `envelope`, without opening it.
+ *
+ * # Safety
+ *
+ * `envelope` must be a NUL-terminated string and `len` valid for a write.
+ */
+int cdumay_sodium_opened_len(const char *envelope, size_t *len);
+
+/**
+ * Seals `data` into an envelope with the secretbox key encryption key `kek_b64` identified by `kek_id`, and writes it to
+ * `out` as a NUL-terminated string.
+ *
+ * # Safety
+ *
+ * `kek_id` and `kek_b64` must be NUL-terminated strings, `data` valid for reads of `data_len` bytes (or NULL if empty) and `out`
+ * valid for writes of `out_len` bytes.
+ */
+int cdumay_sodium_secretbox_seal(const char *kek_id,
+                                 const char *kek_b64,
+                                 uint32_t algorithm,
+                                 const uint8_t *data,
+                                 size_t data_len,
+                                 char *out,
+                                 size_t out_len);
+
+/**
+ * Opens an envelope sealed with the secretbox key encryption key `kek_b64` identified by `kek_id`, writes the plaintext to
+ * `out` and its length to `written`. If `out` is too small, only `written` is set.
+ *
+ * # Safety
+ *
+ * `kek_id`, `kek_b64` and `envelope` must be NUL-terminated strings, `out` valid for writes of `out_len` bytes and `written`
+ * valid for a write.
+ */
+int cdumay_sodium_secretbox_open(const char *kek_id,
+                                 const char *kek_b64,
+                                 const char *envelope,
+                                 uint8_t *out,
+                                 size_t out_len,
+                                 size_t *written);
+
+/**
+ * Seals `data` into an envelope for the owner of the sealed-box public key `public_key_b64` identified by `kek_id`, and writes
+ * it to `out` as a NUL-terminated string.
+ *
+ * # Safety
+ *
+ * `kek_id` and `public_key_b64` must be NUL-terminated strings, `data` valid for reads of `data_len` bytes (or NULL if empty)
+ * and `out` valid for writes of `out_len` bytes.
+ */
+int cdumay_sodium_sealedbox_seal(const char *kek_id,
+                                 const char *public_key_b64,
+                                 uint32_t algorithm,
+                                 const uint8_t *data,
+                                 size_t data_len,
+                                 char *out,
+                                 size_t out_len);
+
+/**
+ * Opens an envelope sealed to the sealed-box keypair identified by `kek_id`, writes the plaintext to `out` and its length to
+ * `written`. If `out` is too small, only `written` is set.
+ *
+ * # Safety
+ *
+ * `kek_id`, `public_key_b64`, `private_key_b64` and `envelope` must be NUL-terminated strings, `out` valid for writes of
+ * `out_len` bytes and `written` valid for a write.
+ */
+int cdumay_sodium_sealedbox_open(const char *kek_id,
+                                 const char *public_key_b64,
+                                 const char *private_key_b64,
+                                 const char *envelope,
+                                 uint8_t *out,
+                                 size_t out_len,
+                                 size_t *written);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CDUMAY_SODIUM_H */
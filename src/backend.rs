@@ -0,0 +1,419 @@
+//! Pluggable implementations of the primitives the crate is built on.
+//!
+//! A [`Backend`] seals and opens secretboxes (both [`Algorithm`]s) and sealed boxes on raw bytes, and generates the keys and
+//! nonces they use. Every backend produces the same wire formats as libsodium, so a ciphertext sealed by one backend is opened by
+//! any other:
+//!
+//! * `Sodium`, the default, calls libsodium. It requires the `sodium` feature, enabled by default, which links libsodium.
+//! * `RustCrypto`, behind the `rustcrypto` feature, is written in pure Rust on top of the RustCrypto `crypto_secretbox` and
+//!   `crypto_box` crates, for targets where linking libsodium is impractical (static musl images, for example). It only needs
+//!   `alloc`, so it is the backend of `no_std` builds.
+//!
+//! Length checks and errors are identical across backends: a wrong key or nonce length is reported with
+//! [`crate::InvalidBoxKeyLength`] or [`crate::InvalidBoxNonceLength`], a wrong keypair length with [`crate::InvalidPublicKeyLength`]
+//! or [`crate::InvalidPrivateKeyLength`], and a failed opening with [`crate::AuthenticationFailed`]. The errors carry the same
+//! details, including the `key_fingerprint` of the public key of a sealed box which cannot be opened.
+//!
+//! The other modules (`secretbox`, `sealedbox`, `envelope`, `keyring`...) are not routed through a backend: they call libsodium
+//! directly and require the `sodium` feature. With `std` and `rustcrypto` but without `sodium`, the crate builds without
+//! libsodium and this module, with the errors, is all that is left.
+//!
+//! # Example
+//!
+//! ```
+//! use cdumay_sodium::backend::{self, Backend};
+//! use cdumay_sodium::secretbox::Algorithm;
+//!
+//! let backend = backend::default();
+//! let key = backend.generate_secretbox_key();
+//! let nonce = backend.generate_nonce();
+//! let sealed = backend.secretbox_seal(Algorithm::XSalsa20Poly1305, b"my secret message", &nonce, &key, None).unwrap();
+//! let opened = backend.secretbox_open(Algorithm::XSalsa20Poly1305, &sealed, &nonce, &key, None).unwrap();
+//! assert_eq!(opened, b"my secret message");
+//! ```
+
+use crate::errors::operation;
+#[cfg(feature = "rustcrypto")]
+use crate::errors::{Detail, enrich};
+use crate::{UnsupportedAlgorithm, lengths};
+use alloc::format;
+use alloc::vec::Vec;
+
+/// Length of a secretbox key.
+pub const SECRETBOX_KEYBYTES: usize = lengths::SECRETBOX_KEYBYTES;
+
+/// Length of a secretbox nonce.
+pub const SECRETBOX_NONCEBYTES: usize = lengths::SECRETBOX_NONCEBYTES;
+
+/// Length of a sealed-box public key.
+pub const PUBLICKEYBYTES: usize = lengths::BOX_PUBLICKEYBYTES;
+
+/// Length of a sealed-box private key.
+pub const SECRETKEYBYTES: usize = lengths::BOX_SECRETKEYBYTES;
+
+/// Secretbox construction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// XSalsa20-Poly1305 (`crypto_secretbox_easy`), used by `secretbox::crypt` and `secretbox::decrypt`.
+    #[default]
+    XSalsa20Poly1305,
+    /// XChaCha20-Poly1305 (`crypto_secretbox_xchacha20poly1305_easy`).
+    XChaCha20Poly1305,
+}
+
+impl Algorithm {
+    /// Returns the identifier of the algorithm, as recorded in envelopes.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Algorithm::XSalsa20Poly1305 => "xsalsa20poly1305",
+            Algorithm::XChaCha20Poly1305 => "xchacha20poly1305",
+        }
+    }
+
+    /// Returns the algorithm with the given identifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`UnsupportedAlgorithm`] error if the identifier is unknown.
+    pub fn from_id(id: &str, context: impl Into<Option<crate::Context>>) -> crate::Result<Algorithm> {
+        let context = operation(context, module_path!(), "Algorithm::from_id");
+        match id {
+            "xsalsa20poly1305" => Ok(Algorithm::XSalsa20Poly1305),
+            "xchacha20poly1305" => Ok(Algorithm::XChaCha20Poly1305),
+            _ => Err(UnsupportedAlgorithm::new()
+                .with_message(format!("Unsupported secretbox algorithm '{}'", id))
+                .with_details(context)
+                .into()),
+        }
+    }
+}
+
+/// The primitives behind secretbox and sealed boxes, on raw bytes.
+///
+/// Secretboxes are in combined form (authentication tag followed by the ciphertext), sealed boxes in the `crypto_box_seal` form
+/// (ephemeral public key, tag, ciphertext).
+///
+/// The methods take the details to add to their errors, or `None`; the `module` and `operation` details are added by the backend.
+pub trait Backend: Send + Sync {
+    /// Returns the name of the backend, for logs.
+    fn name(&self) -> &'static str;
+
+    /// Generates a random secretbox key, for both algorithms. The caller is responsible for wiping it.
+    fn generate_secretbox_key(&self) -> [u8; SECRETBOX_KEYBYTES];
+
+    /// Generates a random secretbox nonce, for both algorithms.
+    fn generate_nonce(&self) -> [u8; SECRETBOX_NONCEBYTES];
+
+    /// Generates a random sealed-box keypair, as `(public_key, private_key)`. The caller is responsible for wiping the private key.
+    fn generate_keypair(&self) -> ([u8; PUBLICKEYBYTES], [u8; SECRETKEYBYTES]);
+
+    /// Seals `data` with `key` and `nonce`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`crate::InvalidBoxKeyLength`] or [`crate::InvalidBoxNonceLength`] error if the key or nonce has an invalid length.
+    fn secretbox_seal(
+        &self,
+        algorithm: Algorithm,
+        data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>>;
+
+    /// Opens data sealed by [`Backend::secretbox_seal`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`crate::InvalidBoxKeyLength`] or [`crate::InvalidBoxNonceLength`] error if the key or nonce has an invalid
+    /// length, or an [`crate::AuthenticationFailed`] error if the data fails authentication.
+    fn secretbox_open(
+        &self,
+        algorithm: Algorithm,
+        data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>>;
+
+    /// Seals `data` to the owner of `public_key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`crate::InvalidPublicKeyLength`] error if the public key has an invalid length.
+    fn sealedbox_seal(&self, data: &[u8], public_key: &[u8], context: Option<crate::Context>) -> crate::Result<Vec<u8>>;
+
+    /// Opens data sealed by [`Backend::sealedbox_seal`] with the recipient keypair.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`crate::InvalidPublicKeyLength`] or [`crate::InvalidPrivateKeyLength`] error if a key has an invalid length, a
+    /// [`crate::CiphertextTooShort`] error if the data is too short, or an [`crate::AuthenticationFailed`] error if it fails
+    /// authentication.
+    fn sealedbox_open(
+        &self,
+        data: &[u8],
+        private_key: &[u8],
+        public_key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>>;
+}
+
+/// Returns the default backend, [`Sodium`].
+#[cfg(feature = "sodium")]
+pub fn default() -> &'static dyn Backend {
+    &Sodium
+}
+
+/// The libsodium backend, used by every module of the crate.
+#[cfg(feature = "sodium")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sodium;
+
+#[cfg(feature = "sodium")]
+impl Backend for Sodium {
+    fn name(&self) -> &'static str {
+        "libsodium"
+    }
+
+    /// # Panics
+    ///
+    /// Panics if libsodium cannot be initialized (see [`crate::init`]).
+    fn generate_secretbox_key(&self) -> [u8; SECRETBOX_KEYBYTES] {
+        crate::init_or_panic();
+        let mut key = [0u8; SECRETBOX_KEYBYTES];
+        crate::ffi::random_bytes(&mut key);
+        key
+    }
+
+    /// # Panics
+    ///
+    /// Panics if libsodium cannot be initialized (see [`crate::init`]).
+    fn generate_nonce(&self) -> [u8; SECRETBOX_NONCEBYTES] {
+        crate::init_or_panic();
+        crate::secretbox::gen_nonce()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if libsodium cannot be initialized (see [`crate::init`]).
+    fn generate_keypair(&self) -> ([u8; PUBLICKEYBYTES], [u8; SECRETKEYBYTES]) {
+        crate::init_or_panic();
+        let mut private_key = [0u8; SECRETKEYBYTES];
+        crate::ffi::random_bytes(&mut private_key);
+        (crate::ffi::scalarmult_base(&private_key), private_key)
+    }
+
+    fn secretbox_seal(
+        &self,
+        algorithm: Algorithm,
+        data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>> {
+        let context = operation(context, module_path!(), "Backend::secretbox_seal");
+        let key = lengths::secretbox_key(key, context.clone())?;
+        let nonce = lengths::secretbox_nonce(nonce, context.clone())?;
+        crate::init(context)?;
+        Ok(crate::ffi::secretbox_easy(algorithm, data, nonce, key))
+    }
+
+    fn secretbox_open(
+        &self,
+        algorithm: Algorithm,
+        data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>> {
+        let context = operation(context, module_path!(), "Backend::secretbox_open");
+        let key = lengths::secretbox_key(key, context.clone())?;
+        let nonce = lengths::secretbox_nonce(nonce, context.clone())?;
+        crate::init(context.clone())?;
+        crate::ffi::secretbox_open_easy(algorithm, data, nonce, key)
+            .ok_or_else(|| crate::errors::authentication_failed(algorithm.id(), "data", context))
+    }
+
+    fn sealedbox_seal(&self, data: &[u8], public_key: &[u8], context: Option<crate::Context>) -> crate::Result<Vec<u8>> {
+        let context = operation(context, module_path!(), "Backend::sealedbox_seal");
+        crate::sealedbox::seal_bytes(data, public_key, context)
+    }
+
+    fn sealedbox_open(
+        &self,
+        data: &[u8],
+        private_key: &[u8],
+        public_key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>> {
+        let context = operation(context, module_path!(), "Backend::sealedbox_open");
+        crate::sealedbox::open_bytes(data, private_key, public_key, context)
+    }
+}
+
+/// The pure-Rust backend, built on the RustCrypto `crypto_secretbox` and `crypto_box` crates; it never calls libsodium.
+///
+/// Random keys, nonces and ephemeral keys are drawn from the generator given on construction. With the `std` feature,
+/// `RustCrypto::default` uses the operating system generator; `no_std` targets provide their own, typically a hardware RNG.
+#[cfg(feature = "rustcrypto")]
+#[derive(Debug, Clone, Copy)]
+pub struct RustCrypto {
+    fill_random: fn(&mut [u8]),
+}
+
+#[cfg(feature = "rustcrypto")]
+impl RustCrypto {
+    /// Creates a backend drawing random bytes from `fill_random`, which must be a cryptographically secure generator.
+    pub const fn with_random(fill_random: fn(&mut [u8])) -> RustCrypto {
+        RustCrypto { fill_random }
+    }
+}
+
+#[cfg(all(feature = "rustcrypto", feature = "std"))]
+impl Default for RustCrypto {
+    /// Draws random bytes from the operating system generator.
+    fn default() -> Self {
+        RustCrypto::with_random(|buf| crypto_box::aead::rand_core::RngCore::fill_bytes(&mut crypto_box::aead::OsRng, buf))
+    }
+}
+
+/// Adapts the generator of a [`RustCrypto`] backend to the `rand_core` traits expected by `crypto_box`.
+#[cfg(feature = "rustcrypto")]
+struct FillRandom(fn(&mut [u8]));
+
+#[cfg(feature = "rustcrypto")]
+impl crypto_box::aead::rand_core::RngCore for FillRandom {
+    fn next_u32(&mut self) -> u32 {
+        crypto_box::aead::rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        crypto_box::aead::rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        (self.0)(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> core::result::Result<(), crypto_box::aead::rand_core::Error> {
+        (self.0)(dest);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rustcrypto")]
+impl crypto_box::aead::rand_core::CryptoRng for FillRandom {}
+
+#[cfg(feature = "rustcrypto")]
+impl Backend for RustCrypto {
+    fn name(&self) -> &'static str {
+        "rustcrypto"
+    }
+
+    fn generate_secretbox_key(&self) -> [u8; SECRETBOX_KEYBYTES] {
+        let mut key = [0u8; SECRETBOX_KEYBYTES];
+        (self.fill_random)(&mut key);
+        key
+    }
+
+    fn generate_nonce(&self) -> [u8; SECRETBOX_NONCEBYTES] {
+        let mut nonce = [0u8; SECRETBOX_NONCEBYTES];
+        (self.fill_random)(&mut nonce);
+        nonce
+    }
+
+    fn generate_keypair(&self) -> ([u8; PUBLICKEYBYTES], [u8; SECRETKEYBYTES]) {
+        let private_key = crypto_box::SecretKey::generate(&mut FillRandom(self.fill_random));
+        (private_key.public_key().to_bytes(), private_key.to_bytes())
+    }
+
+    fn secretbox_seal(
+        &self,
+        algorithm: Algorithm,
+        data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>> {
+        let context = operation(context, module_path!(), "Backend::secretbox_seal");
+        use crypto_secretbox::aead::Aead;
+        use crypto_secretbox::KeyInit;
+        let key = lengths::secretbox_key(key, context.clone())?;
+        let nonce = lengths::secretbox_nonce(nonce, context.clone())?;
+        let sealed = match algorithm {
+            Algorithm::XSalsa20Poly1305 => crypto_secretbox::XSalsa20Poly1305::new(key.into()).encrypt(nonce.into(), data),
+            Algorithm::XChaCha20Poly1305 => crypto_secretbox::XChaCha20Poly1305::new(key.into()).encrypt(nonce.into(), data),
+        };
+        sealed.map_err(|_| crate::errors::encryption_failed(algorithm.id(), context))
+    }
+
+    fn secretbox_open(
+        &self,
+        algorithm: Algorithm,
+        data: &[u8],
+        nonce: &[u8],
+        key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>> {
+        let context = operation(context, module_path!(), "Backend::secretbox_open");
+        use crypto_secretbox::aead::Aead;
+        use crypto_secretbox::KeyInit;
+        let key = lengths::secretbox_key(key, context.clone())?;
+        let nonce = lengths::secretbox_nonce(nonce, context.clone())?;
+        let opened = match algorithm {
+            Algorithm::XSalsa20Poly1305 => crypto_secretbox::XSalsa20Poly1305::new(key.into()).decrypt(nonce.into(), data),
+            Algorithm::XChaCha20Poly1305 => crypto_secretbox::XChaCha20Poly1305::new(key.into()).decrypt(nonce.into(), data),
+        };
+        opened.map_err(|_| crate::errors::authentication_failed(algorithm.id(), "data", context))
+    }
+
+    fn sealedbox_seal(&self, data: &[u8], public_key: &[u8], context: Option<crate::Context>) -> crate::Result<Vec<u8>> {
+        let context = operation(context, module_path!(), "Backend::sealedbox_seal");
+        let public_key = crypto_box::PublicKey::from_bytes(*lengths::box_public_key(public_key, context.clone())?);
+        public_key
+            .seal(&mut FillRandom(self.fill_random), data)
+            .map_err(|_| crate::errors::encryption_failed(lengths::SEALEDBOX_ALGORITHM, context))
+    }
+
+    fn sealedbox_open(
+        &self,
+        data: &[u8],
+        private_key: &[u8],
+        public_key: &[u8],
+        context: Option<crate::Context>,
+    ) -> crate::Result<Vec<u8>> {
+        let context = operation(context, module_path!(), "Backend::sealedbox_open");
+        if data.len() < lengths::BOX_SEALBYTES {
+            let minimum = lengths::BOX_SEALBYTES;
+            return Err(crate::errors::ciphertext_too_short(lengths::SEALEDBOX_ALGORITHM, "data", minimum, data.len(), context));
+        }
+        let private_key = crypto_box::SecretKey::from_bytes(*lengths::box_secret_key(private_key, context.clone())?);
+        let public_key = lengths::box_public_key(public_key, context.clone())?;
+        let failed = || {
+            let context = enrich(context.clone(), &[("key_fingerprint", Detail::Text(&public_key_fingerprint(public_key)))]);
+            crate::errors::authentication_failed(lengths::SEALEDBOX_ALGORITHM, "data", context)
+        };
+        // libsodium derives the nonce from the given public key, so a keypair which does not match cannot open the box.
+        if private_key.public_key().as_bytes() != public_key {
+            return Err(failed());
+        }
+        private_key.unseal(data).map_err(|_| failed())
+    }
+}
+
+/// Domain-separation label of public key fingerprints, the one of [`crate::fingerprint`], which requires libsodium.
+#[cfg(feature = "rustcrypto")]
+const PUBLIC_KEY_FINGERPRINT_LABEL: &[u8] = b"cdumay_sodium.fingerprint.public-key.v1";
+
+/// Computes the fingerprint of a public key in its hex form, as [`crate::fingerprint`] does with libsodium: the keyed BLAKE2b-256
+/// hash of the key, truncated to 8 bytes.
+#[cfg(feature = "rustcrypto")]
+fn public_key_fingerprint(public_key: &[u8; PUBLICKEYBYTES]) -> alloc::string::String {
+    use blake2::digest::Mac;
+    use blake2::digest::consts::U32;
+    let mut hash = blake2::Blake2bMac::<U32>::new_with_salt_and_personal(PUBLIC_KEY_FINGERPRINT_LABEL, &[], &[])
+        .expect("the label is a valid BLAKE2b key");
+    hash.update(public_key);
+    hash.finalize().into_bytes()[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,193 @@
+//! Blind indexes make encrypted columns searchable by equality without decrypting every row, in the style of
+//! [CipherSweet](https://ciphersweet.paragonie.com/).
+//!
+//! A blind index is a keyed hash (BLAKE2b or HMAC-SHA-256) of the normalized plaintext, stored next to the regular randomized
+//! secretbox ciphertext. Looking a value up means computing its index and querying the index column.
+//!
+//! Indexes are truncated to a configurable number of bits: short indexes collide on purpose, so that each index value matches several
+//! rows and an attacker holding the table cannot tell which rows share a plaintext. With `N` rows and at least `k` rows expected per
+//! index value, pick a length of at most `log2(N / k)` bits, then filter the candidate rows after decryption.
+//!
+//! The index key must be independent from the encryption key; each index also mixes its name in the hash, so one key can serve
+//! several fields without correlating their indexes.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde_value::Value;
+//! use cdumay_sodium::blind_index::{BlindIndex, encrypt_with_indexes};
+//! use cdumay_sodium::secretbox;
+//!
+//! let sb_key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+//! let index_key_b64 = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+//! let context = BTreeMap::<String, Value>::new();
+//! let email_index = BlindIndex::new("email", index_key_b64, 32, context.clone()).unwrap();
+//!
+//! let field = encrypt_with_indexes("Alice@Example.com", sb_key_b64, &[&email_index], context.clone()).unwrap();
+//! assert_eq!(field.indexes["email"], email_index.compute(" alice@example.com"));
+//! assert_eq!(secretbox::decrypt(&field.ciphertext, sb_key_b64, &field.nonce, context).unwrap(), "Alice@Example.com");
+//! ```
+
+use crate::{Context, InvalidBlindIndex, decode_base64, ffi, secretbox};
+use std::collections::BTreeMap;
+
+/// Length of blind index keys.
+pub const KEYBYTES: usize = 32;
+
+/// Maximum length of a blind index, in bits.
+pub const MAX_BITS: u32 = 256;
+
+/// Keyed hash function used to compute an index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Keyed BLAKE2b (`crypto_generichash`).
+    Blake2b,
+    /// HMAC-SHA-256 (`crypto_auth_hmacsha256`), for interoperability with systems lacking BLAKE2b.
+    HmacSha256,
+}
+
+/// Trims surrounding whitespace and lowercases the text; the default normalization, suited to emails and usernames.
+pub fn normalize(plaintext: &str) -> String {
+    plaintext.trim().to_lowercase()
+}
+
+/// Keeps the text unchanged, for exact-match indexes.
+pub fn exact(plaintext: &str) -> String {
+    plaintext.to_string()
+}
+
+/// A named blind index over one field.
+pub struct BlindIndex {
+    name: String,
+    key: [u8; KEYBYTES],
+    bits: u32,
+    algorithm: Algorithm,
+    normalizer: fn(&str) -> String,
+}
+
+impl BlindIndex {
+    /// Creates a BLAKE2b index truncated to `bits` bits, with the default [`normalize`] normalization.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidBlindIndex`] error if `bits` is not between 1 and [`MAX_BITS`] or if the decoded key is not [`KEYBYTES`]
+    /// long, or an error if the key cannot be base64-decoded.
+    pub fn new(name: &str, key_b64: &str, bits: u32, context: impl Into<Option<Context>>) -> cdumay_core::Result<BlindIndex> {
+        let context = crate::errors::operation(context, module_path!(), "BlindIndex::new");
+        crate::init(context.clone())?;
+        if bits == 0 || bits > MAX_BITS {
+            return Err(InvalidBlindIndex::new()
+                .with_message(format!("Index length must be between 1 and {} bits", MAX_BITS))
+                .with_details(context)
+                .into());
+        }
+        let key_decoded = crate::Zeroizing(decode_base64(key_b64, "key", context.clone())?);
+        let key: [u8; KEYBYTES] = key_decoded.as_ref().try_into().map_err(|_| -> cdumay_core::Error {
+            InvalidBlindIndex::new()
+                .with_message(format!("Invalid key length, expected {} bytes", KEYBYTES))
+                .with_details(context.clone())
+                .into()
+        })?;
+        Ok(BlindIndex { name: name.to_string(), key, bits, algorithm: Algorithm::Blake2b, normalizer: normalize })
+    }
+
+    /// Sets the keyed hash function.
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the normalization applied to the plaintext before hashing, for example [`exact`].
+    pub fn with_normalizer(mut self, normalizer: fn(&str) -> String) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    /// Returns the name of the index.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the length of the index, in bits.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Computes the index of a plaintext as a lowercase hexadecimal string of `ceil(bits / 8)` bytes, unused trailing bits set to
+    /// zero.
+    pub fn compute(&self, plaintext: &str) -> String {
+        let normalized = crate::Zeroizing((self.normalizer)(plaintext).into_bytes());
+        let mut input = crate::Zeroizing(Vec::with_capacity(8 + self.name.len() + normalized.len()));
+        input.0.extend_from_slice(&(self.name.len() as u64).to_le_bytes());
+        input.0.extend_from_slice(self.name.as_bytes());
+        input.0.extend_from_slice(&normalized);
+
+        let digest = match self.algorithm {
+            Algorithm::Blake2b => {
+                let mut digest = [0u8; 32];
+                ffi::generichash(&mut digest, &input, Some(&self.key));
+                digest
+            }
+            Algorithm::HmacSha256 => ffi::hmacsha256(&input, &self.key),
+        };
+
+        let len = self.bits.div_ceil(8) as usize;
+        let mut index = digest[..len].to_vec();
+        if !self.bits.is_multiple_of(8) {
+            index[len - 1] &= 0xffu8 << (8 - self.bits % 8);
+        }
+        index.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl Drop for BlindIndex {
+    fn drop(&mut self) {
+        ffi::memzero(&mut self.key)
+    }
+}
+
+impl std::fmt::Debug for BlindIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlindIndex")
+            .field("name", &self.name)
+            .field("bits", &self.bits)
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
+/// A field encrypted with secretbox, together with its blind indexes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedField {
+    /// Base64-encoded nonce.
+    pub nonce: String,
+    /// Base64-encoded secretbox ciphertext.
+    pub ciphertext: String,
+    /// Hexadecimal blind indexes, by index name.
+    pub indexes: BTreeMap<String, String>,
+}
+
+/// Encrypts a field with secretbox and computes each of its blind indexes.
+///
+/// # Arguments
+///
+/// * `data` - The plaintext of the field.
+/// * `sb_key_b64` - The base64-encoded secret key for SecretBox, distinct from the index keys.
+/// * `indexes` - The blind indexes of the field.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Errors
+///
+/// Returns an error if the secretbox key cannot be base64-decoded or has an invalid length.
+pub fn encrypt_with_indexes(
+    data: &str,
+    sb_key_b64: &str,
+    indexes: &[&BlindIndex],
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<IndexedField> {
+    let context = crate::errors::operation(context, module_path!(), "encrypt_with_indexes");
+    let (nonce, ciphertext) = secretbox::crypt(data, sb_key_b64, context)?;
+    let indexes = indexes.iter().map(|index| (index.name.clone(), index.compute(data))).collect();
+    Ok(IndexedField { nonce, ciphertext, indexes })
+}
@@ -0,0 +1,361 @@
+//! Stable C ABI over [`crate::envelope`], for programs which cannot link Rust directly.
+//!
+//! The functions seal data into the envelope strings written by [`crate::envelope::encrypt`] and open them back, with a
+//! secretbox key encryption key ([`crate::envelope::SecretBoxKek`]) or a sealed-box keypair ([`crate::envelope::SealedBoxKek`]).
+//! Keys are given as NUL-terminated base64 strings, like everywhere else in the crate.
+//!
+//! Outputs go to buffers owned by the caller, sized beforehand with the length queries
+//! ([`cdumay_sodium_secretbox_sealed_len`], [`cdumay_sodium_sealedbox_sealed_len`] and [`cdumay_sodium_opened_len`]). Every
+//! fallible function returns a status: [`CDUMAY_SODIUM_OK`] or a negative code derived from the kind of the crate error
+//! (`InvalidConfiguration`, `ValidationError`, ...), whose description is then available from [`cdumay_sodium_last_error`].
+//! Panics never cross the ABI; they are reported as [`CDUMAY_SODIUM_ERR_UNKNOWN`].
+//!
+//! The library is built with `cargo rustc --release --features capi --crate-type cdylib`, and its header is the checked-in
+//! `include/cdumay_sodium.h`. cbindgen generates it into `OUT_DIR` on every build; it is copied to `include/` when the
+//! `CDUMAY_SODIUM_WRITE_HEADER` environment variable is set.
+
+use crate::envelope::{self, Envelope, KeyEncryptionProvider, SealedBoxKek, SecretBoxKek};
+use crate::fingerprint::FINGERPRINTBYTES;
+use crate::secretbox::Algorithm;
+use crate::{InvalidBase64, Zeroizing, decode_base64, errors, ffi};
+use cdumay_error::{InvalidConfiguration, ValidationError};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+
+/// The call succeeded.
+pub const CDUMAY_SODIUM_OK: c_int = 0;
+/// A pointer is NULL, a string is not valid UTF-8 or the algorithm is unknown.
+pub const CDUMAY_SODIUM_ERR_INVALID_ARGUMENT: c_int = -1;
+/// The output buffer is too small; nothing was written.
+pub const CDUMAY_SODIUM_ERR_BUFFER_TOO_SMALL: c_int = -2;
+/// An error of kind `InvalidConfiguration`: invalid key length, key encryption key mismatch, unsupported algorithm.
+pub const CDUMAY_SODIUM_ERR_INVALID_CONFIGURATION: c_int = -3;
+/// An error of kind `ValidationError`: malformed envelope, failed authentication.
+pub const CDUMAY_SODIUM_ERR_VALIDATION: c_int = -4;
+/// An `InvalidBase64` error: a key or an envelope field is not valid base64.
+pub const CDUMAY_SODIUM_ERR_BASE64: c_int = -5;
+/// An error of kind `UnknownError` (libsodium initialization failure), or any other failure.
+pub const CDUMAY_SODIUM_ERR_UNKNOWN: c_int = -6;
+
+/// XSalsa20-Poly1305 payloads: the `xsalsa20poly1305` algorithm of a `v3` envelope.
+pub const CDUMAY_SODIUM_XSALSA20POLY1305: u32 = 0;
+/// XChaCha20-Poly1305 payloads: the `xchacha20poly1305` algorithm of a `v3` envelope.
+pub const CDUMAY_SODIUM_XCHACHA20POLY1305: u32 = 1;
+
+/// Length of a data key wrapped by a [`SecretBoxKek`]: nonce, authentication tag and key.
+const SECRETBOX_WRAPPED_KEY_LEN: usize = ffi::SECRETBOX_NONCEBYTES + ffi::SECRETBOX_MACBYTES + ffi::SECRETBOX_KEYBYTES;
+/// Length of a data key wrapped by a [`SealedBoxKek`]: sealed-box overhead and key.
+const SEALEDBOX_WRAPPED_KEY_LEN: usize = ffi::BOX_SEALBYTES + ffi::SECRETBOX_KEYBYTES;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Why a call failed.
+enum Failure {
+    InvalidArgument(String),
+    BufferTooSmall { required: usize },
+    Error(cdumay_core::Error),
+}
+
+impl From<cdumay_core::Error> for Failure {
+    fn from(err: cdumay_core::Error) -> Self {
+        Failure::Error(err)
+    }
+}
+
+/// Returns the status reported for a crate error: [`CDUMAY_SODIUM_ERR_BASE64`] for the stable code of [`InvalidBase64`], else the
+/// status of its kind.
+fn status(err: &cdumay_core::Error) -> c_int {
+    if err.code() == InvalidBase64::new().code() {
+        return CDUMAY_SODIUM_ERR_BASE64;
+    }
+    match errors::kind_of(err) {
+        Some(kind) if kind == InvalidConfiguration => CDUMAY_SODIUM_ERR_INVALID_CONFIGURATION,
+        Some(kind) if kind == ValidationError => CDUMAY_SODIUM_ERR_VALIDATION,
+        _ => CDUMAY_SODIUM_ERR_UNKNOWN,
+    }
+}
+
+/// Runs the body of an exported function, recording the description of a failure for [`cdumay_sodium_last_error`].
+fn call(body: impl FnOnce() -> Result<(), Failure>) -> c_int {
+    let (status, message) = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => return CDUMAY_SODIUM_OK,
+        Ok(Err(Failure::InvalidArgument(message))) => (CDUMAY_SODIUM_ERR_INVALID_ARGUMENT, message),
+        Ok(Err(Failure::BufferTooSmall { required })) => {
+            (CDUMAY_SODIUM_ERR_BUFFER_TOO_SMALL, format!("Output buffer too small, required: {}", required))
+        }
+        Ok(Err(Failure::Error(err))) => (status(&err), err.to_string()),
+        Err(_) => (CDUMAY_SODIUM_ERR_UNKNOWN, "Unexpected panic".to_string()),
+    };
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+/// Borrows a NUL-terminated UTF-8 string.
+///
+/// # Safety
+///
+/// `ptr` must be NULL or point to a NUL-terminated string which outlives the returned reference.
+unsafe fn c_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if ptr.is_null() {
+        return Err(Failure::InvalidArgument(format!("'{}' is NULL", name)));
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| Failure::InvalidArgument(format!("'{}' is not valid UTF-8", name)))
+}
+
+/// Borrows `len` bytes, NULL being accepted for an empty input.
+///
+/// # Safety
+///
+/// `ptr` must be NULL with `len == 0`, or valid for reads of `len` bytes which outlive the returned reference.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize, name: &str) -> Result<&'a [u8], Failure> {
+    match ptr.is_null() {
+        true if len == 0 => Ok(&[]),
+        true => Err(Failure::InvalidArgument(format!("'{}' is NULL", name))),
+        false => Ok(unsafe { std::slice::from_raw_parts(ptr, len) }),
+    }
+}
+
+fn algorithm(algorithm: u32) -> Option<Algorithm> {
+    match algorithm {
+        CDUMAY_SODIUM_XSALSA20POLY1305 => Some(Algorithm::XSalsa20Poly1305),
+        CDUMAY_SODIUM_XCHACHA20POLY1305 => Some(Algorithm::XChaCha20Poly1305),
+        _ => None,
+    }
+}
+
+/// Length of the padded base64 encoding of `len` bytes.
+fn base64_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+/// Length of an envelope string, including the terminating NUL.
+fn sealed_len(kek_id_len: usize, algorithm: Algorithm, wrapped_key_len: usize, data_len: usize) -> usize {
+    "v3.".len()
+        + algorithm.id().len()
+        + 1
+        + 2 * FINGERPRINTBYTES
+        + 1
+        + kek_id_len
+        + 1
+        + base64_len(wrapped_key_len)
+        + 1
+        + base64_len(ffi::SECRETBOX_NONCEBYTES)
+        + 1
+        + base64_len(data_len + ffi::SECRETBOX_MACBYTES)
+        + 1
+}
+
+/// Parses a NUL-terminated envelope string.
+///
+/// # Safety
+///
+/// See [`c_str`].
+unsafe fn parse(envelope: *const c_char) -> Result<Envelope, Failure> {
+    let envelope = unsafe { c_str(envelope, "envelope") }?;
+    Ok(Envelope::parse(envelope, BTreeMap::new())?)
+}
+
+/// Seals `data` and writes the NUL-terminated envelope to `out`.
+///
+/// # Safety
+///
+/// `out` must be valid for writes of `out_len` bytes, and `data` for reads of `data_len` bytes.
+unsafe fn seal(
+    provider: &dyn KeyEncryptionProvider,
+    algorithm: Algorithm,
+    data: *const u8,
+    data_len: usize,
+    out: *mut c_char,
+    out_len: usize,
+) -> Result<(), Failure> {
+    let data = unsafe { bytes(data, data_len, "data") }?;
+    if out.is_null() {
+        return Err(Failure::InvalidArgument("'out' is NULL".to_string()));
+    }
+    let encoded = envelope::seal_bytes(data, provider, algorithm, BTreeMap::new())?.to_string();
+    if encoded.len() >= out_len {
+        return Err(Failure::BufferTooSmall { required: encoded.len() + 1 });
+    }
+    unsafe {
+        std::ptr::copy_nonoverlapping(encoded.as_ptr(), out.cast::<u8>(), encoded.len());
+        *out.add(encoded.len()) = 0;
+    }
+    Ok(())
+}
+
+/// Opens a NUL-terminated envelope into `out`, storing the plaintext length in `written`.
+///
+/// # Safety
+///
+/// `envelope` must be a NUL-terminated string, `out` valid for writes of `out_len` bytes and `written` valid for a write.
+unsafe fn open(
+    provider: &dyn KeyEncryptionProvider,
+    envelope: *const c_char,
+    out: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> Result<(), Failure> {
+    let envelope = unsafe { parse(envelope) }?;
+    if (out.is_null() && out_len > 0) || written.is_null() {
+        return Err(Failure::InvalidArgument("'out' or 'written' is NULL".to_string()));
+    }
+    let plaintext = Zeroizing(envelope::open_bytes(&envelope, provider, BTreeMap::new())?);
+    unsafe { *written = plaintext.len() };
+    if plaintext.len() > out_len {
+        return Err(Failure::BufferTooSmall { required: plaintext.len() });
+    }
+    if !plaintext.is_empty() {
+        unsafe { std::ptr::copy_nonoverlapping(plaintext.as_ptr(), out, plaintext.len()) };
+    }
+    Ok(())
+}
+
+/// Initializes libsodium (see [`crate::init`]). The other functions do it on their own; this one detects a failure early.
+#[unsafe(no_mangle)]
+pub extern "C" fn cdumay_sodium_init() -> c_int {
+    call(|| Ok(crate::init(BTreeMap::new())?))
+}
+
+/// Returns the description of the last failure of the calling thread, or NULL if no call failed yet.
+///
+/// The string is owned by the library and stays valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn cdumay_sodium_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+}
+
+/// Returns the buffer size, terminating NUL included, of the envelope sealing `data_len` bytes with a secretbox key
+/// encryption key whose identifier is `kek_id_len` bytes long, or 0 if the algorithm is unknown.
+#[unsafe(no_mangle)]
+pub extern "C" fn cdumay_sodium_secretbox_sealed_len(kek_id_len: usize, algorithm: u32, data_len: usize) -> usize {
+    self::algorithm(algorithm).map_or(0, |algorithm| sealed_len(kek_id_len, algorithm, SECRETBOX_WRAPPED_KEY_LEN, data_len))
+}
+
+/// Returns the buffer size, terminating NUL included, of the envelope sealing `data_len` bytes with a sealed-box key
+/// encryption key whose identifier is `kek_id_len` bytes long, or 0 if the algorithm is unknown.
+#[unsafe(no_mangle)]
+pub extern "C" fn cdumay_sodium_sealedbox_sealed_len(kek_id_len: usize, algorithm: u32, data_len: usize) -> usize {
+    self::algorithm(algorithm).map_or(0, |algorithm| sealed_len(kek_id_len, algorithm, SEALEDBOX_WRAPPED_KEY_LEN, data_len))
+}
+
+/// Stores in `len` the length of the plaintext sealed in `envelope`, without opening it.
+///
+/// # Safety
+///
+/// `envelope` must be a NUL-terminated string and `len` valid for a write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cdumay_sodium_opened_len(envelope: *const c_char, len: *mut usize) -> c_int {
+    call(|| {
+        let envelope = unsafe { parse(envelope) }?;
+        if len.is_null() {
+            return Err(Failure::InvalidArgument("'len' is NULL".to_string()));
+        }
+        let ciphertext = decode_base64(&envelope.ciphertext, "ciphertext", BTreeMap::new())?;
+        unsafe { *len = ciphertext.len().saturating_sub(ffi::SECRETBOX_MACBYTES) };
+        Ok(())
+    })
+}
+
+/// Seals `data` into an envelope with the secretbox key encryption key `kek_b64` identified by `kek_id`, and writes it to
+/// `out` as a NUL-terminated string.
+///
+/// # Safety
+///
+/// `kek_id` and `kek_b64` must be NUL-terminated strings, `data` valid for reads of `data_len` bytes (or NULL if empty) and `out`
+/// valid for writes of `out_len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cdumay_sodium_secretbox_seal(
+    kek_id: *const c_char,
+    kek_b64: *const c_char,
+    algorithm: u32,
+    data: *const u8,
+    data_len: usize,
+    out: *mut c_char,
+    out_len: usize,
+) -> c_int {
+    call(|| {
+        let kek = SecretBoxKek::new(unsafe { c_str(kek_id, "kek_id") }?, unsafe { c_str(kek_b64, "kek_b64") }?, BTreeMap::new())?;
+        let algorithm = self::algorithm(algorithm).ok_or_else(|| Failure::InvalidArgument(format!("Unknown algorithm {}", algorithm)))?;
+        unsafe { seal(&kek, algorithm, data, data_len, out, out_len) }
+    })
+}
+
+/// Opens an envelope sealed with the secretbox key encryption key `kek_b64` identified by `kek_id`, writes the plaintext to
+/// `out` and its length to `written`. If `out` is too small, only `written` is set.
+///
+/// # Safety
+///
+/// `kek_id`, `kek_b64` and `envelope` must be NUL-terminated strings, `out` valid for writes of `out_len` bytes and `written`
+/// valid for a write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cdumay_sodium_secretbox_open(
+    kek_id: *const c_char,
+    kek_b64: *const c_char,
+    envelope: *const c_char,
+    out: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> c_int {
+    call(|| {
+        let kek = SecretBoxKek::new(unsafe { c_str(kek_id, "kek_id") }?, unsafe { c_str(kek_b64, "kek_b64") }?, BTreeMap::new())?;
+        unsafe { open(&kek, envelope, out, out_len, written) }
+    })
+}
+
+/// Seals `data` into an envelope for the owner of the sealed-box public key `public_key_b64` identified by `kek_id`, and writes
+/// it to `out` as a NUL-terminated string.
+///
+/// # Safety
+///
+/// `kek_id` and `public_key_b64` must be NUL-terminated strings, `data` valid for reads of `data_len` bytes (or NULL if empty)
+/// and `out` valid for writes of `out_len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cdumay_sodium_sealedbox_seal(
+    kek_id: *const c_char,
+    public_key_b64: *const c_char,
+    algorithm: u32,
+    data: *const u8,
+    data_len: usize,
+    out: *mut c_char,
+    out_len: usize,
+) -> c_int {
+    call(|| {
+        let public_key_b64 = unsafe { c_str(public_key_b64, "public_key_b64") }?;
+        let kek = SealedBoxKek::new(unsafe { c_str(kek_id, "kek_id") }?, public_key_b64, BTreeMap::new())?;
+        let algorithm = self::algorithm(algorithm).ok_or_else(|| Failure::InvalidArgument(format!("Unknown algorithm {}", algorithm)))?;
+        unsafe { seal(&kek, algorithm, data, data_len, out, out_len) }
+    })
+}
+
+/// Opens an envelope sealed to the sealed-box keypair identified by `kek_id`, writes the plaintext to `out` and its length to
+/// `written`. If `out` is too small, only `written` is set.
+///
+/// # Safety
+///
+/// `kek_id`, `public_key_b64`, `private_key_b64` and `envelope` must be NUL-terminated strings, `out` valid for writes of
+/// `out_len` bytes and `written` valid for a write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cdumay_sodium_sealedbox_open(
+    kek_id: *const c_char,
+    public_key_b64: *const c_char,
+    private_key_b64: *const c_char,
+    envelope: *const c_char,
+    out: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> c_int {
+    call(|| {
+        let public_key_b64 = unsafe { c_str(public_key_b64, "public_key_b64") }?;
+        let private_key_b64 = unsafe { c_str(private_key_b64, "private_key_b64") }?;
+        let kek = SealedBoxKek::new(unsafe { c_str(kek_id, "kek_id") }?, public_key_b64, BTreeMap::new())?
+            .with_private_key(private_key_b64, BTreeMap::new())?;
+        unsafe { open(&kek, envelope, out, out_len, written) }
+    })
+}
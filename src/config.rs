@@ -0,0 +1,288 @@
+//! SOPS-style encrypted configuration documents: secret values are replaced by `ENC[...]` markers, while the structure of the
+//! document and its non-secret values stay readable for code review.
+//!
+//! The module works on `serde_value::Value` trees, so any format with a serde implementation (YAML, JSON, TOML...) can be handled
+//! by parsing the file into a `Value` first.
+//!
+//! Markers have the following forms, where `type` records the type of the original scalar (`str`, `int`, `uint`, `float`, `bool`
+//! or `bytes`) so it is restored on decryption:
+//!
+//! - `ENC[secretbox,key:<key_id>,nonce:<nonce_b64>,data:<ciphertext_b64>,type:<type>]`, produced with a [`Keyring`].
+//! - `ENC[sealedbox,data:<ciphertext_b64>,type:<type>]`, produced with a sealed-box public key.
+//!
+//! The ciphertext also authenticates the JSON pointer of the value in the document and its type: a marker moved under another key,
+//! or given another type, fails to decrypt.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde_value::Value;
+//! use cdumay_sodium::config::{decrypt_document, encrypt_document, ConfigKey};
+//! use cdumay_sodium::keyring::Keyring;
+//!
+//! let context = BTreeMap::<String, Value>::new();
+//! let keyring = Keyring::new("2024", "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=", context.clone()).unwrap();
+//! let mut database = BTreeMap::new();
+//! database.insert(Value::String("host".to_string()), Value::String("db.local".to_string()));
+//! database.insert(Value::String("password_secret".to_string()), Value::String("hunter2".to_string()));
+//! let document = Value::Map(database);
+//!
+//! let key = ConfigKey::Keyring(&keyring);
+//! let encrypted = encrypt_document(document.clone(), |name| name.ends_with("_secret"), key, context.clone()).unwrap();
+//! assert_ne!(encrypted, document);
+//! assert_eq!(decrypt_document(encrypted, &[key], context).unwrap(), document);
+//! ```
+
+use crate::keyring::{KEY_ID_SEPARATOR, Keyring};
+use crate::{Context, InvalidEnvelope, decode_base64, sealedbox};
+use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use cdumay_base64::base64::Engine;
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+/// Prefix of an encrypted marker.
+pub const MARKER_PREFIX: &str = "ENC[";
+
+/// Suffix of an encrypted marker.
+pub const MARKER_SUFFIX: &str = "]";
+
+/// Key used to encrypt or decrypt the markers of a document. All sealed-box keys are base64-encoded.
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigKey<'a> {
+    /// A secretbox keyring; encryption uses its active key, decryption the key named by the marker.
+    Keyring(&'a Keyring),
+    /// A sealed-box recipient public key, which can only encrypt.
+    SealedBoxPublic(&'a str),
+    /// A sealed-box recipient keypair, which can encrypt and decrypt.
+    SealedBoxKeypair {
+        /// Base64-encoded private key.
+        private_key_b64: &'a str,
+        /// Base64-encoded public key.
+        public_key_b64: &'a str,
+    },
+}
+
+/// Encrypts every scalar value found under a key selected by `is_secret`, for example `|name| name.ends_with("_secret")`.
+///
+/// A selected key protects its whole sub-tree: nested maps and sequences keep their structure, and each scalar inside is replaced by
+/// a marker. Values which are already markers, units and empty options are left untouched, so encrypting a document twice is a
+/// no-op.
+///
+/// # Arguments
+///
+/// * `value` - The document to protect.
+/// * `is_secret` - Selects the map keys whose values must be encrypted.
+/// * `key` - The key to encrypt with.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok(Value)` with secret values replaced by markers, or an error of type [`cdumay_core::Error`].
+///
+/// # Errors
+///
+/// Returns an error if the sealed-box public key is invalid. Errors carry the JSON pointer of the failing value under the `path` key
+/// of their details.
+pub fn encrypt_document<F>(value: Value, is_secret: F, key: ConfigKey<'_>, context: impl Into<Option<Context>>) -> cdumay_core::Result<Value>
+where
+    F: Fn(&str) -> bool,
+{
+    let context = crate::errors::operation(context, module_path!(), "encrypt_document");
+    let mut value = value;
+    encrypt_node(&mut value, false, &is_secret, key, &mut String::new(), &context)?;
+    Ok(value)
+}
+
+/// Decrypts every marker of the document in place, whatever the key it is stored under.
+///
+/// Secretbox markers are opened with the first [`ConfigKey::Keyring`] of `keys`, sealed-box markers with the first
+/// [`ConfigKey::SealedBoxKeypair`].
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - A marker is malformed, or no key of `keys` can open its algorithm ([`InvalidEnvelope`]).
+/// - A marker names a key which is not part of the keyring, or fails authentication.
+/// - The decrypted value does not match the recorded type.
+///
+/// Errors carry the JSON pointer of the failing marker under the `path` key of their details, never the value itself.
+pub fn decrypt_document(value: Value, keys: &[ConfigKey<'_>], context: impl Into<Option<Context>>) -> cdumay_core::Result<Value> {
+    let context = crate::errors::operation(context, module_path!(), "decrypt_document");
+    let mut value = value;
+    decrypt_node(&mut value, keys, &mut String::new(), &context)?;
+    Ok(value)
+}
+
+/// Returns `true` if the string is an `ENC[...]` marker.
+pub fn is_marker(value: &str) -> bool {
+    value.starts_with(MARKER_PREFIX) && value.ends_with(MARKER_SUFFIX)
+}
+
+fn path_context(path: &str, context: &BTreeMap<String, Value>) -> BTreeMap<String, Value> {
+    let mut context = context.clone();
+    context.insert("path".to_string(), Value::String(path.to_string()));
+    context
+}
+
+fn invalid_marker(message: &str, context: BTreeMap<String, Value>) -> cdumay_core::Error {
+    InvalidEnvelope::new().with_message(message.to_string()).with_details(context).into()
+}
+
+/// Calls `apply` on every child of a map or sequence, with its JSON pointer pushed onto `path`.
+fn for_each_child<F>(value: &mut Value, path: &mut String, mut apply: F) -> cdumay_core::Result<()>
+where
+    F: FnMut(Option<&str>, &mut Value, &mut String) -> cdumay_core::Result<()>,
+{
+    let len = path.len();
+    match value {
+        Value::Option(Some(inner)) | Value::Newtype(inner) => apply(None, inner, path)?,
+        Value::Seq(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push_str(&format!("/{}", index));
+                apply(None, item, path)?;
+                path.truncate(len);
+            }
+        }
+        Value::Map(entries) => {
+            for (name, item) in entries.iter_mut() {
+                let name = match name {
+                    Value::String(name) => Some(name.as_str()),
+                    _ => None,
+                };
+                path.push_str(&format!("/{}", name.unwrap_or_default().replace('~', "~0").replace('/', "~1")));
+                apply(name, item, path)?;
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn encrypt_node<F>(value: &mut Value, secret: bool, is_secret: &F, key: ConfigKey<'_>, path: &mut String, context: &BTreeMap<String, Value>) -> cdumay_core::Result<()>
+where
+    F: Fn(&str) -> bool,
+{
+    if secret && let Some((value_type, plaintext)) = to_plaintext(value) {
+        *value = Value::String(encrypt_value(value_type, &plaintext, path, key, path_context(path, context))?);
+        return Ok(());
+    }
+    for_each_child(value, path, |name, child, path| {
+        let secret = secret || name.is_some_and(is_secret);
+        encrypt_node(child, secret, is_secret, key, path, context)
+    })
+}
+
+fn decrypt_node(value: &mut Value, keys: &[ConfigKey<'_>], path: &mut String, context: &BTreeMap<String, Value>) -> cdumay_core::Result<()> {
+    if let Value::String(marker) = value
+        && is_marker(marker)
+    {
+        *value = decrypt_value(marker, keys, path, path_context(path, context))?;
+        return Ok(());
+    }
+    for_each_child(value, path, |_, child, path| decrypt_node(child, keys, path, context))
+}
+
+/// Returns the type tag and the textual form of a scalar, or `None` if the value is a container, empty or already a marker.
+fn to_plaintext(value: &Value) -> Option<(&'static str, String)> {
+    match value {
+        Value::String(value) if !is_marker(value) => Some(("str", value.clone())),
+        Value::Char(value) => Some(("str", value.to_string())),
+        Value::Bool(value) => Some(("bool", value.to_string())),
+        Value::I8(value) => Some(("int", value.to_string())),
+        Value::I16(value) => Some(("int", value.to_string())),
+        Value::I32(value) => Some(("int", value.to_string())),
+        Value::I64(value) => Some(("int", value.to_string())),
+        Value::U8(value) => Some(("uint", value.to_string())),
+        Value::U16(value) => Some(("uint", value.to_string())),
+        Value::U32(value) => Some(("uint", value.to_string())),
+        Value::U64(value) => Some(("uint", value.to_string())),
+        Value::F32(value) => Some(("float", value.to_string())),
+        Value::F64(value) => Some(("float", value.to_string())),
+        Value::Bytes(value) => Some(("bytes", BASE64_STANDARD.encode(value))),
+        _ => None,
+    }
+}
+
+fn from_plaintext(value_type: &str, plaintext: String, context: BTreeMap<String, Value>) -> cdumay_core::Result<Value> {
+    let value = match value_type {
+        "str" => Some(Value::String(plaintext)),
+        "bool" => plaintext.parse().ok().map(Value::Bool),
+        "int" => plaintext.parse().ok().map(Value::I64),
+        "uint" => plaintext.parse().ok().map(Value::U64),
+        "float" => plaintext.parse().ok().map(Value::F64),
+        "bytes" => Some(Value::Bytes(decode_base64(plaintext, "value", context.clone())?)),
+        _ => return Err(invalid_marker("Unknown marker value type", context)),
+    };
+    value.ok_or_else(|| invalid_marker("Decrypted value does not match the marker type", context))
+}
+
+/// Returns the prefix binding a plaintext to the JSON pointer and type of its value. The pointer is length-prefixed, so that distinct
+/// values never share a prefix.
+fn binding(path: &str, value_type: &str) -> String {
+    format!("{}:{}{}:", path.len(), path, value_type)
+}
+
+fn encrypt_value(
+    value_type: &str,
+    plaintext: &str,
+    path: &str,
+    key: ConfigKey<'_>,
+    context: BTreeMap<String, Value>,
+) -> cdumay_core::Result<String> {
+    let plaintext = format!("{}{}", binding(path, value_type), plaintext);
+    let plaintext = plaintext.as_str();
+    let fields = match key {
+        ConfigKey::Keyring(keyring) => {
+            let (nonce_b64, stamped) = keyring.crypt(plaintext, context)?;
+            let (key_id, data_b64) = stamped.split_once(KEY_ID_SEPARATOR).unwrap_or_default();
+            format!("secretbox,key:{},nonce:{},data:{}", key_id, nonce_b64, data_b64)
+        }
+        ConfigKey::SealedBoxPublic(public_key_b64) | ConfigKey::SealedBoxKeypair { public_key_b64, .. } => {
+            format!("sealedbox,data:{}", sealedbox::crypt(plaintext, public_key_b64, context)?)
+        }
+    };
+    Ok(format!("{}{},type:{}{}", MARKER_PREFIX, fields, value_type, MARKER_SUFFIX))
+}
+
+fn decrypt_value(marker: &str, keys: &[ConfigKey<'_>], path: &str, context: BTreeMap<String, Value>) -> cdumay_core::Result<Value> {
+    let inner = &marker[MARKER_PREFIX.len()..marker.len() - MARKER_SUFFIX.len()];
+    let mut parts = inner.split(',');
+    let algorithm = parts.next().unwrap_or_default();
+    let mut fields = BTreeMap::new();
+    for part in parts {
+        let (name, field) = part.split_once(':').ok_or_else(|| invalid_marker("Malformed marker field", context.clone()))?;
+        fields.insert(name, field);
+    }
+    let field = |name: &str| fields.get(name).copied().ok_or_else(|| invalid_marker(&format!("Missing marker field '{}'", name), context.clone()));
+    let value_type = field("type")?;
+    let plaintext = match algorithm {
+        "secretbox" => {
+            let keyring = keys
+                .iter()
+                .find_map(|key| match key {
+                    ConfigKey::Keyring(keyring) => Some(*keyring),
+                    _ => None,
+                })
+                .ok_or_else(|| invalid_marker("No keyring to open 'secretbox' marker", context.clone()))?;
+            let stamped = format!("{}{}{}", field("key")?, KEY_ID_SEPARATOR, field("data")?);
+            keyring.decrypt(&stamped, field("nonce")?, context.clone())?
+        }
+        "sealedbox" => {
+            let (private_key_b64, public_key_b64) = keys
+                .iter()
+                .find_map(|key| match key {
+                    ConfigKey::SealedBoxKeypair { private_key_b64, public_key_b64 } => Some((*private_key_b64, *public_key_b64)),
+                    _ => None,
+                })
+                .ok_or_else(|| invalid_marker("No keypair to open 'sealedbox' marker", context.clone()))?;
+            sealedbox::decrypt(field("data")?, private_key_b64, public_key_b64, context.clone())?
+        }
+        _ => return Err(invalid_marker("Unknown marker algorithm", context)),
+    };
+    match plaintext.strip_prefix(&binding(path, value_type)) {
+        Some(plaintext) => from_plaintext(value_type, plaintext.to_string(), context),
+        None => Err(invalid_marker("Marker was encrypted for another value", context)),
+    }
+}
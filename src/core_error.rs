@@ -0,0 +1,187 @@
+//! Errors of `no_std` builds, mirroring the parts of `cdumay_core` the crate relies on.
+//!
+//! Without the `std` feature neither `cdumay_core` nor `serde_value` is available. The crate errors are then generated by a
+//! [`define_errors!`] with the same syntax and builder methods, and converted into the [`Error`] below: it keeps the class, code
+//! and message of the `std` error, and its details are plain strings instead of `serde_value::Value`s.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+/// Details attached to an error.
+pub type Context = BTreeMap<String, String>;
+
+/// Result of the crate operations.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Category of an error, with its default code and message (the `no_std` counterpart of `cdumay_core::ErrorKind`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorKind {
+    name: &'static str,
+    code: u16,
+    description: &'static str,
+}
+
+impl ErrorKind {
+    /// Returns the name of the kind.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the default code of the errors of this kind.
+    pub const fn code(&self) -> u16 {
+        self.code
+    }
+
+    /// Returns the default message of the errors of this kind.
+    pub const fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Returns `"Client"` for 4xx codes and `"Server"` otherwise.
+    pub const fn side(&self) -> &'static str {
+        match self.code {
+            400..=499 => "Client",
+            _ => "Server",
+        }
+    }
+}
+
+#[allow(non_upper_case_globals)]
+pub(crate) const UnknownError: ErrorKind = ErrorKind { name: "UnknownError", code: 500, description: "Unexpected error" };
+#[allow(non_upper_case_globals)]
+pub(crate) const ValidationError: ErrorKind = ErrorKind { name: "ValidationError", code: 400, description: "Validation error" };
+#[allow(non_upper_case_globals)]
+pub(crate) const InvalidConfiguration: ErrorKind = ErrorKind { name: "InvalidConfiguration", code: 400, description: "Invalid Configuration" };
+
+/// An error of the crate: `<side>::<kind>::<name>` class, code, message and details.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    code: u16,
+    class: String,
+    message: String,
+    details: Context,
+}
+
+impl Error {
+    /// Creates an error.
+    pub fn new(code: u16, class: String, message: String, details: Context) -> Self {
+        Error { code, class, message, details }
+    }
+
+    /// Returns the code of the error.
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    /// Returns the class of the error, for example `Client::InvalidConfiguration::InvalidBoxKeyLength`.
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    /// Returns the message of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the details of the error.
+    pub fn details(&self) -> &Context {
+        &self.details
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} ({}): {}", self.class, self.code, self.message)
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Defines error types with the builder methods of `cdumay_core::define_errors!`, converting into [`Error`].
+macro_rules! define_errors {
+    ($($name:ident = $kind_spec:tt),* $(,)?) => {
+        $(
+            define_errors!(@parse $name = $kind_spec);
+        )*
+    };
+    (@parse $name:ident = $kind:ident) => {
+        define_errors!(@impl $name, $kind, $kind.code());
+    };
+    (@parse $name:ident = ($kind:ident, $code:expr)) => {
+        define_errors!(@impl $name, $kind, $code);
+    };
+    (@impl $name:ident, $kind:ident, $code:expr) => {
+        #[doc = concat!("Error : ", stringify!($name), " (Kind: `", stringify!($kind), "`)")]
+        #[derive(Debug, Clone, Default)]
+        pub struct $name {
+            code: Option<u16>,
+            message: Option<alloc::string::String>,
+            details: Option<$crate::Context>,
+        }
+
+        impl $name {
+            /// Creates a new error with the defaults of its kind.
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Represents a categorized error kind.
+            #[allow(non_upper_case_globals)]
+            pub const kind: $crate::ErrorKind = $kind;
+
+            /// Returns the code of the error.
+            pub fn code(&self) -> u16 {
+                self.code.unwrap_or($code)
+            }
+
+            /// Adds a custom code to the error.
+            pub fn with_code(mut self, code: u16) -> Self {
+                self.code = Some(code);
+                self
+            }
+
+            /// Returns the message of the error.
+            pub fn message(&self) -> alloc::string::String {
+                self.message.clone().unwrap_or_else(|| alloc::string::ToString::to_string(Self::kind.description()))
+            }
+
+            /// Adds a custom message to the error.
+            pub fn with_message(mut self, message: alloc::string::String) -> Self {
+                self.message = Some(message);
+                self
+            }
+
+            /// Returns a clone of the details map.
+            pub fn details(&self) -> $crate::Context {
+                self.details.clone().unwrap_or_default()
+            }
+
+            /// Adds a map of additional error details.
+            pub fn with_details(mut self, details: $crate::Context) -> Self {
+                self.details = Some(details);
+                self
+            }
+
+            /// Returns the error class.
+            pub fn class(&self) -> alloc::string::String {
+                alloc::format!("{}::{}::{}", Self::kind.side(), Self::kind.name(), stringify!($name))
+            }
+        }
+
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, "{} ({}): {}", self.class(), self.code(), self.message())
+            }
+        }
+
+        impl core::error::Error for $name {}
+
+        impl From<$name> for $crate::Error {
+            fn from(err: $name) -> $crate::Error {
+                $crate::Error::new(err.code(), err.class(), err.message(), err.details())
+            }
+        }
+    };
+}
+
+pub(crate) use define_errors;
@@ -0,0 +1,326 @@
+//! [`Encrypted<T>`] holds a value of any serializable type in encrypted form: the value is serialized (JSON or CBOR), then encrypted
+//! with secretbox or sealedbox.
+//!
+//! An `Encrypted<T>` itself serializes as an opaque string `<algorithm>:<format>:<base64 data>` (for example
+//! `sb:json:...`), so it can be nested inside larger documents and only be opened by holders of the key.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde::{Deserialize, Serialize};
+//! use serde_value::Value;
+//! use cdumay_sodium::encrypted::{Encrypted, Key};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Card {
+//!     number: String,
+//!     expiry: String,
+//! }
+//!
+//! let key = Key::SecretBox("llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=");
+//! let context = BTreeMap::<String, Value>::new();
+//! let card = Card { number: "4111111111111111".to_string(), expiry: "12/30".to_string() };
+//!
+//! let sealed = Encrypted::seal(&card, key, context.clone()).unwrap();
+//! assert!(sealed.to_string().starts_with("sb:json:"));
+//! assert_eq!(sealed.open(key, context).unwrap(), card);
+//! ```
+
+use crate::errors::{Detail, enrich};
+use crate::{Context, InvalidEnvelope, Zeroizing, decode_base64, sealedbox, secretbox};
+use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use cdumay_base64::base64::Engine;
+use cdumay_error::{DeserializationError, SerializationError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::error::Category;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+/// Serialization format of the value before encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// JSON, via `serde_json`.
+    Json,
+    /// CBOR, via `ciborium`; more compact for binary-heavy values.
+    Cbor,
+}
+
+impl Format {
+    fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Cbor => "cbor",
+        }
+    }
+}
+
+/// Encryption algorithm of an [`Encrypted`] value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Secretbox (XSalsa20-Poly1305) with a shared key.
+    SecretBox,
+    /// Sealed box to a recipient public key.
+    SealedBox,
+}
+
+impl Algorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            Algorithm::SecretBox => "sb",
+            Algorithm::SealedBox => "seal",
+        }
+    }
+}
+
+/// Key used to seal or open an [`Encrypted`] value. All keys are base64-encoded.
+#[derive(Debug, Clone, Copy)]
+pub enum Key<'a> {
+    /// A secretbox key, which can both seal and open.
+    SecretBox(&'a str),
+    /// A sealed-box recipient public key, which can only seal.
+    SealedBoxPublic(&'a str),
+    /// A sealed-box recipient keypair, which can seal and open.
+    SealedBoxKeypair {
+        /// Base64-encoded private key.
+        private_key_b64: &'a str,
+        /// Base64-encoded public key.
+        public_key_b64: &'a str,
+    },
+}
+
+impl Key<'_> {
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            Key::SecretBox(_) => Algorithm::SecretBox,
+            Key::SealedBoxPublic(_) | Key::SealedBoxKeypair { .. } => Algorithm::SealedBox,
+        }
+    }
+}
+
+/// A value of type `T` stored encrypted.
+pub struct Encrypted<T> {
+    algorithm: Algorithm,
+    format: Format,
+    data: Vec<u8>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Encrypted<T> {
+    /// Serializes `value` as JSON and encrypts it with `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized, or if the key is invalid.
+    pub fn seal(value: &T, key: Key<'_>, context: impl Into<Option<Context>>) -> cdumay_core::Result<Encrypted<T>> {
+        let context = crate::errors::operation(context, module_path!(), "Encrypted::seal");
+        Encrypted::seal_as(value, Format::Json, key, context)
+    }
+
+    /// Serializes `value` with the given format and encrypts it with `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value cannot be serialized, or if the key is invalid.
+    pub fn seal_as(value: &T, format: Format, key: Key<'_>, context: impl Into<Option<Context>>) -> cdumay_core::Result<Encrypted<T>> {
+        let context = crate::errors::operation(context, module_path!(), "Encrypted::seal_as");
+        crate::init(context.clone())?;
+        match key {
+            Key::SecretBox(key_b64) => {
+                let key_decoded = decode_base64(key_b64, "key", context.clone())?;
+                Encrypted::seal_secretbox(value, format, &secretbox::into_secretbox_key(key_decoded, context.clone())?, context)
+            }
+            Key::SealedBoxPublic(public_key_b64) | Key::SealedBoxKeypair { public_key_b64, .. } => {
+                let pub_key_decoded = decode_base64(public_key_b64, "public_key", context.clone())?;
+                let plaintext = Zeroizing(serialize(value, format, context.clone())?);
+                let data = sealedbox::seal_bytes(&plaintext, &pub_key_decoded, context)?;
+                Ok(Encrypted { algorithm: key.algorithm(), format, data, marker: PhantomData })
+            }
+        }
+    }
+
+    /// Decrypts and deserializes the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The key does not match the algorithm of the value, or is a public key only ([`InvalidEnvelope`]).
+    /// - The key is invalid or the value fails authentication.
+    /// - The plaintext cannot be deserialized into `T`.
+    pub fn open(&self, key: Key<'_>, context: impl Into<Option<Context>>) -> cdumay_core::Result<T> {
+        let context = crate::errors::operation(context, module_path!(), "Encrypted::open");
+        crate::init(context.clone())?;
+        let plaintext = match (self.algorithm, key) {
+            (Algorithm::SecretBox, Key::SecretBox(key_b64)) => {
+                let key_decoded = decode_base64(key_b64, "key", context.clone())?;
+                return self.open_secretbox(&secretbox::into_secretbox_key(key_decoded, context.clone())?, context);
+            }
+            (Algorithm::SealedBox, Key::SealedBoxKeypair { private_key_b64, public_key_b64 }) => {
+                let priv_key_decoded = Zeroizing(decode_base64(private_key_b64, "private_key", context.clone())?);
+                let pub_key_decoded = decode_base64(public_key_b64, "public_key", context.clone())?;
+                sealedbox::open_bytes(&self.data, &priv_key_decoded, &pub_key_decoded, context.clone())?
+            }
+            _ => return Err(self.key_mismatch(context)),
+        };
+        deserialize(&Zeroizing(plaintext), self.format, context)
+    }
+
+    /// Serializes `value` and seals it with a decoded secretbox key, so that callers sealing many values decode the key once.
+    pub(crate) fn seal_secretbox(value: &T, format: Format, key: &secretbox::Key, context: Context) -> cdumay_core::Result<Encrypted<T>> {
+        let plaintext = Zeroizing(serialize(value, format, context.clone())?);
+        let data = secretbox::seal_bytes(&plaintext, key.as_ref(), context)?;
+        Ok(Encrypted { algorithm: Algorithm::SecretBox, format, data, marker: PhantomData })
+    }
+
+    /// Opens a value sealed with a decoded secretbox key and deserializes it.
+    pub(crate) fn open_secretbox(&self, key: &secretbox::Key, context: Context) -> cdumay_core::Result<T> {
+        if self.algorithm != Algorithm::SecretBox {
+            return Err(self.key_mismatch(context));
+        }
+        let plaintext = Zeroizing(secretbox::open_bytes(&self.data, key.as_ref(), context.clone())?);
+        deserialize(&plaintext, self.format, context)
+    }
+
+    fn key_mismatch(&self, context: Context) -> cdumay_core::Error {
+        InvalidEnvelope::new()
+            .with_message(format!("Value sealed with '{}' cannot be opened with this key", self.algorithm.name()))
+            .with_details(context)
+            .into()
+    }
+}
+
+impl<T> Encrypted<T> {
+    /// Returns the encryption algorithm.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Returns the serialization format of the plaintext.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Parses the string form `<algorithm>:<format>:<base64 data>` of an encrypted value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidEnvelope`] error if the algorithm or format is unknown, or an error if the data cannot be base64-decoded.
+    pub fn parse(encrypted: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Encrypted<T>> {
+        let context = crate::errors::operation(context, module_path!(), "Encrypted::parse");
+        let invalid = |message: &str| -> cdumay_core::Error {
+            InvalidEnvelope::new()
+                .with_message(message.to_string())
+                .with_details(context.clone())
+                .into()
+        };
+        let mut parts = encrypted.splitn(3, ':');
+        let algorithm = match parts.next() {
+            Some("sb") => Algorithm::SecretBox,
+            Some("seal") => Algorithm::SealedBox,
+            _ => return Err(invalid("Unknown encryption algorithm")),
+        };
+        let format = match parts.next() {
+            Some("json") => Format::Json,
+            Some("cbor") => Format::Cbor,
+            _ => return Err(invalid("Unknown serialization format")),
+        };
+        let data_b64 = parts.next().ok_or_else(|| invalid("Missing encrypted data"))?;
+        let data = decode_base64(data_b64, "data", context.clone())?;
+        Ok(Encrypted { algorithm, format, data, marker: PhantomData })
+    }
+}
+
+impl<T> Clone for Encrypted<T> {
+    fn clone(&self) -> Self {
+        Encrypted { algorithm: self.algorithm, format: self.format, data: self.data.clone(), marker: PhantomData }
+    }
+}
+
+impl<T> PartialEq for Encrypted<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.algorithm == other.algorithm && self.format == other.format && self.data == other.data
+    }
+}
+
+impl<T> std::fmt::Debug for Encrypted<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Encrypted")
+            .field("algorithm", &self.algorithm)
+            .field("format", &self.format)
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+impl<T> std::fmt::Display for Encrypted<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.algorithm.name(), self.format.name(), BASE64_STANDARD.encode(&self.data))
+    }
+}
+
+impl<T> Serialize for Encrypted<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for Encrypted<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encrypted = String::deserialize(deserializer)?;
+        Encrypted::parse(&encrypted, BTreeMap::new()).map_err(serde::de::Error::custom)
+    }
+}
+
+fn serialize<T: Serialize>(value: &T, format: Format, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+    let result = match format {
+        Format::Json => serde_json::to_vec(value).map_err(|err| json_details(&err)),
+        Format::Cbor => {
+            let mut buffer = Vec::new();
+            ciborium::into_writer(value, &mut buffer).map(|_| buffer).map_err(|err| match err {
+                ciborium::ser::Error::Io(_) => vec![("category", Detail::Text("io"))],
+                ciborium::ser::Error::Value(_) => vec![("category", Detail::Text("data"))],
+            })
+        }
+    };
+    result.map_err(|details| {
+        SerializationError::new()
+            .with_message(format!("Failed to serialize the value as {}", format.name()))
+            .with_details(error_details(context, format, &details))
+            .into()
+    })
+}
+
+fn deserialize<T: DeserializeOwned>(data: &[u8], format: Format, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<T> {
+    let result = match format {
+        Format::Json => serde_json::from_slice(data).map_err(|err| json_details(&err)),
+        Format::Cbor => ciborium::from_reader(data).map_err(|err| match err {
+            ciborium::de::Error::Io(_) => vec![("category", Detail::Text("io"))],
+            ciborium::de::Error::Syntax(offset) => vec![("category", Detail::Text("syntax")), ("offset", Detail::Length(offset))],
+            ciborium::de::Error::Semantic(Some(offset), _) => vec![("category", Detail::Text("data")), ("offset", Detail::Length(offset))],
+            ciborium::de::Error::Semantic(None, _) => vec![("category", Detail::Text("data"))],
+            ciborium::de::Error::RecursionLimitExceeded => vec![("category", Detail::Text("recursion_limit"))],
+        }),
+    };
+    result.map_err(|details| {
+        DeserializationError::new()
+            .with_message(format!("Failed to deserialize the plaintext as {}", format.name()))
+            .with_details(error_details(context, format, &details))
+            .into()
+    })
+}
+
+/// Returns the category and position of a `serde_json` error. Its message is left out: it may quote the plaintext.
+fn json_details(err: &serde_json::Error) -> Vec<(&'static str, Detail<'static>)> {
+    let category = match err.classify() {
+        Category::Io => "io",
+        Category::Syntax => "syntax",
+        Category::Data => "data",
+        Category::Eof => "eof",
+    };
+    vec![("category", Detail::Text(category)), ("line", Detail::Length(err.line())), ("column", Detail::Length(err.column()))]
+}
+
+fn error_details(context: BTreeMap<String, serde_value::Value>, format: Format, details: &[(&str, Detail<'_>)]) -> Context {
+    enrich(enrich(context, &[("format", Detail::Text(format.name()))]), details)
+}
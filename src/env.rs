@@ -0,0 +1,243 @@
+//! Secrets passed to containers as environment variables, kept encrypted at rest in deployment manifests.
+//!
+//! An encrypted variable holds a prefixed value:
+//!
+//! - `sodium:sb:<nonce_b64>:<ciphertext_b64>`, opened with a secretbox key.
+//! - `sodium:seal:<ciphertext_b64>`, opened with a sealed-box keypair.
+//!
+//! Any other value is returned unchanged, so plain and encrypted variables can be mixed. Keys are read from a [`KeySource`]: by
+//! default the `SODIUM_SECRETBOX_KEY`, `SODIUM_SEALEDBOX_PRIVATE_KEY` and `SODIUM_SEALEDBOX_PUBLIC_KEY` variables, or the files
+//! named by the same variables suffixed with `_FILE` (as for Docker secrets).
+//!
+//! Errors carry the name of the variable under the `variable` key of their details, never its value.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde_value::Value;
+//! use cdumay_sodium::env::{EnvKeys, KeySource, encrypt_secretbox};
+//!
+//! let key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+//! let context = BTreeMap::<String, Value>::new();
+//! let encrypted = encrypt_secretbox("hunter2", key_b64, context.clone()).unwrap();
+//! assert!(encrypted.starts_with("sodium:sb:"));
+//!
+//! unsafe {
+//!     std::env::set_var("DOC_SECRETBOX_KEY", key_b64);
+//!     std::env::set_var("DOC_DB_PASSWORD", &encrypted);
+//! }
+//! let keys = EnvKeys::default().with_secretbox_key(KeySource::Env("DOC_SECRETBOX_KEY".to_string()));
+//! assert_eq!(keys.get_decrypted("DOC_DB_PASSWORD", context).unwrap(), "hunter2");
+//! ```
+
+use crate::{Context, EnvironmentVariableNotFound, InvalidContent, InvalidEnvelope, KeyFileUnreadable, Zeroizing, sealedbox, secretbox};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Prefix of a value encrypted with secretbox.
+pub const SECRETBOX_PREFIX: &str = "sodium:sb:";
+
+/// Prefix of a value encrypted with sealedbox.
+pub const SEALEDBOX_PREFIX: &str = "sodium:seal:";
+
+/// Context key holding the name of the variable being read.
+const VARIABLE: &str = "variable";
+
+/// Context key holding the name of the variable a key is loaded from.
+const KEY_VARIABLE: &str = "key_variable";
+
+/// Suffix of the variable naming a file which holds the value, when the variable itself is not set.
+pub const FILE_SUFFIX: &str = "_FILE";
+
+/// Variable holding the default secretbox key.
+pub const SECRETBOX_KEY_VAR: &str = "SODIUM_SECRETBOX_KEY";
+
+/// Variable holding the default sealed-box private key.
+pub const SEALEDBOX_PRIVATE_KEY_VAR: &str = "SODIUM_SEALEDBOX_PRIVATE_KEY";
+
+/// Variable holding the default sealed-box public key.
+pub const SEALEDBOX_PUBLIC_KEY_VAR: &str = "SODIUM_SEALEDBOX_PUBLIC_KEY";
+
+/// Location of a base64-encoded key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// An environment variable, or the file named by the same variable suffixed with [`FILE_SUFFIX`] if it is not set.
+    Env(String),
+    /// A file; surrounding whitespace is ignored.
+    File(PathBuf),
+}
+
+impl KeySource {
+    /// Loads the key, without its surrounding whitespace. The key is wiped from memory when the returned buffer is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`EnvironmentVariableNotFound`] error if neither the variable nor its `_FILE` variant is set, or a
+    /// [`KeyFileUnreadable`] error if the file cannot be read.
+    pub fn load(&self, context: impl Into<Option<Context>>) -> cdumay_core::Result<Zeroizing> {
+        let context = crate::errors::operation(context, module_path!(), "KeySource::load");
+        match self {
+            KeySource::File(path) => read_file(path, context),
+            KeySource::Env(name) => match var(name, KEY_VARIABLE, context.clone())? {
+                Some(value) => {
+                    let value = Zeroizing(value.into_bytes());
+                    Ok(Zeroizing(value.trim_ascii().to_vec()))
+                }
+                None => {
+                    let file_name = format!("{}{}", name, FILE_SUFFIX);
+                    match var(&file_name, KEY_VARIABLE, context.clone())? {
+                        Some(path) => read_file(Path::new(&path), context),
+                        None => Err(not_found(name, KEY_VARIABLE, context)),
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Key sources used to open encrypted variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvKeys {
+    secretbox_key: KeySource,
+    sealedbox_private_key: KeySource,
+    sealedbox_public_key: KeySource,
+}
+
+impl Default for EnvKeys {
+    /// Reads keys from [`SECRETBOX_KEY_VAR`], [`SEALEDBOX_PRIVATE_KEY_VAR`] and [`SEALEDBOX_PUBLIC_KEY_VAR`].
+    fn default() -> Self {
+        EnvKeys {
+            secretbox_key: KeySource::Env(SECRETBOX_KEY_VAR.to_string()),
+            sealedbox_private_key: KeySource::Env(SEALEDBOX_PRIVATE_KEY_VAR.to_string()),
+            sealedbox_public_key: KeySource::Env(SEALEDBOX_PUBLIC_KEY_VAR.to_string()),
+        }
+    }
+}
+
+impl EnvKeys {
+    /// Sets the source of the secretbox key.
+    pub fn with_secretbox_key(mut self, source: KeySource) -> Self {
+        self.secretbox_key = source;
+        self
+    }
+
+    /// Sets the sources of the sealed-box keypair.
+    pub fn with_sealedbox_keypair(mut self, private_key: KeySource, public_key: KeySource) -> Self {
+        self.sealedbox_private_key = private_key;
+        self.sealedbox_public_key = public_key;
+        self
+    }
+
+    /// Reads the variable `name` and decrypts it if it holds an encrypted value. Keys are only loaded when needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The variable is not set ([`EnvironmentVariableNotFound`]) or is not valid unicode ([`InvalidContent`]).
+    /// - The encrypted value is malformed ([`InvalidEnvelope`]).
+    /// - The key cannot be loaded, or the value cannot be decrypted with it.
+    ///
+    /// The variable name is added to the context under the `variable` key; the variable of a missing key is recorded under
+    /// `key_variable`.
+    pub fn get_decrypted(&self, name: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+        let context = crate::errors::operation(context, module_path!(), "EnvKeys::get_decrypted");
+        let mut context = context;
+        context.insert(VARIABLE.to_string(), serde_value::Value::String(name.to_string()));
+        let value = var(name, VARIABLE, context.clone())?.ok_or_else(|| not_found(name, VARIABLE, context.clone()))?;
+        self.decrypt(&value, context)
+    }
+
+    /// Decrypts a value read from the environment, or returns it unchanged if it is not prefixed.
+    ///
+    /// # Errors
+    ///
+    /// See [`EnvKeys::get_decrypted`].
+    pub fn decrypt(&self, value: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+        let context = crate::errors::operation(context, module_path!(), "EnvKeys::decrypt");
+        if let Some(encrypted) = value.strip_prefix(SECRETBOX_PREFIX) {
+            let (nonce_b64, data_b64) = encrypted.split_once(':').ok_or_else(|| -> cdumay_core::Error {
+                InvalidEnvelope::new()
+                    .with_message(format!("Expected '{}<nonce>:<data>'", SECRETBOX_PREFIX))
+                    .with_details(context.clone())
+                    .into()
+            })?;
+            let key_b64 = self.secretbox_key.load(context.clone())?;
+            secretbox::decrypt(data_b64, key_str(&key_b64), nonce_b64, context)
+        } else if let Some(data_b64) = value.strip_prefix(SEALEDBOX_PREFIX) {
+            let private_key_b64 = self.sealedbox_private_key.load(context.clone())?;
+            let public_key_b64 = self.sealedbox_public_key.load(context.clone())?;
+            sealedbox::decrypt(data_b64, key_str(&private_key_b64), key_str(&public_key_b64), context)
+        } else {
+            Ok(value.to_string())
+        }
+    }
+}
+
+/// Reads the variable `name` with the default [`EnvKeys`] and decrypts it if it holds an encrypted value.
+///
+/// # Errors
+///
+/// See [`EnvKeys::get_decrypted`].
+pub fn get_decrypted(name: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "get_decrypted");
+    EnvKeys::default().get_decrypted(name, context)
+}
+
+/// Encrypts a value with secretbox into the `sodium:sb:<nonce_b64>:<ciphertext_b64>` form, for deployment manifests.
+///
+/// # Errors
+///
+/// Returns an error if the key cannot be base64-decoded or has an invalid length.
+pub fn encrypt_secretbox(data: &str, sb_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "encrypt_secretbox");
+    let (nonce_b64, data_b64) = secretbox::crypt(data, sb_key_b64, context)?;
+    Ok(format!("{}{}:{}", SECRETBOX_PREFIX, nonce_b64, data_b64))
+}
+
+/// Encrypts a value with sealedbox into the `sodium:seal:<ciphertext_b64>` form, for deployment manifests.
+///
+/// # Errors
+///
+/// Returns an error if the public key cannot be base64-decoded or has an invalid length.
+pub fn encrypt_sealedbox(data: &str, public_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "encrypt_sealedbox");
+    Ok(format!("{}{}", SEALEDBOX_PREFIX, sealedbox::crypt(data, public_key_b64, context)?))
+}
+
+fn var(name: &str, field: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Option<String>> {
+    match std::env::var(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => {
+            let mut context = context;
+            context.insert(field.to_string(), serde_value::Value::String(name.to_string()));
+            Err(InvalidContent::new()
+                .with_message("Environment variable is not valid unicode".to_string())
+                .with_details(context)
+                .into())
+        }
+    }
+}
+
+fn not_found(name: &str, field: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Error {
+    let mut context = context;
+    context.insert(field.to_string(), serde_value::Value::String(name.to_string()));
+    EnvironmentVariableNotFound::new()
+        .with_message("Environment variable is not set".to_string())
+        .with_details(context)
+        .into()
+}
+
+/// Returns a key loaded by [`KeySource::load`] as a string; keys come from strings, so the bytes are valid UTF-8.
+fn key_str(key: &Zeroizing) -> &str {
+    std::str::from_utf8(key).expect("keys are loaded from UTF-8 strings")
+}
+
+fn read_file(path: &Path, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Zeroizing> {
+    std::fs::read_to_string(path).map(|key| Zeroizing(Zeroizing(key.into_bytes()).trim_ascii().to_vec())).map_err(|err| {
+        let mut context = context;
+        context.insert("path".to_string(), serde_value::Value::String(path.display().to_string()));
+        KeyFileUnreadable::new().with_message(err.to_string()).with_details(context).into()
+    })
+}
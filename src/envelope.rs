@@ -0,0 +1,425 @@
+//! Envelope encryption follows the KMS data-key pattern: every object is encrypted with a fresh data encryption key (DEK), and that
+//! DEK is wrapped by a long-lived key encryption key (KEK). Only the small wrapped DEK depends on the KEK, so the KEK can live in a
+//! KMS or HSM and never touch the payload.
+//!
+//! The KEK is abstracted by the [`KeyEncryptionProvider`] trait. Two local implementations are provided:
+//!
+//! * [`SecretBoxKek`]: the DEK is wrapped with secretbox under a shared symmetric KEK.
+//! * [`SealedBoxKek`]: the DEK is sealed to a public key; only the owner of the private key can unwrap it.
+//!
+//! A cloud KMS client can be plugged in by implementing the same trait.
+//!
+//! The payload is sealed with XSalsa20-Poly1305 by default, or with XChaCha20-Poly1305 through [`encrypt_with_algorithm`]. The
+//! algorithm is recorded in the envelope, so stores mixing both decrypt with [`decrypt`] alone.
+//!
+//! Envelopes also record the [fingerprint](crate::fingerprint) of the KEK when the provider has one, so that a KEK replaced under the
+//! same identifier is reported as a [`KeyEncryptionKeyMismatch`] instead of a failed decryption. The header (algorithm, KEK
+//! identifier and fingerprint) is bound to the payload: the payload key is derived from the data key and the header, so an envelope
+//! whose header was altered fails authentication.
+
+use crate::errors::{Detail, authentication_failed, enrich, with_key_fingerprint};
+use crate::fingerprint::Fingerprint;
+use crate::memory::SecureBuffer;
+use crate::{Context, InvalidEnvelope, KeyEncryptionKeyMismatch, Zeroizing, decode_base64, ffi, sealedbox, secretbox, vec_to_string};
+use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use cdumay_base64::base64::Engine;
+use std::collections::BTreeMap;
+
+const ENVELOPE_VERSION: &str = "v1";
+/// Version of envelopes recording their secretbox algorithm.
+const ENVELOPE_VERSION_ALGORITHM: &str = "v2";
+/// Version of envelopes recording their secretbox algorithm and the fingerprint of their KEK, bound to the payload.
+const ENVELOPE_VERSION_FINGERPRINT: &str = "v3";
+/// Domain-separation label of the header hashed into the payload key of `v3` envelopes.
+const HEADER_LABEL: &[u8] = b"cdumay_sodium.envelope.v3.header";
+
+/// Wraps and unwraps data encryption keys with a key encryption key.
+pub trait KeyEncryptionProvider {
+    /// Returns the identifier of the key encryption key, stored alongside each envelope.
+    fn kek_id(&self) -> &str;
+
+    /// Returns the fingerprint of the key encryption key, stored alongside each envelope, if the provider can compute one.
+    fn kek_fingerprint(&self) -> Option<Fingerprint> {
+        None
+    }
+
+    /// Wraps (encrypts) a data encryption key.
+    fn wrap_key(&self, data_key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>>;
+
+    /// Unwraps (decrypts) a data encryption key previously returned by [`KeyEncryptionProvider::wrap_key`].
+    fn unwrap_key(&self, wrapped_key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>>;
+}
+
+/// A symmetric key encryption key wrapping data keys with secretbox. The KEK is kept in a [`SecureBuffer`].
+pub struct SecretBoxKek {
+    id: String,
+    key: SecureBuffer,
+    fingerprint: Fingerprint,
+}
+
+impl SecretBoxKek {
+    /// Creates a provider from a KEK identifier and a base64-encoded secretbox key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be base64-decoded or does not have the secretbox key length.
+    pub fn new(id: &str, key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<SecretBoxKek> {
+        let context = crate::errors::operation(context, module_path!(), "SecretBoxKek::new");
+        let key_decoded = decode_base64(key_b64, "key", context.clone())?;
+        let key = secretbox::into_secretbox_key(key_decoded, context.clone())?;
+        Ok(SecretBoxKek { id: id.to_string(), key: SecureBuffer::from_slice(key.as_ref(), context)?, fingerprint: key.fingerprint() })
+    }
+
+    /// Creates a provider from a KEK identifier and a secretbox key already held in secure memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`crate::InvalidBoxKeyLength`] error if the buffer does not have the secretbox key length.
+    pub fn from_secure_key(id: &str, key: SecureBuffer, context: impl Into<Option<Context>>) -> cdumay_core::Result<SecretBoxKek> {
+        let context = crate::errors::operation(context, module_path!(), "SecretBoxKek::from_secure_key");
+        ffi::secretbox_key(&key.read(), context)?;
+        let fingerprint = Fingerprint::secretbox_key(&key.read());
+        Ok(SecretBoxKek { id: id.to_string(), key, fingerprint })
+    }
+
+    /// Returns the fingerprint of the KEK.
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+}
+
+impl KeyEncryptionProvider for SecretBoxKek {
+    fn kek_id(&self) -> &str {
+        &self.id
+    }
+
+    fn kek_fingerprint(&self) -> Option<Fingerprint> {
+        Some(self.fingerprint)
+    }
+
+    fn wrap_key(&self, data_key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        secretbox::seal_bytes(data_key, &self.key.read(), with_key_fingerprint(context, self.fingerprint))
+    }
+
+    fn unwrap_key(&self, wrapped_key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        let key = self.key.read();
+        secretbox::open_bytes(wrapped_key, &key, with_key_fingerprint(context, self.fingerprint))
+    }
+}
+
+/// An asymmetric key encryption key sealing data keys to a public key.
+///
+/// A provider built only from the public key can encrypt but not decrypt, which suits write-only producers. The private key is
+/// kept in a [`SecureBuffer`].
+pub struct SealedBoxKek {
+    id: String,
+    public_key: Vec<u8>,
+    private_key: Option<SecureBuffer>,
+}
+
+impl SealedBoxKek {
+    /// Creates a wrap-only provider from a KEK identifier and a base64-encoded public key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be base64-decoded.
+    pub fn new(id: &str, public_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<SealedBoxKek> {
+        let context = crate::errors::operation(context, module_path!(), "SealedBoxKek::new");
+        let public_key = decode_base64(public_key_b64, "public_key", context.clone())?;
+        ffi::box_public_key(&public_key, context)?;
+        Ok(SealedBoxKek { id: id.to_string(), public_key, private_key: None })
+    }
+
+    /// Adds the base64-encoded private key, allowing the provider to unwrap data keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be base64-decoded or has an invalid length, a [`KeyEncryptionKeyMismatch`] error if it is
+    /// not the private key of the public key given to [`SealedBoxKek::new`], or a [`crate::SecureMemoryAllocationFailed`] error if
+    /// the secure buffer cannot be allocated.
+    pub fn with_private_key(mut self, private_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<SealedBoxKek> {
+        let context = crate::errors::operation(context, module_path!(), "SealedBoxKek::with_private_key");
+        crate::init(context.clone())?;
+        let private_key = Zeroizing(decode_base64(private_key_b64, "private_key", context.clone())?);
+        if !ffi::memcmp(&ffi::scalarmult_base(ffi::box_secret_key(&private_key, context.clone())?), &self.public_key) {
+            return Err(KeyEncryptionKeyMismatch::new()
+                .with_message(format!("Private key does not match the public key of key encryption key '{}'", self.id))
+                .with_details(with_key_fingerprint(context, self.fingerprint()))
+                .into());
+        }
+        self.private_key = Some(SecureBuffer::from_slice(&private_key, context)?);
+        Ok(self)
+    }
+
+    /// Returns the fingerprint of the public key, which is the fingerprint of the KEK.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::public_key(&self.public_key)
+    }
+}
+
+impl KeyEncryptionProvider for SealedBoxKek {
+    fn kek_id(&self) -> &str {
+        &self.id
+    }
+
+    fn kek_fingerprint(&self) -> Option<Fingerprint> {
+        Some(self.fingerprint())
+    }
+
+    fn wrap_key(&self, data_key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        sealedbox::seal_bytes(data_key, &self.public_key, context)
+    }
+
+    fn unwrap_key(&self, wrapped_key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        match &self.private_key {
+            Some(private_key) => sealedbox::open_bytes(wrapped_key, &private_key.read(), &self.public_key, context),
+            None => Err(KeyEncryptionKeyMismatch::new()
+                .with_message(format!("Key encryption key '{}' has no private key to unwrap data keys", self.id))
+                .with_details(context)
+                .into()),
+        }
+    }
+}
+
+/// An encrypted object: the wrapped data key, the identifier of the key that wrapped it and the payload ciphertext.
+///
+/// Its string form is `v3.<algorithm>.<kek_fingerprint>.<kek_id>.<wrapped_key>.<nonce>.<ciphertext>` where binary fields are
+/// base64-encoded and the fingerprint is written in hex, or left empty for providers without one. The KEK identifier may itself
+/// contain dots.
+///
+/// Envelopes written by earlier releases, `v1.<kek_id>.<wrapped_key>.<nonce>.<ciphertext>` and
+/// `v2.<algorithm>.<kek_id>.<wrapped_key>.<nonce>.<ciphertext>`, are still read; their header is not authenticated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    /// Secretbox algorithm of the payload.
+    pub algorithm: secretbox::Algorithm,
+    /// Identifier of the key encryption key.
+    pub kek_id: String,
+    /// Fingerprint of the key encryption key, absent from `v1` and `v2` envelopes.
+    pub kek_fingerprint: Option<Fingerprint>,
+    /// Whether the header is bound to the payload, as in `v3` envelopes. It is `false` for `v1` and `v2` envelopes, which are
+    /// written back in their own form, without fingerprint.
+    pub authenticated_header: bool,
+    /// Base64-encoded wrapped data key.
+    pub wrapped_key: String,
+    /// Base64-encoded secretbox nonce.
+    pub nonce: String,
+    /// Base64-encoded secretbox ciphertext.
+    pub ciphertext: String,
+}
+
+impl Envelope {
+    /// Parses the string form of an envelope.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidEnvelope`] error if the string is not a `v1`, `v2` or `v3` envelope, or an [`crate::UnsupportedAlgorithm`]
+    /// error if the algorithm is unknown.
+    pub fn parse(envelope: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Envelope> {
+        let context = crate::errors::operation(context, module_path!(), "Envelope::parse");
+        let invalid = |message: &str| -> cdumay_core::Error {
+            InvalidEnvelope::new()
+                .with_message(message.to_string())
+                .with_details(context.clone())
+                .into()
+        };
+        let mut parts = envelope.rsplitn(4, '.');
+        let ciphertext = parts.next().ok_or_else(|| invalid("Missing ciphertext"))?;
+        let nonce = parts.next().ok_or_else(|| invalid("Missing nonce"))?;
+        let wrapped_key = parts.next().ok_or_else(|| invalid("Missing wrapped key"))?;
+        let header = parts.next().ok_or_else(|| invalid("Missing key encryption key id"))?;
+        let (version, rest) = header.split_once('.').ok_or_else(|| invalid("Missing key encryption key id"))?;
+        let (algorithm, kek_fingerprint, kek_id, authenticated_header) = match version {
+            ENVELOPE_VERSION => (secretbox::Algorithm::XSalsa20Poly1305, None, rest, false),
+            ENVELOPE_VERSION_ALGORITHM => {
+                let (algorithm, kek_id) = rest.split_once('.').ok_or_else(|| invalid("Missing key encryption key id"))?;
+                (secretbox::Algorithm::from_id(algorithm, context.clone())?, None, kek_id, false)
+            }
+            ENVELOPE_VERSION_FINGERPRINT => {
+                let (algorithm, rest) = rest.split_once('.').ok_or_else(|| invalid("Missing key encryption key fingerprint"))?;
+                let (fingerprint, kek_id) = rest.split_once('.').ok_or_else(|| invalid("Missing key encryption key id"))?;
+                let fingerprint = match fingerprint.is_empty() {
+                    true => None,
+                    false => Some(Fingerprint::parse(fingerprint, context.clone()).map_err(|_| invalid("Invalid key encryption key fingerprint"))?),
+                };
+                (secretbox::Algorithm::from_id(algorithm, context.clone())?, fingerprint, kek_id, true)
+            }
+            _ => return Err(invalid("Unsupported envelope version")),
+        };
+        Ok(Envelope {
+            algorithm,
+            kek_id: kek_id.to_string(),
+            kek_fingerprint,
+            authenticated_header,
+            wrapped_key: wrapped_key.to_string(),
+            nonce: nonce.to_string(),
+            ciphertext: ciphertext.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for Envelope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.authenticated_header, self.algorithm) {
+            (true, algorithm) => {
+                let fingerprint = self.kek_fingerprint.map(|fingerprint| fingerprint.to_string()).unwrap_or_default();
+                write!(f, "{}.{}.{}.", ENVELOPE_VERSION_FINGERPRINT, algorithm.id(), fingerprint)?
+            }
+            (false, secretbox::Algorithm::XSalsa20Poly1305) => write!(f, "{}.", ENVELOPE_VERSION)?,
+            (false, algorithm) => write!(f, "{}.{}.", ENVELOPE_VERSION_ALGORITHM, algorithm.id())?,
+        }
+        write!(f, "{}.{}.{}.{}", self.kek_id, self.wrapped_key, self.nonce, self.ciphertext)
+    }
+}
+
+/// Encrypts data under a fresh data key wrapped by the given key encryption provider.
+///
+/// # Arguments
+///
+/// * `data` - The plaintext data to encrypt as a UTF-8 string.
+/// * `provider` - The key encryption provider wrapping the data key.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok(Envelope)` holding the KEK id, the wrapped data key and the ciphertext,
+/// or an error of type [`cdumay_core::Error`] if wrapping fails.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::envelope::{decrypt, encrypt, Envelope, SecretBoxKek};
+///
+/// let context = BTreeMap::<String, Value>::new();
+/// let kek = SecretBoxKek::new("kek-2024", "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=", context.clone()).unwrap();
+/// let envelope = encrypt("my secret message", &kek, context.clone()).unwrap();
+/// let stored = envelope.to_string();
+///
+/// let envelope = Envelope::parse(&stored, context.clone()).unwrap();
+/// assert_eq!(envelope.kek_id, "kek-2024");
+/// assert_eq!(decrypt(&envelope, &kek, context).unwrap(), "my secret message");
+/// ```
+pub fn encrypt(data: &str, provider: &dyn KeyEncryptionProvider, context: impl Into<Option<Context>>) -> cdumay_core::Result<Envelope> {
+    let context = crate::errors::operation(context, module_path!(), "encrypt");
+    encrypt_with_algorithm(data, provider, secretbox::Algorithm::default(), context)
+}
+
+/// Encrypts data like [`encrypt`], sealing the payload with the given secretbox algorithm, which is recorded in the envelope.
+///
+/// # Errors
+///
+/// Returns an error if the data key cannot be wrapped.
+pub fn encrypt_with_algorithm(
+    data: &str,
+    provider: &dyn KeyEncryptionProvider,
+    algorithm: secretbox::Algorithm,
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<Envelope> {
+    let context = crate::errors::operation(context, module_path!(), "encrypt_with_algorithm");
+    seal_bytes(data.as_bytes(), provider, algorithm, context)
+}
+
+/// Seals raw bytes into an envelope, for [`encrypt_with_algorithm`] and the C API.
+pub(crate) fn seal_bytes(
+    data: &[u8],
+    provider: &dyn KeyEncryptionProvider,
+    algorithm: secretbox::Algorithm,
+    context: BTreeMap<String, serde_value::Value>,
+) -> cdumay_core::Result<Envelope> {
+    crate::init(context.clone())?;
+    let data_key = secretbox::Key::generate();
+    let wrapped_key = provider.wrap_key(data_key.as_ref(), context)?;
+    let (kek_id, kek_fingerprint) = (provider.kek_id(), provider.kek_fingerprint());
+    let nonce = secretbox::gen_nonce();
+    let ciphertext = secretbox::seal_with(algorithm, data, &nonce, payload_key(&data_key, algorithm, kek_id, kek_fingerprint).as_ref());
+    Ok(Envelope {
+        algorithm,
+        kek_id: kek_id.to_string(),
+        kek_fingerprint,
+        authenticated_header: true,
+        wrapped_key: BASE64_STANDARD.encode(wrapped_key),
+        nonce: BASE64_STANDARD.encode(nonce.as_ref()),
+        ciphertext: BASE64_STANDARD.encode(ciphertext),
+    })
+}
+
+/// Decrypts an envelope by unwrapping its data key with the given key encryption provider.
+///
+/// # Arguments
+///
+/// * `envelope` - The envelope to decrypt.
+/// * `provider` - The key encryption provider owning the KEK referenced by the envelope.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok(String)` containing the decrypted plaintext if successful,
+/// or an error of type [`cdumay_core::Error`] if any step fails.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The envelope references another KEK than the provider's, by identifier or by fingerprint ([`KeyEncryptionKeyMismatch`]).
+/// - A field cannot be base64-decoded, or the nonce or unwrapped key has an invalid length.
+/// - The data key cannot be unwrapped or the payload fails authentication.
+/// - The decrypted data is not valid UTF-8.
+pub fn decrypt(envelope: &Envelope, provider: &dyn KeyEncryptionProvider, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "decrypt");
+    vec_to_string(open_bytes(envelope, provider, context.clone())?, context)
+}
+
+/// Opens an envelope into raw bytes, for [`decrypt`] and the C API.
+pub(crate) fn open_bytes(
+    envelope: &Envelope,
+    provider: &dyn KeyEncryptionProvider,
+    context: BTreeMap<String, serde_value::Value>,
+) -> cdumay_core::Result<Vec<u8>> {
+    crate::init(context.clone())?;
+    if envelope.kek_id != provider.kek_id() {
+        return Err(KeyEncryptionKeyMismatch::new()
+            .with_message(format!(
+                "Envelope was wrapped by key encryption key '{}', not '{}'",
+                envelope.kek_id,
+                provider.kek_id()
+            ))
+            .with_details(context)
+            .into());
+    }
+    if let Some(expected) = envelope.kek_fingerprint
+        && let Some(actual) = provider.kek_fingerprint()
+        && expected != actual
+    {
+        let context = enrich(context, &[("kek_id", Detail::Text(&envelope.kek_id)), ("kek_fingerprint", Detail::Text(&expected.to_string()))]);
+        return Err(KeyEncryptionKeyMismatch::new()
+            .with_message(format!("Envelope was wrapped by key encryption key '{}' with fingerprint {}, not {}", envelope.kek_id, expected, actual))
+            .with_details(with_key_fingerprint(context, actual))
+            .into());
+    }
+    let wrapped_key = decode_base64(&envelope.wrapped_key, "wrapped_key", context.clone())?;
+    let nonce_decoded = decode_base64(&envelope.nonce, "nonce", context.clone())?;
+    let data_decoded = decode_base64(&envelope.ciphertext, "ciphertext", context.clone())?;
+
+    let data_key = secretbox::into_secretbox_key(provider.unwrap_key(&wrapped_key, context.clone())?, context.clone())?;
+    let data_key = match envelope.authenticated_header {
+        true => payload_key(&data_key, envelope.algorithm, &envelope.kek_id, envelope.kek_fingerprint),
+        false => data_key,
+    };
+    let nonce = secretbox::into_secretbox_nonce(nonce_decoded, context.clone())?;
+    match secretbox::open_with(envelope.algorithm, &data_decoded, &nonce, data_key.as_ref()) {
+        Some(decrypted) => Ok(decrypted),
+        None => Err(authentication_failed(envelope.algorithm.id(), "ciphertext", context)),
+    }
+}
+
+/// Derives the payload key of a `v3` envelope from its data key and header, so that altering the header fails authentication.
+fn payload_key(data_key: &secretbox::Key, algorithm: secretbox::Algorithm, kek_id: &str, kek_fingerprint: Option<Fingerprint>) -> secretbox::Key {
+    let fingerprint = kek_fingerprint.as_ref().map_or(&[][..], |fingerprint| &fingerprint.as_bytes()[..]);
+    let mut header = HEADER_LABEL.to_vec();
+    for field in [algorithm.id().as_bytes(), fingerprint, kek_id.as_bytes()] {
+        header.extend_from_slice(&(field.len() as u64).to_le_bytes());
+        header.extend_from_slice(field);
+    }
+    data_key.derive(&header)
+}
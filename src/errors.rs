@@ -1,10 +1,171 @@
+use alloc::string::ToString;
+#[cfg(feature = "std")]
 use cdumay_core::define_errors;
-use cdumay_error::{InvalidConfiguration, ValidationError};
+#[cfg(feature = "std")]
+use cdumay_error::{InvalidConfiguration, UnknownError, ValidationError};
 
-define_errors! {
+#[cfg(not(feature = "std"))]
+use crate::core_error::{InvalidConfiguration, UnknownError, ValidationError, define_errors};
+#[cfg(not(feature = "std"))]
+pub use crate::core_error::{Context, Error, ErrorKind, Result};
+
+/// Details attached to errors. Every function of the crate takes one, or `None`, and adds its own details to the errors it returns.
+#[cfg(feature = "std")]
+pub type Context = std::collections::BTreeMap<String, serde_value::Value>;
+
+/// Error of the crate operations.
+#[cfg(feature = "std")]
+pub type Error = cdumay_core::Error;
+
+/// Result of the crate operations.
+#[cfg(feature = "std")]
+pub type Result<T> = cdumay_core::Result<T>;
+
+/// Defines the crate errors with `define_errors!`, and `$lookup`, which returns the kind of one of them from its class: a converted
+/// [`Error`] keeps no other trace of its kind.
+macro_rules! crate_errors {
+    ($lookup:ident; $($name:ident = $kind_spec:tt),* $(,)?) => {
+        define_errors! { $($name = $kind_spec),* }
+
+        #[cfg(feature = "capi")]
+        fn $lookup(class: &str) -> Option<cdumay_core::ErrorKind> {
+            $(
+                if class == $name::new().class() {
+                    return Some($name::kind);
+                }
+            )*
+            None
+        }
+    };
+}
+
+crate_errors! {
+    error_kind;
     InvalidBoxKeyLength = InvalidConfiguration,
     InvalidBoxNonceLength = InvalidConfiguration,
+    InvalidBoxTagLength = InvalidConfiguration,
+    UnsupportedAlgorithm = InvalidConfiguration,
     InvalidContent = ValidationError,
     FailedToOpenSecretBox = ValidationError,
     FailedToOpenSealedBox = ValidationError,
+    InvalidNoiseKeyLength = InvalidConfiguration,
+    InvalidNoiseHandshakeState = InvalidConfiguration,
+    FailedToOpenNoiseMessage = ValidationError,
+    InvalidEnvelope = ValidationError,
+    KeyEncryptionKeyMismatch = InvalidConfiguration,
+    InvalidKeyId = InvalidConfiguration,
+    UnknownKeyId = InvalidConfiguration,
+    InvalidJsonPointer = InvalidConfiguration,
+    EnvironmentVariableNotFound = InvalidConfiguration,
+    KeyFileUnreadable = InvalidConfiguration,
+    InvalidBlindIndex = InvalidConfiguration,
+    NonceReuse = ValidationError,
+    NonceSequenceExhausted = InvalidConfiguration,
+    SecureMemoryAllocationFailed = UnknownError,
+    SodiumInitFailed = UnknownError,
+    InvalidFingerprint = ValidationError,
+    InvalidKeyFormat = ValidationError,
+    KeyFileUnwritable = InvalidConfiguration,
+    KeyNotInScope = InvalidConfiguration,
+    InvalidRecipientCount = InvalidConfiguration,
+}
+
+// Errors with a stable code of their own: `4xxx` for client errors, `5xxx` for server errors.
+crate_errors! {
+    coded_error_kind;
+    InvalidPublicKeyLength = (InvalidConfiguration, 4001),
+    InvalidPrivateKeyLength = (InvalidConfiguration, 4002),
+    CiphertextTooShort = (ValidationError, 4003),
+    AuthenticationFailed = (ValidationError, 4004),
+    InvalidBase64 = (ValidationError, 4005),
+    EncryptionFailed = (UnknownError, 5001),
+}
+
+/// A detail added by the crate to the context of an error.
+pub(crate) enum Detail<'a> {
+    Text(&'a str),
+    Length(usize),
+}
+
+/// Adds details to an error context, replacing the entries of the same name.
+pub(crate) fn enrich(mut context: Context, details: &[(&str, Detail<'_>)]) -> Context {
+    for (name, detail) in details {
+        #[cfg(feature = "std")]
+        let value = match detail {
+            Detail::Text(text) => serde_value::Value::String(text.to_string()),
+            Detail::Length(length) => serde_value::Value::U64(*length as u64),
+        };
+        #[cfg(not(feature = "std"))]
+        let value = match detail {
+            Detail::Text(text) => text.to_string(),
+            Detail::Length(length) => alloc::format!("{}", length),
+        };
+        context.insert(name.to_string(), value);
+    }
+    context
+}
+
+/// Adds the `field`, `expected_length` and `actual_length` details, and the `algorithm` if the length is specific to one.
+pub(crate) fn length_details(context: Context, algorithm: Option<&str>, field: &str, expected: usize, actual: usize) -> Context {
+    let context = enrich(
+        context,
+        &[("field", Detail::Text(field)), ("expected_length", Detail::Length(expected)), ("actual_length", Detail::Length(actual))],
+    );
+    match algorithm {
+        Some(algorithm) => enrich(context, &[("algorithm", Detail::Text(algorithm))]),
+        None => context,
+    }
+}
+
+/// Returns an [`AuthenticationFailed`] error: the `field` input was tampered with, or opened with the wrong key.
+#[cfg_attr(not(any(feature = "sodium", feature = "rustcrypto")), allow(dead_code))]
+pub(crate) fn authentication_failed(algorithm: &str, field: &str, context: Context) -> Error {
+    AuthenticationFailed::new()
+        .with_message(alloc::format!("Decryption failed: {} does not authenticate", field))
+        .with_details(enrich(context, &[("algorithm", Detail::Text(algorithm)), ("field", Detail::Text(field))]))
+        .into()
+}
+
+/// Returns a [`CiphertextTooShort`] error for a `field` input of `actual` bytes, shorter than the `minimum` of the algorithm.
+#[cfg_attr(not(any(feature = "sodium", feature = "rustcrypto")), allow(dead_code))]
+pub(crate) fn ciphertext_too_short(algorithm: &str, field: &str, minimum: usize, actual: usize, context: Context) -> Error {
+    CiphertextTooShort::new()
+        .with_message(alloc::format!("Ciphertext too short: at least {} bytes required, got {}", minimum, actual))
+        .with_details(length_details(context, Some(algorithm), field, minimum, actual))
+        .into()
+}
+
+/// Returns an [`EncryptionFailed`] error.
+#[cfg_attr(not(any(feature = "sodium", feature = "rustcrypto")), allow(dead_code))]
+pub(crate) fn encryption_failed(algorithm: &str, context: Context) -> Error {
+    EncryptionFailed::new()
+        .with_message("Encryption failed".to_string())
+        .with_details(enrich(context, &[("algorithm", Detail::Text(algorithm))]))
+        .into()
+}
+
+/// Starts the context of a public operation from the caller context, if any, with the `module` and `operation` details.
+///
+/// Entries already present are kept, so that an operation calling another one reports the operation the caller invoked.
+#[cfg_attr(not(any(feature = "sodium", feature = "rustcrypto")), allow(dead_code))]
+pub(crate) fn operation(context: impl Into<Option<Context>>, module: &str, operation: &str) -> Context {
+    let mut context = context.into().unwrap_or_default();
+    for (name, value) in [("module", module), ("operation", operation)] {
+        if !context.contains_key(name) {
+            context = enrich(context, &[(name, Detail::Text(value))]);
+        }
+    }
+    context
+}
+
+/// Adds the `key_fingerprint` detail (see [`crate::fingerprint`]), which identifies the key of an operation without revealing it.
+#[cfg(feature = "sodium")]
+pub(crate) fn with_key_fingerprint(context: Context, fingerprint: crate::fingerprint::Fingerprint) -> Context {
+    enrich(context, &[("key_fingerprint", Detail::Text(&fingerprint.to_string()))])
+}
+
+/// Returns the kind of an error raised by the crate, or `None` for the errors of other crates.
+#[cfg(feature = "capi")]
+pub(crate) fn kind_of(err: &Error) -> Option<cdumay_core::ErrorKind> {
+    error_kind(err.class()).or_else(|| coded_error_kind(err.class()))
 }
@@ -0,0 +1,324 @@
+//! The single binding to libsodium used by the crate.
+//!
+//! Every call into `libsodium-sys` goes through this module, and no other module contains `unsafe` FFI code. Buffers cross the
+//! boundary as fixed-size arrays, so the lengths libsodium reads and writes are guaranteed by the types; untrusted slices are turned
+//! into such arrays by the checked conversions of [`crate::lengths`], re-exported here ([`secretbox_key`], [`box_public_key`],
+//! ...), which map a wrong length to the error the crate has always reported for it.
+//!
+//! Operations which fail on untrusted input (authentication, invalid points) return `Option` or `bool`, and callers map the failure
+//! to the error of their module. libsodium must be initialized with [`crate::init`] before any function of this module is used.
+
+use crate::secretbox::Algorithm;
+use std::ptr::NonNull;
+
+pub(crate) use crate::lengths::{
+    BOX_PUBLICKEYBYTES, BOX_SEALBYTES, BOX_SECRETKEYBYTES, SECRETBOX_KEYBYTES, SECRETBOX_MACBYTES, SECRETBOX_NONCEBYTES, box_public_key,
+    box_secret_key, secretbox_key, secretbox_nonce, secretbox_tag,
+};
+
+const _: () = {
+    assert!(SECRETBOX_KEYBYTES == sodium::crypto_secretbox_KEYBYTES as usize);
+    assert!(SECRETBOX_NONCEBYTES == sodium::crypto_secretbox_NONCEBYTES as usize);
+    assert!(SECRETBOX_MACBYTES == sodium::crypto_secretbox_MACBYTES as usize);
+    assert!(BOX_PUBLICKEYBYTES == sodium::crypto_box_PUBLICKEYBYTES as usize);
+    assert!(BOX_SECRETKEYBYTES == sodium::crypto_box_SECRETKEYBYTES as usize);
+    assert!(BOX_SEALBYTES == sodium::crypto_box_SEALBYTES as usize);
+};
+
+/// Length of X25519 keys and shared secrets.
+pub(crate) const SCALARMULT_BYTES: usize = sodium::crypto_scalarmult_curve25519_BYTES as usize;
+
+/// Length of a ChaCha20-Poly1305 (IETF) key.
+pub(crate) const AEAD_KEYBYTES: usize = sodium::crypto_aead_chacha20poly1305_ietf_KEYBYTES as usize;
+/// Length of a ChaCha20-Poly1305 (IETF) nonce.
+pub(crate) const AEAD_NPUBBYTES: usize = sodium::crypto_aead_chacha20poly1305_ietf_NPUBBYTES as usize;
+/// Length of a ChaCha20-Poly1305 (IETF) authentication tag.
+pub(crate) const AEAD_ABYTES: usize = sodium::crypto_aead_chacha20poly1305_ietf_ABYTES as usize;
+
+/// Length of an HMAC-SHA-256 key and output.
+pub(crate) const HMACSHA256_BYTES: usize = sodium::crypto_auth_hmacsha256_BYTES as usize;
+/// Length of a `crypto_kdf` master key.
+pub(crate) const KDF_KEYBYTES: usize = sodium::crypto_kdf_KEYBYTES as usize;
+/// Length of a `crypto_kdf` context.
+pub(crate) const KDF_CONTEXTBYTES: usize = sodium::crypto_kdf_CONTEXTBYTES as usize;
+
+/// Calls `sodium_init()`, returning `false` if libsodium cannot be used.
+pub(crate) fn init() -> bool {
+    unsafe { sodium::sodium_init() >= 0 }
+}
+
+/// Fills `buf` with random bytes (`randombytes_buf`).
+pub(crate) fn random_bytes(buf: &mut [u8]) {
+    unsafe { sodium::randombytes_buf(buf.as_mut_ptr() as *mut _, buf.len()) }
+}
+
+/// Returns a uniformly distributed random number below `upper_bound` (`randombytes_uniform`).
+pub(crate) fn random_uniform(upper_bound: u32) -> u32 {
+    unsafe { sodium::randombytes_uniform(upper_bound) }
+}
+
+/// Overwrites `buf` with zeros in a way the compiler cannot optimize out (`sodium_memzero`).
+pub(crate) fn memzero(buf: &mut [u8]) {
+    unsafe { sodium::sodium_memzero(buf.as_mut_ptr() as *mut _, buf.len()) }
+}
+
+/// Compares two buffers in constant time (`sodium_memcmp`); buffers of different lengths are not equal.
+pub(crate) fn memcmp(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && unsafe { sodium::sodium_memcmp(a.as_ptr() as *const _, b.as_ptr() as *const _, a.len()) == 0 }
+}
+
+/// Increments a little-endian number in constant time (`sodium_increment`).
+pub(crate) fn increment(number: &mut [u8]) {
+    unsafe { sodium::sodium_increment(number.as_mut_ptr(), number.len()) }
+}
+
+/// Returns `true` if the buffer only holds zeros (`sodium_is_zero`).
+pub(crate) fn is_zero(buf: &[u8]) -> bool {
+    unsafe { sodium::sodium_is_zero(buf.as_ptr(), buf.len()) == 1 }
+}
+
+/// Allocates `len` bytes of guarded memory (`sodium_malloc`), locked in RAM where the platform allows it.
+pub(crate) fn malloc(len: usize) -> Option<NonNull<u8>> {
+    NonNull::new(unsafe { sodium::sodium_malloc(len) } as *mut u8)
+}
+
+/// Wipes, unlocks and releases memory allocated by [`malloc`] (`sodium_free`).
+///
+/// # Safety
+///
+/// `ptr` must come from [`malloc`] and must not be used afterwards.
+pub(crate) unsafe fn free(ptr: NonNull<u8>) {
+    unsafe { sodium::sodium_free(ptr.as_ptr() as *mut _) }
+}
+
+/// Makes memory allocated by [`malloc`] inaccessible (`sodium_mprotect_noaccess`).
+///
+/// # Safety
+///
+/// `ptr` must come from [`malloc`], and no reference into the region may be used until access is restored.
+pub(crate) unsafe fn mprotect_noaccess(ptr: NonNull<u8>) {
+    unsafe { sodium::sodium_mprotect_noaccess(ptr.as_ptr() as *mut _) };
+}
+
+/// Makes memory allocated by [`malloc`] read-only (`sodium_mprotect_readonly`).
+///
+/// # Safety
+///
+/// `ptr` must come from [`malloc`], and no mutable reference into the region may be used until write access is restored.
+pub(crate) unsafe fn mprotect_readonly(ptr: NonNull<u8>) {
+    unsafe { sodium::sodium_mprotect_readonly(ptr.as_ptr() as *mut _) };
+}
+
+/// Makes memory allocated by [`malloc`] readable and writable (`sodium_mprotect_readwrite`).
+///
+/// # Safety
+///
+/// `ptr` must come from [`malloc`].
+pub(crate) unsafe fn mprotect_readwrite(ptr: NonNull<u8>) {
+    unsafe { sodium::sodium_mprotect_readwrite(ptr.as_ptr() as *mut _) };
+}
+
+/// Seals `data` in combined form (tag followed by ciphertext) with the given secretbox algorithm.
+pub(crate) fn secretbox_easy(
+    algorithm: Algorithm,
+    data: &[u8],
+    nonce: &[u8; SECRETBOX_NONCEBYTES],
+    key: &[u8; SECRETBOX_KEYBYTES],
+) -> Vec<u8> {
+    let easy = match algorithm {
+        Algorithm::XSalsa20Poly1305 => sodium::crypto_secretbox_easy,
+        Algorithm::XChaCha20Poly1305 => sodium::crypto_secretbox_xchacha20poly1305_easy,
+    };
+    let mut ciphertext = vec![0u8; data.len() + SECRETBOX_MACBYTES];
+    unsafe {
+        easy(ciphertext.as_mut_ptr(), data.as_ptr(), data.len() as u64, nonce.as_ptr(), key.as_ptr());
+    }
+    ciphertext
+}
+
+/// Opens data in combined form with the given secretbox algorithm, or returns `None` if it is too short or fails authentication.
+pub(crate) fn secretbox_open_easy(
+    algorithm: Algorithm,
+    data: &[u8],
+    nonce: &[u8; SECRETBOX_NONCEBYTES],
+    key: &[u8; SECRETBOX_KEYBYTES],
+) -> Option<Vec<u8>> {
+    if data.len() < SECRETBOX_MACBYTES {
+        return None;
+    }
+    let open_easy = match algorithm {
+        Algorithm::XSalsa20Poly1305 => sodium::crypto_secretbox_open_easy,
+        Algorithm::XChaCha20Poly1305 => sodium::crypto_secretbox_xchacha20poly1305_open_easy,
+    };
+    let mut plaintext = vec![0u8; data.len() - SECRETBOX_MACBYTES];
+    let result = unsafe { open_easy(plaintext.as_mut_ptr(), data.as_ptr(), data.len() as u64, nonce.as_ptr(), key.as_ptr()) };
+    (result == 0).then_some(plaintext)
+}
+
+/// Encrypts `data` in place with XSalsa20-Poly1305 and returns the detached authentication tag (`crypto_secretbox_detached`).
+pub(crate) fn secretbox_detached(
+    data: &mut [u8],
+    nonce: &[u8; SECRETBOX_NONCEBYTES],
+    key: &[u8; SECRETBOX_KEYBYTES],
+) -> [u8; SECRETBOX_MACBYTES] {
+    let mut tag = [0u8; SECRETBOX_MACBYTES];
+    unsafe {
+        sodium::crypto_secretbox_detached(data.as_mut_ptr(), tag.as_mut_ptr(), data.as_ptr(), data.len() as u64, nonce.as_ptr(), key.as_ptr());
+    }
+    tag
+}
+
+/// Decrypts `data` in place with XSalsa20-Poly1305 and a detached tag (`crypto_secretbox_open_detached`), returning `false` if
+/// it fails authentication.
+pub(crate) fn secretbox_open_detached(
+    data: &mut [u8],
+    tag: &[u8; SECRETBOX_MACBYTES],
+    nonce: &[u8; SECRETBOX_NONCEBYTES],
+    key: &[u8; SECRETBOX_KEYBYTES],
+) -> bool {
+    unsafe {
+        sodium::crypto_secretbox_open_detached(data.as_mut_ptr(), data.as_ptr(), tag.as_ptr(), data.len() as u64, nonce.as_ptr(), key.as_ptr()) == 0
+    }
+}
+
+/// Seals `data` for the owner of `public_key` (`crypto_box_seal`), or returns `None` if libsodium rejects the key.
+pub(crate) fn box_seal(data: &[u8], public_key: &[u8; BOX_PUBLICKEYBYTES]) -> Option<Vec<u8>> {
+    let mut ciphertext = vec![0u8; data.len() + BOX_SEALBYTES];
+    let result = unsafe { sodium::crypto_box_seal(ciphertext.as_mut_ptr(), data.as_ptr(), data.len() as u64, public_key.as_ptr()) };
+    (result == 0).then_some(ciphertext)
+}
+
+/// Opens a sealed box with the recipient keypair (`crypto_box_seal_open`), or returns `None` if it is too short or fails
+/// authentication.
+pub(crate) fn box_seal_open(
+    data: &[u8],
+    public_key: &[u8; BOX_PUBLICKEYBYTES],
+    private_key: &[u8; BOX_SECRETKEYBYTES],
+) -> Option<Vec<u8>> {
+    if data.len() < BOX_SEALBYTES {
+        return None;
+    }
+    let mut plaintext = vec![0u8; data.len() - BOX_SEALBYTES];
+    let result = unsafe {
+        sodium::crypto_box_seal_open(plaintext.as_mut_ptr(), data.as_ptr(), data.len() as u64, public_key.as_ptr(), private_key.as_ptr())
+    };
+    (result == 0).then_some(plaintext)
+}
+
+/// Hashes `input` with BLAKE2b into `out`, keyed with `key` if given (`crypto_generichash`).
+///
+/// # Panics
+///
+/// Panics if `out` or `key` do not have a length accepted by `crypto_generichash`; both are constants of the callers.
+pub(crate) fn generichash(out: &mut [u8], input: &[u8], key: Option<&[u8]>) {
+    let out_range = sodium::crypto_generichash_BYTES_MIN as usize..=sodium::crypto_generichash_BYTES_MAX as usize;
+    assert!(out_range.contains(&out.len()));
+    let (key_ptr, key_len) = match key {
+        Some(key) => {
+            assert!((sodium::crypto_generichash_KEYBYTES_MIN as usize..=sodium::crypto_generichash_KEYBYTES_MAX as usize).contains(&key.len()));
+            (key.as_ptr(), key.len())
+        }
+        None => (std::ptr::null(), 0),
+    };
+    unsafe {
+        sodium::crypto_generichash(out.as_mut_ptr(), out.len(), input.as_ptr(), input.len() as u64, key_ptr, key_len);
+    }
+}
+
+/// Computes the HMAC-SHA-256 of `input` (`crypto_auth_hmacsha256`).
+pub(crate) fn hmacsha256(input: &[u8], key: &[u8; HMACSHA256_BYTES]) -> [u8; HMACSHA256_BYTES] {
+    let mut out = [0u8; HMACSHA256_BYTES];
+    unsafe {
+        sodium::crypto_auth_hmacsha256(out.as_mut_ptr(), input.as_ptr(), input.len() as u64, key.as_ptr());
+    }
+    out
+}
+
+/// Derives the subkey `subkey_id` of `key` in the given context (`crypto_kdf_derive_from_key`).
+pub(crate) fn kdf_derive_from_key(
+    subkey: &mut [u8; SECRETBOX_KEYBYTES],
+    subkey_id: u64,
+    context: &[u8; KDF_CONTEXTBYTES],
+    key: &[u8; KDF_KEYBYTES],
+) {
+    unsafe {
+        sodium::crypto_kdf_derive_from_key(subkey.as_mut_ptr(), subkey.len(), subkey_id, context.as_ptr() as *const _, key.as_ptr());
+    }
+}
+
+/// Derives the X25519 public key of a private key (`crypto_scalarmult_base`).
+pub(crate) fn scalarmult_base(private_key: &[u8; SCALARMULT_BYTES]) -> [u8; SCALARMULT_BYTES] {
+    let mut public_key = [0u8; SCALARMULT_BYTES];
+    unsafe {
+        sodium::crypto_scalarmult_base(public_key.as_mut_ptr(), private_key.as_ptr());
+    }
+    public_key
+}
+
+/// Computes an X25519 shared secret (`crypto_scalarmult`), or returns `None` for a low-order public key.
+pub(crate) fn scalarmult(private_key: &[u8; SCALARMULT_BYTES], public_key: &[u8; SCALARMULT_BYTES]) -> Option<[u8; SCALARMULT_BYTES]> {
+    let mut shared = [0u8; SCALARMULT_BYTES];
+    let result = unsafe { sodium::crypto_scalarmult(shared.as_mut_ptr(), private_key.as_ptr(), public_key.as_ptr()) };
+    (result == 0).then_some(shared)
+}
+
+/// Length of an Ed25519 seed, the private key as written in JWKs.
+pub(crate) const SIGN_SEEDBYTES: usize = sodium::crypto_sign_SEEDBYTES as usize;
+/// Length of an Ed25519 public key.
+pub(crate) const SIGN_PUBLICKEYBYTES: usize = sodium::crypto_sign_PUBLICKEYBYTES as usize;
+
+/// Derives the Ed25519 public key of a seed (`crypto_sign_seed_keypair`). The expanded secret key is wiped.
+pub(crate) fn sign_seed_public_key(seed: &[u8; SIGN_SEEDBYTES]) -> [u8; SIGN_PUBLICKEYBYTES] {
+    let mut public_key = [0u8; SIGN_PUBLICKEYBYTES];
+    let mut secret_key = [0u8; sodium::crypto_sign_SECRETKEYBYTES as usize];
+    unsafe {
+        sodium::crypto_sign_seed_keypair(public_key.as_mut_ptr(), secret_key.as_mut_ptr(), seed.as_ptr());
+    }
+    memzero(&mut secret_key);
+    public_key
+}
+
+/// Encrypts with ChaCha20-Poly1305 (IETF), returning the ciphertext followed by the tag
+/// (`crypto_aead_chacha20poly1305_ietf_encrypt`).
+pub(crate) fn aead_encrypt(plaintext: &[u8], ad: &[u8], nonce: &[u8; AEAD_NPUBBYTES], key: &[u8; AEAD_KEYBYTES]) -> Vec<u8> {
+    let mut ciphertext = vec![0u8; plaintext.len() + AEAD_ABYTES];
+    let mut ciphertext_len = 0u64;
+    unsafe {
+        sodium::crypto_aead_chacha20poly1305_ietf_encrypt(
+            ciphertext.as_mut_ptr(),
+            &mut ciphertext_len,
+            plaintext.as_ptr(),
+            plaintext.len() as u64,
+            ad.as_ptr(),
+            ad.len() as u64,
+            std::ptr::null(),
+            nonce.as_ptr(),
+            key.as_ptr(),
+        );
+    }
+    ciphertext
+}
+
+/// Decrypts with ChaCha20-Poly1305 (IETF) (`crypto_aead_chacha20poly1305_ietf_decrypt`), or returns `None` if the ciphertext is
+/// too short or fails authentication.
+pub(crate) fn aead_decrypt(ciphertext: &[u8], ad: &[u8], nonce: &[u8; AEAD_NPUBBYTES], key: &[u8; AEAD_KEYBYTES]) -> Option<Vec<u8>> {
+    if ciphertext.len() < AEAD_ABYTES {
+        return None;
+    }
+    let mut plaintext = vec![0u8; ciphertext.len() - AEAD_ABYTES];
+    let mut plaintext_len = 0u64;
+    let result = unsafe {
+        sodium::crypto_aead_chacha20poly1305_ietf_decrypt(
+            plaintext.as_mut_ptr(),
+            &mut plaintext_len,
+            std::ptr::null_mut(),
+            ciphertext.as_ptr(),
+            ciphertext.len() as u64,
+            ad.as_ptr(),
+            ad.len() as u64,
+            nonce.as_ptr(),
+            key.as_ptr(),
+        )
+    };
+    (result == 0).then_some(plaintext)
+}
@@ -0,0 +1,114 @@
+//! Key fingerprints name a key in logs, runbooks, envelopes and error contexts without revealing it.
+//!
+//! A fingerprint is the BLAKE2b hash of the key, keyed with a domain-separation label naming the kind of key, truncated to
+//! [`FINGERPRINTBYTES`] bytes. The label keeps fingerprints apart from any other hash of the same bytes. The label is public, so a
+//! fingerprint can be tested against a guessed key, but it reveals nothing about a full-entropy key such as the ones generated by
+//! the crate. Secretbox keys and public keys use distinct labels.
+//!
+//! Fingerprints are obtained with [`crate::secretbox::fingerprint`], [`crate::sealedbox::fingerprint`], the key encryption
+//! providers of [`crate::envelope`], [`crate::keyring::Keyring::fingerprint`] and [`crate::noise::Keypair::fingerprint`]. They are
+//! written as 16 lowercase hex digits, or in groups of four with [`Fingerprint::to_hex_groups`] for reading aloud.
+//!
+//! # Example
+//!
+//! ```
+//! use cdumay_sodium::fingerprint::Fingerprint;
+//! use cdumay_sodium::secretbox;
+//!
+//! let fingerprint = secretbox::fingerprint("llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=", None).unwrap();
+//! let grouped = fingerprint.to_hex_groups();
+//! assert_eq!(grouped.len(), 19);
+//! assert_eq!(Fingerprint::parse(&grouped, None).unwrap(), fingerprint);
+//! assert_eq!(Fingerprint::parse(&fingerprint.to_string(), None).unwrap(), fingerprint);
+//! ```
+
+use crate::{Context, InvalidFingerprint, ffi};
+use std::fmt;
+
+/// Length of a fingerprint.
+pub const FINGERPRINTBYTES: usize = 8;
+
+/// Length of the BLAKE2b hash truncated into a fingerprint.
+const HASHBYTES: usize = 32;
+
+/// Domain-separation label of secretbox key fingerprints.
+const SECRETBOX_KEY_LABEL: &[u8] = b"cdumay_sodium.fingerprint.secretbox-key.v1";
+
+/// Domain-separation label of public key fingerprints.
+const PUBLIC_KEY_LABEL: &[u8] = b"cdumay_sodium.fingerprint.public-key.v1";
+
+/// The fingerprint of a key.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint([u8; FINGERPRINTBYTES]);
+
+impl Fingerprint {
+    /// Computes the fingerprint of a secretbox key.
+    pub(crate) fn secretbox_key(key: &[u8]) -> Fingerprint {
+        Fingerprint::of(SECRETBOX_KEY_LABEL, key)
+    }
+
+    /// Computes the fingerprint of a public key.
+    pub(crate) fn public_key(key: &[u8]) -> Fingerprint {
+        Fingerprint::of(PUBLIC_KEY_LABEL, key)
+    }
+
+    fn of(label: &[u8], key: &[u8]) -> Fingerprint {
+        crate::init_or_panic();
+        let mut hash = [0u8; HASHBYTES];
+        ffi::generichash(&mut hash, key, Some(label));
+        let mut fingerprint = [0u8; FINGERPRINTBYTES];
+        fingerprint.copy_from_slice(&hash[..FINGERPRINTBYTES]);
+        Fingerprint(fingerprint)
+    }
+
+    /// Parses a fingerprint in its `Display` form or written by [`Fingerprint::to_hex_groups`]. Case and dashes are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidFingerprint`] error if the input is not 16 hex digits.
+    pub fn parse(fingerprint: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Fingerprint> {
+        let context = crate::errors::operation(context, module_path!(), "Fingerprint::parse");
+        let digits: Vec<u8> = fingerprint.bytes().filter(|byte| *byte != b'-').collect();
+        let nibble = |digit: u8| (digit as char).to_digit(16).map(|value| value as u8);
+        if digits.len() != 2 * FINGERPRINTBYTES {
+            return Err(invalid_fingerprint(fingerprint, context));
+        }
+        let mut bytes = [0u8; FINGERPRINTBYTES];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+            match (nibble(pair[0]), nibble(pair[1])) {
+                (Some(high), Some(low)) => *byte = (high << 4) | low,
+                _ => return Err(invalid_fingerprint(fingerprint, context)),
+            }
+        }
+        Ok(Fingerprint(bytes))
+    }
+
+    /// Returns the raw fingerprint.
+    pub fn as_bytes(&self) -> &[u8; FINGERPRINTBYTES] {
+        &self.0
+    }
+
+    /// Returns the fingerprint as four dash-separated groups of four hex digits, such as `3f2a-9c1d-04be-77a0`.
+    pub fn to_hex_groups(&self) -> String {
+        self.0.chunks(2).map(|group| format!("{:02x}{:02x}", group[0], group[1])).collect::<Vec<_>>().join("-")
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Fingerprint({})", self)
+    }
+}
+
+fn invalid_fingerprint(fingerprint: &str, context: Context) -> cdumay_core::Error {
+    InvalidFingerprint::new()
+        .with_message(format!("Invalid key fingerprint '{}': {} hex digits required", fingerprint, 2 * FINGERPRINTBYTES))
+        .with_details(context)
+        .into()
+}
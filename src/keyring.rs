@@ -0,0 +1,256 @@
+//! A keyring holds several versioned secretbox keys, one of them marked as active, so that keys can be rotated without a flag day.
+//!
+//! Encryption always uses the active key and stamps its identifier in front of the ciphertext as `<key_id>:<ciphertext_b64>`.
+//! Decryption selects the key from the stamp, and falls back to trying every key for legacy, un-stamped ciphertexts produced by
+//! [`crate::secretbox::crypt`]. Nonces are handled exactly as in the [`crate::secretbox`] module.
+//!
+//! Keys are kept in [`SecureBuffer`]s: guarded, locked memory which is only readable while an operation uses it.
+//!
+//! A keyring is usually loaded from the configuration map of key identifier to base64-encoded key:
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde_value::Value;
+//! use cdumay_sodium::keyring::Keyring;
+//!
+//! let mut config = BTreeMap::new();
+//! config.insert("2023", "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=");
+//! config.insert("2024", "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=");
+//! let context = BTreeMap::<String, Value>::new();
+//! let keyring = Keyring::from_config(&config, "2024", context.clone()).unwrap();
+//!
+//! let (nonce_b64, ciphertext) = keyring.crypt("my secret message", context.clone()).unwrap();
+//! assert!(ciphertext.starts_with("2024:"));
+//! assert_eq!(keyring.decrypt(&ciphertext, &nonce_b64, context).unwrap(), "my secret message");
+//! ```
+
+use crate::errors::{authentication_failed, with_key_fingerprint};
+use crate::fingerprint::Fingerprint;
+use crate::memory::{SecureBuffer, SecureRead};
+use crate::secretbox::Algorithm;
+use crate::{Context, InvalidKeyId, UnknownKeyId, decode_base64, ffi, secretbox, vec_to_string};
+use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use cdumay_base64::base64::Engine;
+use std::collections::BTreeMap;
+
+/// Separator between the key identifier and the base64-encoded ciphertext. It never appears in standard base64.
+pub const KEY_ID_SEPARATOR: char = ':';
+
+/// A set of secretbox keys indexed by identifier, with one active key used for encryption.
+pub struct Keyring {
+    keys: BTreeMap<String, SecureBuffer>,
+    active: String,
+}
+
+impl Keyring {
+    /// Creates a keyring holding a single key, which becomes the active key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key id is invalid, or if the key cannot be base64-decoded or has an invalid length.
+    pub fn new(key_id: &str, key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Keyring> {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::new");
+        let mut keyring = Keyring { keys: BTreeMap::new(), active: key_id.to_string() };
+        keyring.add_key(key_id, key_b64, context)?;
+        Ok(keyring)
+    }
+
+    /// Loads a keyring from a map of key identifier to base64-encoded key, and marks `active_id` as the active key.
+    ///
+    /// Any iterable of pairs works, such as `&BTreeMap<String, String>` or `&HashMap<&str, &str>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key id is invalid, if a key cannot be decoded, or if `active_id` is not part of the map.
+    pub fn from_config<I, K, V>(keys: I, active_id: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Keyring>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::from_config");
+        let mut keyring = Keyring { keys: BTreeMap::new(), active: String::new() };
+        for (key_id, key_b64) in keys {
+            keyring.add_key(key_id.as_ref(), key_b64.as_ref(), context.clone())?;
+        }
+        keyring.set_active(active_id, context)?;
+        Ok(keyring)
+    }
+
+    /// Adds (or replaces) a key without changing the active key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidKeyId`] error if the id is empty or contains [`KEY_ID_SEPARATOR`], or an error if the key cannot be
+    /// base64-decoded or has an invalid length.
+    pub fn add_key(&mut self, key_id: &str, key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<()> {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::add_key");
+        let key_decoded = decode_base64(key_b64, "key", context.clone())?;
+        let key = secretbox::into_secretbox_key(key_decoded, context.clone())?;
+        self.add_secure_key(key_id, SecureBuffer::from_slice(key.as_ref(), context.clone())?, context)
+    }
+
+    /// Adds (or replaces) a key already held in secure memory, without changing the active key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidKeyId`] error if the id is empty or contains [`KEY_ID_SEPARATOR`], or an [`crate::InvalidBoxKeyLength`] error
+    /// if the buffer does not have the secretbox key length.
+    pub fn add_secure_key(&mut self, key_id: &str, key: SecureBuffer, context: impl Into<Option<Context>>) -> cdumay_core::Result<()> {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::add_secure_key");
+        if key_id.is_empty() || key_id.contains(KEY_ID_SEPARATOR) {
+            return Err(InvalidKeyId::new()
+                .with_message(format!("Key id must be non-empty and must not contain '{}'", KEY_ID_SEPARATOR))
+                .with_details(context)
+                .into());
+        }
+        ffi::secretbox_key(&key.read(), context)?;
+        self.keys.insert(key_id.to_string(), key);
+        Ok(())
+    }
+
+    /// Marks an existing key as the active key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`UnknownKeyId`] error if the keyring holds no key with this id.
+    pub fn set_active(&mut self, key_id: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<()> {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::set_active");
+        self.key(key_id, context)?;
+        self.active = key_id.to_string();
+        Ok(())
+    }
+
+    /// Returns the identifier of the active key.
+    pub fn active_id(&self) -> &str {
+        &self.active
+    }
+
+    /// Returns the identifiers of every key, in lexicographic order.
+    pub fn key_ids(&self) -> impl Iterator<Item = &str> {
+        self.keys.keys().map(String::as_str)
+    }
+
+    /// Returns the fingerprint of a key, to log which key is used without logging the key (see [`crate::fingerprint`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`UnknownKeyId`] error if the keyring holds no key with this id.
+    pub fn fingerprint(&self, key_id: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Fingerprint> {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::fingerprint");
+        Ok(Fingerprint::secretbox_key(&self.key(key_id, context)?))
+    }
+
+    /// Returns the identifier of the key having this fingerprint, such as one read from a log line or an envelope.
+    pub fn key_id_by_fingerprint(&self, fingerprint: &Fingerprint) -> Option<&str> {
+        self.keys.iter().find(|(_, key)| Fingerprint::secretbox_key(&key.read()) == *fingerprint).map(|(key_id, _)| key_id.as_str())
+    }
+
+    pub(crate) fn key(&self, key_id: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<SecureRead<'_>> {
+        self.keys.get(key_id).map(SecureBuffer::read).ok_or_else(|| {
+            UnknownKeyId::new()
+                .with_message(format!("No key '{}' in keyring", key_id))
+                .with_details(context)
+                .into()
+        })
+    }
+
+    pub(crate) fn active_key(&self) -> SecureRead<'_> {
+        self.keys[&self.active].read()
+    }
+
+    /// Encrypts data with the active key and returns the base64-encoded nonce and the stamped ciphertext `<key_id>:<ciphertext_b64>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if libsodium cannot be initialized, or, in debug builds, a [`crate::NonceReuse`] error as
+    /// [`crate::secretbox::crypt`] does.
+    pub fn crypt(&self, data: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<(String, String)> {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::crypt");
+        crate::init(context.clone())?;
+        let (nonce, ciphertext) = secretbox::seal_random(Algorithm::XSalsa20Poly1305, data.as_bytes(), &self.active_key(), context)?;
+        Ok((
+            BASE64_STANDARD.encode(nonce.as_ref()),
+            format!("{}{}{}", self.active, KEY_ID_SEPARATOR, BASE64_STANDARD.encode(ciphertext)),
+        ))
+    }
+
+    /// Decrypts a ciphertext produced by [`Keyring::crypt`] or, for legacy values, by [`crate::secretbox::crypt`].
+    ///
+    /// A stamped ciphertext is opened with the key it names. An un-stamped ciphertext is tried against every key of the keyring.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The ciphertext names a key which is not part of the keyring ([`UnknownKeyId`]).
+    /// - The ciphertext or nonce cannot be base64-decoded, or the nonce has an invalid length.
+    /// - No key can open the ciphertext ([`crate::AuthenticationFailed`]).
+    /// - The decrypted data is not valid UTF-8.
+    pub fn decrypt(&self, data: &str, nonce_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::decrypt");
+        crate::init(context.clone())?;
+        if data.is_empty() {
+            return Ok(String::new());
+        }
+        let nonce_decoded = decode_base64(nonce_b64, "nonce", context.clone())?;
+        let nonce = secretbox::into_secretbox_nonce(nonce_decoded, context.clone())?;
+        match data.split_once(KEY_ID_SEPARATOR) {
+            Some((key_id, data_b64)) => {
+                let key = self.key(key_id, context.clone())?;
+                let data_decoded = decode_base64(data_b64, "data", context.clone())?;
+                match secretbox::open_with(Algorithm::XSalsa20Poly1305, &data_decoded, &nonce, &key) {
+                    Some(decrypted) => vec_to_string(decrypted, context),
+                    None => {
+                        let context = with_key_fingerprint(context, Fingerprint::secretbox_key(&key));
+                        Err(authentication_failed(Algorithm::XSalsa20Poly1305.id(), "data", context))
+                    }
+                }
+            }
+            None => {
+                let data_decoded = decode_base64(data, "data", context.clone())?;
+                match self.keys.values().find_map(|key| secretbox::open_with(Algorithm::XSalsa20Poly1305, &data_decoded, &nonce, &key.read())) {
+                    Some(decrypted) => vec_to_string(decrypted, context),
+                    None => Err(authentication_failed(Algorithm::XSalsa20Poly1305.id(), "data", context)),
+                }
+            }
+        }
+    }
+
+    /// Re-encrypts a ciphertext with the active key, for migrating stored values after a rotation.
+    ///
+    /// The ciphertext may be stamped or legacy, as accepted by [`Keyring::decrypt`]. The plaintext only lives in a buffer which is
+    /// wiped once re-encrypted (see [`crate::secretbox::reencrypt`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Keyring::decrypt`], except that the plaintext is not required to be valid UTF-8.
+    pub fn reencrypt(&self, data: &str, nonce_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<(String, String)> {
+        let context = crate::errors::operation(context, module_path!(), "Keyring::reencrypt");
+        crate::init(context.clone())?;
+        let (nonce_b64, data_b64) = match data.split_once(KEY_ID_SEPARATOR) {
+            Some((key_id, data_b64)) => {
+                let key = self.key(key_id, context.clone())?;
+                let open = |data: &[u8], nonce: &secretbox::Nonce| secretbox::open_with(Algorithm::XSalsa20Poly1305, data, nonce, &key);
+                secretbox::reencrypt_with(data_b64, nonce_b64, open, Some(Fingerprint::secretbox_key(&key)), &self.active_key(), context)?
+            }
+            None => secretbox::reencrypt_with(
+                data,
+                nonce_b64,
+                |data, nonce| self.keys.values().find_map(|key| secretbox::open_with(Algorithm::XSalsa20Poly1305, data, nonce, &key.read())),
+                None,
+                &self.active_key(),
+                context,
+            )?,
+        };
+        Ok((nonce_b64, format!("{}{}{}", self.active, KEY_ID_SEPARATOR, data_b64)))
+    }
+}
+
+impl std::fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keyring")
+            .field("keys", &self.keys.keys().collect::<Vec<_>>())
+            .field("active", &self.active)
+            .finish()
+    }
+}
@@ -0,0 +1,390 @@
+//! Import and export of keys in the formats expected by other stacks.
+//!
+//! A [`KeyMaterial`] holds one 32-byte key of a [`KeyType`]: a secretbox key, or one half of an X25519 keypair (sealed boxes,
+//! Noise) or of an Ed25519 keypair. It converts from and to:
+//!
+//! - PEM-like armor ([`KeyMaterial::to_armor`]): the base64 key between `-----BEGIN <TYPE>-----` and `-----END <TYPE>-----`
+//!   lines naming its type, such as `X25519 PRIVATE KEY`. The key is not DER-encoded, so OpenSSL does not read these blocks.
+//! - JSON Web Keys ([`KeyMaterial::to_jwk`]): `OKP` keys on the `X25519` or `Ed25519` curve (RFC 8037), and `oct` keys for
+//!   secretbox keys (RFC 7518), with the key [fingerprint](crate::fingerprint) as `kid`.
+//! - Raw files holding the 32 key bytes ([`KeyMaterial::write_raw_file`]), whose type is given when reading them back.
+//! - The standard base64 strings taken by the rest of the crate ([`KeyMaterial::to_base64`]).
+//!
+//! The key bytes are wiped from memory when a [`KeyMaterial`] is dropped. Wiping the exported strings of secret keys is left to
+//! the caller.
+//!
+//! # Example
+//!
+//! ```
+//! use cdumay_sodium::keys::{KeyMaterial, KeyType};
+//! use cdumay_sodium::sealedbox;
+//!
+//! let private_key = KeyMaterial::from_base64(KeyType::X25519Private, "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=", None).unwrap();
+//! let public_key = private_key.public_key().unwrap();
+//! let ciphertext = sealedbox::crypt("my secret message", &public_key.to_base64(), None).unwrap();
+//!
+//! // The keypair travels as a JWK to another service, and back.
+//! let imported = KeyMaterial::from_jwk(&private_key.to_jwk(), None).unwrap();
+//! assert_eq!(imported, private_key);
+//! let decrypted = sealedbox::decrypt(&ciphertext, &imported.to_base64(), &public_key.to_base64(), None).unwrap();
+//! assert_eq!(decrypted, "my secret message");
+//! ```
+
+use crate::errors::{Detail, enrich, length_details};
+use crate::fingerprint::Fingerprint;
+use crate::noise::Keypair;
+use crate::{
+    Context, InvalidBase64, InvalidKeyFormat, InvalidPrivateKeyLength, InvalidPublicKeyLength, KeyFileUnreadable, KeyFileUnwritable,
+    Zeroizing, decode_base64, ffi,
+};
+use cdumay_base64::base64::engine::general_purpose::{STANDARD as BASE64_STANDARD, URL_SAFE_NO_PAD};
+use cdumay_base64::base64::Engine;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+/// Length of every key type.
+pub const KEYBYTES: usize = 32;
+
+const _: () = {
+    assert!(KEYBYTES == ffi::SECRETBOX_KEYBYTES);
+    assert!(KEYBYTES == ffi::BOX_PUBLICKEYBYTES);
+    assert!(KEYBYTES == ffi::BOX_SECRETKEYBYTES);
+    assert!(KEYBYTES == ffi::SIGN_SEEDBYTES);
+    assert!(KEYBYTES == ffi::SIGN_PUBLICKEYBYTES);
+};
+
+/// The type of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyType {
+    /// A secretbox key.
+    SecretBox,
+    /// An X25519 public key, as used by sealed boxes and Noise.
+    X25519Public,
+    /// An X25519 private key, as used by sealed boxes and Noise.
+    X25519Private,
+    /// An Ed25519 public key.
+    Ed25519Public,
+    /// An Ed25519 private key, as its 32-byte seed.
+    Ed25519Private,
+}
+
+impl KeyType {
+    const ALL: [KeyType; 5] = [KeyType::SecretBox, KeyType::X25519Public, KeyType::X25519Private, KeyType::Ed25519Public, KeyType::Ed25519Private];
+
+    /// Returns the identifier of the type, as reported in error details (`x25519_private`, ...).
+    pub fn id(&self) -> &'static str {
+        match self {
+            KeyType::SecretBox => "secretbox",
+            KeyType::X25519Public => "x25519_public",
+            KeyType::X25519Private => "x25519_private",
+            KeyType::Ed25519Public => "ed25519_public",
+            KeyType::Ed25519Private => "ed25519_private",
+        }
+    }
+
+    /// Returns the type named by the armor lines (`X25519 PRIVATE KEY`, ...).
+    pub fn armor_label(&self) -> &'static str {
+        match self {
+            KeyType::SecretBox => "SECRETBOX KEY",
+            KeyType::X25519Public => "X25519 PUBLIC KEY",
+            KeyType::X25519Private => "X25519 PRIVATE KEY",
+            KeyType::Ed25519Public => "ED25519 PUBLIC KEY",
+            KeyType::Ed25519Private => "ED25519 PRIVATE KEY",
+        }
+    }
+
+    /// Returns `true` for the keys which must be kept secret: secretbox keys and private keys.
+    pub fn is_secret(&self) -> bool {
+        matches!(self, KeyType::SecretBox | KeyType::X25519Private | KeyType::Ed25519Private)
+    }
+
+    /// Returns the JWK curve (`crv`) of the keypair types.
+    fn curve(&self) -> Option<&'static str> {
+        match self {
+            KeyType::SecretBox => None,
+            KeyType::X25519Public | KeyType::X25519Private => Some("X25519"),
+            KeyType::Ed25519Public | KeyType::Ed25519Private => Some("Ed25519"),
+        }
+    }
+}
+
+/// A key and its type, to import or export it.
+///
+/// Equality compares the key bytes in constant time. `Debug` shows the type and the fingerprint, never the key.
+pub struct KeyMaterial {
+    key_type: KeyType,
+    key: Zeroizing,
+}
+
+impl KeyMaterial {
+    /// Builds a key from its raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`crate::InvalidBoxKeyLength`], [`InvalidPublicKeyLength`] or [`InvalidPrivateKeyLength`] error if `key` is not
+    /// [`KEYBYTES`] long.
+    pub fn from_bytes(key_type: KeyType, key: &[u8], context: impl Into<Option<Context>>) -> cdumay_core::Result<KeyMaterial> {
+        let context = crate::errors::operation(context, module_path!(), "KeyMaterial::from_bytes");
+        check_length(key_type, key, context)?;
+        Ok(KeyMaterial { key_type, key: Zeroizing(key.to_vec()) })
+    }
+
+    /// Builds a key from the standard base64 string taken by the rest of the crate.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be base64-decoded or has an invalid length.
+    pub fn from_base64(key_type: KeyType, key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<KeyMaterial> {
+        let context = crate::errors::operation(context, module_path!(), "KeyMaterial::from_base64");
+        let key = Zeroizing(decode_base64(key_b64, "key", context.clone())?);
+        KeyMaterial::from_bytes(key_type, &key, context)
+    }
+
+    /// Builds an [`KeyType::X25519Private`] key from a Noise keypair.
+    pub fn from_keypair(keypair: &Keypair) -> KeyMaterial {
+        KeyMaterial { key_type: KeyType::X25519Private, key: Zeroizing(keypair.private_key().to_vec()) }
+    }
+
+    /// Returns the type of the key.
+    pub fn key_type(&self) -> KeyType {
+        self.key_type
+    }
+
+    /// Returns the raw key.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns the key as a standard base64 string, as taken by the rest of the crate.
+    pub fn to_base64(&self) -> String {
+        BASE64_STANDARD.encode(&*self.key)
+    }
+
+    /// Returns the public key of a private key, itself for a public key, or `None` for a secretbox key.
+    pub fn public_key(&self) -> Option<KeyMaterial> {
+        let public_key = match self.key_type {
+            KeyType::SecretBox => return None,
+            KeyType::X25519Public | KeyType::Ed25519Public => return Some(self.clone()),
+            KeyType::X25519Private => {
+                crate::init_or_panic();
+                (KeyType::X25519Public, ffi::scalarmult_base(self.array()))
+            }
+            KeyType::Ed25519Private => {
+                crate::init_or_panic();
+                (KeyType::Ed25519Public, ffi::sign_seed_public_key(self.array()))
+            }
+        };
+        Some(KeyMaterial { key_type: public_key.0, key: Zeroizing(public_key.1.to_vec()) })
+    }
+
+    /// Returns the fingerprint of the key: the fingerprint of the public key for a keypair, so that both halves share it.
+    pub fn fingerprint(&self) -> Fingerprint {
+        match self.public_key() {
+            Some(public_key) => Fingerprint::public_key(&public_key.key),
+            None => Fingerprint::secretbox_key(&self.key),
+        }
+    }
+
+    /// Returns the Noise keypair of an [`KeyType::X25519Private`] key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidKeyFormat`] error for another type of key.
+    pub fn to_keypair(&self, context: impl Into<Option<Context>>) -> cdumay_core::Result<Keypair> {
+        let context = crate::errors::operation(context, module_path!(), "KeyMaterial::to_keypair");
+        match self.key_type {
+            KeyType::X25519Private => Keypair::from_private_key(&self.key, context),
+            key_type => Err(invalid_format(&format!("a keypair requires an X25519 private key, not {}", key_type.id()), context)),
+        }
+    }
+
+    /// Returns the key as a PEM-like armored block, such as:
+    ///
+    /// ```text
+    /// -----BEGIN X25519 PUBLIC KEY-----
+    /// odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=
+    /// -----END X25519 PUBLIC KEY-----
+    /// ```
+    pub fn to_armor(&self) -> String {
+        let label = self.key_type.armor_label();
+        format!("-----BEGIN {}-----\n{}\n-----END {}-----\n", label, self.to_base64(), label)
+    }
+
+    /// Parses a block written by [`KeyMaterial::to_armor`], whose type is read from its first line. Surrounding whitespace and
+    /// line breaks inside the base64 body are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidKeyFormat`] error if the block is malformed or names an unknown type, or an error if the key cannot be
+    /// base64-decoded or has an invalid length.
+    pub fn from_armor(armor: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<KeyMaterial> {
+        let context = crate::errors::operation(context, module_path!(), "KeyMaterial::from_armor");
+        let mut lines = armor.trim().lines().map(str::trim);
+        let label = lines
+            .next()
+            .and_then(|line| line.strip_prefix("-----BEGIN ")?.strip_suffix("-----"))
+            .ok_or_else(|| invalid_format("missing armor header line", context.clone()))?;
+        let key_type = KeyType::ALL
+            .into_iter()
+            .find(|key_type| key_type.armor_label() == label)
+            .ok_or_else(|| invalid_format(&format!("unsupported armored key type '{}'", label), context.clone()))?;
+        let context = enrich(context, &[("key_type", Detail::Text(key_type.id()))]);
+        let mut lines: Vec<&str> = lines.collect();
+        if lines.pop() != Some(&format!("-----END {}-----", label)) {
+            return Err(invalid_format("missing or mismatched armor footer line", context));
+        }
+        let key_b64: String = lines.concat();
+        KeyMaterial::from_base64(key_type, &key_b64, context)
+    }
+
+    /// Returns the key as a compact JSON Web Key: an `OKP` key with its curve and base64url public key `x` (and private key `d`)
+    /// for keypairs, or an `oct` key with its base64url key `k` for secretbox keys. The `kid` is the key fingerprint.
+    pub fn to_jwk(&self) -> String {
+        let kid = self.fingerprint().to_string();
+        let jwk = match (self.key_type.curve(), self.public_key()) {
+            (Some(curve), Some(public_key)) if self.key_type.is_secret() => serde_json::json!({
+                "kty": "OKP", "crv": curve, "x": URL_SAFE_NO_PAD.encode(&*public_key.key), "d": URL_SAFE_NO_PAD.encode(&*self.key), "kid": kid,
+            }),
+            (Some(curve), _) => serde_json::json!({"kty": "OKP", "crv": curve, "x": URL_SAFE_NO_PAD.encode(&*self.key), "kid": kid}),
+            (None, _) => serde_json::json!({"kty": "oct", "k": URL_SAFE_NO_PAD.encode(&*self.key), "kid": kid}),
+        };
+        jwk.to_string()
+    }
+
+    /// Parses a JSON Web Key: an `OKP` key on the `X25519` or `Ed25519` curve, private if it holds `d`, or an `oct` key of
+    /// [`KEYBYTES`] bytes as a secretbox key. Other members, such as `kid`, `use` or `alg`, are ignored.
+    ///
+    /// # Errors
+    ///
+    /// - The JWK is not a JSON object, has an unsupported `kty` or `crv`, or lacks a required member ([`InvalidKeyFormat`]).
+    /// - A key member is not valid unpadded base64url ([`InvalidBase64`]).
+    /// - The key has an invalid length.
+    /// - The public key `x` of a private key does not match `d` ([`InvalidKeyFormat`]).
+    pub fn from_jwk(jwk: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<KeyMaterial> {
+        let context = crate::errors::operation(context, module_path!(), "KeyMaterial::from_jwk");
+        crate::init(context.clone())?;
+        let jwk: serde_json::Value = serde_json::from_str(jwk).map_err(|err| invalid_format(&format!("invalid JWK: {}", err), context.clone()))?;
+        let member = |name: &str| jwk.get(name).and_then(serde_json::Value::as_str);
+        let key_type = match (member("kty"), member("crv"), jwk.get("d").is_some()) {
+            (Some("oct"), _, _) => KeyType::SecretBox,
+            (Some("OKP"), Some("X25519"), false) => KeyType::X25519Public,
+            (Some("OKP"), Some("X25519"), true) => KeyType::X25519Private,
+            (Some("OKP"), Some("Ed25519"), false) => KeyType::Ed25519Public,
+            (Some("OKP"), Some("Ed25519"), true) => KeyType::Ed25519Private,
+            (Some("OKP"), curve, _) => {
+                return Err(invalid_format(&format!("unsupported JWK curve '{}'", curve.unwrap_or_default()), context));
+            }
+            (kty, _, _) => return Err(invalid_format(&format!("unsupported JWK key type '{}'", kty.unwrap_or_default()), context)),
+        };
+        let context = enrich(context, &[("key_type", Detail::Text(key_type.id()))]);
+        let decode = |name: &str| -> cdumay_core::Result<Zeroizing> {
+            let value = member(name).ok_or_else(|| invalid_format(&format!("missing JWK member '{}'", name), context.clone()))?;
+            URL_SAFE_NO_PAD.decode(value).map(Zeroizing).map_err(|err| {
+                InvalidBase64::new()
+                    .with_message(format!("Invalid base64url {}: {}", name, err))
+                    .with_details(enrich(context.clone(), &[("field", Detail::Text(name)), ("input_length", Detail::Length(value.len()))]))
+                    .into()
+            })
+        };
+        match key_type {
+            KeyType::SecretBox => KeyMaterial::from_bytes(key_type, &decode("k")?, context),
+            KeyType::X25519Public | KeyType::Ed25519Public => KeyMaterial::from_bytes(key_type, &decode("x")?, context),
+            KeyType::X25519Private | KeyType::Ed25519Private => {
+                let public_key = decode("x")?;
+                let key = KeyMaterial::from_bytes(key_type, &decode("d")?, context.clone())?;
+                match key.public_key() {
+                    Some(derived) if ffi::memcmp(&derived.key, &public_key) => Ok(key),
+                    _ => Err(invalid_format("the JWK public key 'x' does not match its private key 'd'", context)),
+                }
+            }
+        }
+    }
+
+    /// Reads a key of the given type from a file holding its raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`KeyFileUnreadable`] error if the file cannot be read, or an error if it does not hold [`KEYBYTES`] bytes. The
+    /// details hold the `path`.
+    pub fn from_raw_file(key_type: KeyType, path: impl AsRef<Path>, context: impl Into<Option<Context>>) -> cdumay_core::Result<KeyMaterial> {
+        let context = crate::errors::operation(context, module_path!(), "KeyMaterial::from_raw_file");
+        let path = path.as_ref();
+        let context = enrich(context, &[("path", Detail::Text(&path.display().to_string()))]);
+        match std::fs::read(path) {
+            Ok(key) => KeyMaterial::from_bytes(key_type, &Zeroizing(key), context),
+            Err(err) => Err(KeyFileUnreadable::new().with_message(err.to_string()).with_details(context).into()),
+        }
+    }
+
+    /// Writes the raw key bytes to a file, replacing its content. On Unix, a new file is only readable and writable by its owner.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`KeyFileUnwritable`] error if the file cannot be written. The details hold the `path`.
+    pub fn write_raw_file(&self, path: impl AsRef<Path>, context: impl Into<Option<Context>>) -> cdumay_core::Result<()> {
+        let context = crate::errors::operation(context, module_path!(), "KeyMaterial::write_raw_file");
+        let path = path.as_ref();
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(path).and_then(|mut file| file.write_all(&self.key)).map_err(|err| {
+            KeyFileUnwritable::new()
+                .with_message(err.to_string())
+                .with_details(enrich(context, &[("path", Detail::Text(&path.display().to_string()))]))
+                .into()
+        })
+    }
+
+    /// Returns the key as an array, its length being checked at construction.
+    fn array(&self) -> &[u8; KEYBYTES] {
+        self.key[..].try_into().unwrap_or_else(|_| unreachable!())
+    }
+}
+
+impl Clone for KeyMaterial {
+    fn clone(&self) -> KeyMaterial {
+        KeyMaterial { key_type: self.key_type, key: Zeroizing(self.key.to_vec()) }
+    }
+}
+
+impl PartialEq for KeyMaterial {
+    fn eq(&self, other: &KeyMaterial) -> bool {
+        self.key_type == other.key_type && ffi::memcmp(&self.key, &other.key)
+    }
+}
+
+impl Eq for KeyMaterial {}
+
+impl fmt::Debug for KeyMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyMaterial").field("key_type", &self.key_type).field("fingerprint", &self.fingerprint()).finish_non_exhaustive()
+    }
+}
+
+/// Checks that `key` is [`KEYBYTES`] long, with the length error of the crate for its type.
+fn check_length(key_type: KeyType, key: &[u8], context: Context) -> cdumay_core::Result<()> {
+    if key_type == KeyType::SecretBox {
+        return ffi::secretbox_key(key, context).map(|_| ());
+    }
+    if key.len() == KEYBYTES {
+        return Ok(());
+    }
+    let algorithm = key_type.curve().map(str::to_lowercase);
+    let message = format!("Invalid {} key length required: {}", key_type.id(), KEYBYTES);
+    match key_type.is_secret() {
+        true => Err(InvalidPrivateKeyLength::new()
+            .with_message(message)
+            .with_details(length_details(context, algorithm.as_deref(), "private_key", KEYBYTES, key.len()))
+            .into()),
+        false => Err(InvalidPublicKeyLength::new()
+            .with_message(message)
+            .with_details(length_details(context, algorithm.as_deref(), "public_key", KEYBYTES, key.len()))
+            .into()),
+    }
+}
+
+fn invalid_format(message: &str, context: Context) -> cdumay_core::Error {
+    InvalidKeyFormat::new().with_message(format!("Invalid key format: {}", message)).with_details(context).into()
+}
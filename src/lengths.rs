@@ -0,0 +1,100 @@
+//! Lengths of the secretbox and sealed-box buffers, and the checked conversions turning untrusted slices into arrays of those
+//! lengths.
+//!
+//! The conversions map a wrong length to the error of the input, with the `field`, `expected_length` and `actual_length` details.
+//! They do not call libsodium, so they are shared by [`crate::backend::RustCrypto`] and available without the `sodium` feature;
+//! [`crate::ffi`] checks at compile time that the constants match libsodium.
+
+// Without a backend (no `sodium` nor `rustcrypto` feature), only the error types are left to use the checks.
+#![cfg_attr(not(any(feature = "sodium", feature = "rustcrypto")), allow(dead_code))]
+
+use crate::errors::length_details;
+use crate::{InvalidBoxKeyLength, InvalidBoxNonceLength, InvalidBoxTagLength, InvalidPrivateKeyLength, InvalidPublicKeyLength};
+use alloc::format;
+
+/// Length of a secretbox key, for both secretbox algorithms.
+pub(crate) const SECRETBOX_KEYBYTES: usize = 32;
+/// Length of a secretbox nonce, for both secretbox algorithms.
+pub(crate) const SECRETBOX_NONCEBYTES: usize = 24;
+/// Length of a secretbox authentication tag, for both secretbox algorithms.
+pub(crate) const SECRETBOX_MACBYTES: usize = 16;
+
+/// Length of a `crypto_box` public key.
+pub(crate) const BOX_PUBLICKEYBYTES: usize = 32;
+/// Length of a `crypto_box` private key.
+pub(crate) const BOX_SECRETKEYBYTES: usize = 32;
+/// Overhead of a sealed box over its plaintext: the ephemeral public key and the authentication tag.
+pub(crate) const BOX_SEALBYTES: usize = BOX_PUBLICKEYBYTES + SECRETBOX_MACBYTES;
+/// Identifier of the sealed-box construction (`crypto_box_seal`), as reported in error details.
+pub(crate) const SEALEDBOX_ALGORITHM: &str = "curve25519xsalsa20poly1305";
+
+/// Checks the length of a secretbox key.
+///
+/// # Errors
+///
+/// Returns an [`InvalidBoxKeyLength`] error if `key` is not [`SECRETBOX_KEYBYTES`] long.
+pub(crate) fn secretbox_key(key: &[u8], context: crate::Context) -> crate::Result<&[u8; SECRETBOX_KEYBYTES]> {
+    key.try_into().map_err(|_| {
+        InvalidBoxKeyLength::new()
+            .with_message(format!("Invalid box_key length required: {}", SECRETBOX_KEYBYTES))
+            .with_details(length_details(context, None, "key", SECRETBOX_KEYBYTES, key.len()))
+            .into()
+    })
+}
+
+/// Checks the length of a secretbox nonce.
+///
+/// # Errors
+///
+/// Returns an [`InvalidBoxNonceLength`] error if `nonce` is not [`SECRETBOX_NONCEBYTES`] long.
+pub(crate) fn secretbox_nonce(nonce: &[u8], context: crate::Context) -> crate::Result<&[u8; SECRETBOX_NONCEBYTES]> {
+    nonce.try_into().map_err(|_| {
+        InvalidBoxNonceLength::new()
+            .with_message(format!("Invalid box_nonce length required: {}", SECRETBOX_NONCEBYTES))
+            .with_details(length_details(context, None, "nonce", SECRETBOX_NONCEBYTES, nonce.len()))
+            .into()
+    })
+}
+
+/// Checks the length of a detached secretbox authentication tag.
+///
+/// # Errors
+///
+/// Returns an [`InvalidBoxTagLength`] error if `tag` is not [`SECRETBOX_MACBYTES`] long.
+#[cfg_attr(not(feature = "sodium"), allow(dead_code))]
+pub(crate) fn secretbox_tag(tag: &[u8], context: crate::Context) -> crate::Result<&[u8; SECRETBOX_MACBYTES]> {
+    tag.try_into().map_err(|_| {
+        InvalidBoxTagLength::new()
+            .with_message(format!("Invalid box_tag length required: {}", SECRETBOX_MACBYTES))
+            .with_details(length_details(context, None, "tag", SECRETBOX_MACBYTES, tag.len()))
+            .into()
+    })
+}
+
+/// Checks the length of a sealed-box public key.
+///
+/// # Errors
+///
+/// Returns an [`InvalidPublicKeyLength`] error if `key` is not [`BOX_PUBLICKEYBYTES`] long.
+pub(crate) fn box_public_key(key: &[u8], context: crate::Context) -> crate::Result<&[u8; BOX_PUBLICKEYBYTES]> {
+    key.try_into().map_err(|_| {
+        InvalidPublicKeyLength::new()
+            .with_message(format!("Invalid public key length for sealed box, required: {}", BOX_PUBLICKEYBYTES))
+            .with_details(length_details(context, Some(SEALEDBOX_ALGORITHM), "public_key", BOX_PUBLICKEYBYTES, key.len()))
+            .into()
+    })
+}
+
+/// Checks the length of a sealed-box private key.
+///
+/// # Errors
+///
+/// Returns an [`InvalidPrivateKeyLength`] error if `key` is not [`BOX_SECRETKEYBYTES`] long.
+pub(crate) fn box_secret_key(key: &[u8], context: crate::Context) -> crate::Result<&[u8; BOX_SECRETKEYBYTES]> {
+    key.try_into().map_err(|_| {
+        InvalidPrivateKeyLength::new()
+            .with_message(format!("Invalid private key length for sealed box, required: {}", BOX_SECRETKEYBYTES))
+            .with_details(length_details(context, Some(SEALEDBOX_ALGORITHM), "private_key", BOX_SECRETKEYBYTES, key.len()))
+            .into()
+    })
+}
@@ -3,17 +3,120 @@
 //! [![cdumay_sodium on docs.rs](https://docs.rs/cdumay_sodium/badge.svg)](https://docs.rs/cdumay_sodium)
 //! [![Source Code Repository](https://img.shields.io/badge/Code-On%20GitHub-blue?logo=GitHub)](https://github.com/cdumay/cdumay_sodium)
 //!
-//! This crate provides functions and errors related to [libsodium](https://doc.libsodium.org/) sealed-box and secret-box usages.
+//! This crate provides functions and errors related to [libsodium](https://doc.libsodium.org/) sealed-box and secret-box usages, and Noise protocol handshakes built on them.
 //!
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+#[cfg(feature = "sodium")]
 extern crate libsodium_sys as sodium;
 mod errors;
 
+#[cfg(not(feature = "std"))]
+mod core_error;
+
+mod lengths;
+
+#[cfg(feature = "sodium")]
+mod ffi;
+
 pub use errors::*;
 
+#[cfg(feature = "sodium")]
 pub mod secretbox;
 
+#[cfg(feature = "sodium")]
 pub mod sealedbox;
 
+#[cfg(feature = "sodium")]
+pub mod noise;
+
+#[cfg(feature = "sodium")]
+pub mod envelope;
+
+#[cfg(feature = "sodium")]
+pub mod keyring;
+
+#[cfg(feature = "sodium")]
+pub mod serde;
+
+#[cfg(feature = "sodium")]
+pub mod encrypted;
+
+#[cfg(feature = "sodium")]
+pub mod paths;
+
+#[cfg(feature = "sodium")]
+pub mod config;
+
+#[cfg(feature = "sodium")]
+pub mod env;
+
+#[cfg(feature = "sodium")]
+pub mod blind_index;
+
+#[cfg(feature = "sodium")]
+pub mod nonce;
+
+#[cfg(feature = "sodium")]
+pub mod fingerprint;
+
+#[cfg(feature = "sodium")]
+pub mod keys;
+
+#[cfg(feature = "sodium")]
+pub mod memory;
+
+pub mod backend;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+/// Outcome of the single `sodium_init()` call of the process.
+#[cfg(feature = "sodium")]
+static SODIUM_INIT: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+/// Initializes libsodium, once per process.
+///
+/// `sodium_init()` is only called the first time, whichever thread gets there first; later calls return the recorded outcome.
+/// Every entry point of the crate goes through this function before touching libsodium, so calling it explicitly is only useful
+/// to detect a failure early, for example at service startup.
+///
+/// # Errors
+///
+/// Returns a [`SodiumInitFailed`] error if `sodium_init()` returned `-1`, in which case no libsodium function may be used.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+///
+/// cdumay_sodium::init(BTreeMap::<String, Value>::new()).expect("libsodium is unusable");
+/// ```
+#[cfg(feature = "sodium")]
+pub fn init(context: impl Into<Option<Context>>) -> cdumay_core::Result<()> {
+    let context = crate::errors::operation(context, module_path!(), "init");
+    match *SODIUM_INIT.get_or_init(ffi::init) {
+        true => Ok(()),
+        false => Err(SodiumInitFailed::new()
+            .with_message("sodium_init() failed, libsodium cannot be used".to_string())
+            .with_details(context)
+            .into()),
+    }
+}
+
+/// Initializes libsodium from the infallible constructors which draw random bytes.
+///
+/// # Panics
+///
+/// Panics if [`init`] fails.
+#[cfg(feature = "sodium")]
+pub(crate) fn init_or_panic() {
+    if let Err(err) = init(std::collections::BTreeMap::new()) {
+        panic!("{}", err);
+    }
+}
+
 /// Converts a vector of bytes (`Vec<u8>`) into a UTF-8 string.
 ///
 /// This function attempts to convert the provided byte vector into a `String` using
@@ -34,7 +137,69 @@ pub mod sealedbox;
 ///
 /// Returns an error if the input data is not valid UTF-8. The error includes a message
 /// and the provided context for easier debugging.
-/// 
+///
+#[cfg(feature = "sodium")]
 fn vec_to_string(data: Vec<u8>, context: std::collections::BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<String> {
     String::from_utf8(data).map_err(|err| InvalidContent::new().with_message(err.to_string()).with_details(context).into())
 }
+
+/// Decodes a standard base64 input of the crate (a key, a nonce, a ciphertext...), named `field` in the error details.
+///
+/// # Errors
+///
+/// Returns an [`InvalidBase64`] error if the input is not valid base64. The details hold the `field`, the `input_length` and the
+/// `base64_variant` the input is written in, if another one.
+#[cfg(feature = "sodium")]
+fn decode_base64(
+    data: impl AsRef<[u8]>,
+    field: &str,
+    context: std::collections::BTreeMap<String, serde_value::Value>,
+) -> cdumay_core::Result<Vec<u8>> {
+    use cdumay_base64::base64::Engine;
+    let data = data.as_ref();
+    cdumay_base64::base64::engine::general_purpose::STANDARD.decode(data).map_err(|err| {
+        let details = [
+            ("field", errors::Detail::Text(field)),
+            ("input_length", errors::Detail::Length(data.len())),
+            ("base64_variant", errors::Detail::Text(base64_variant(data))),
+        ];
+        InvalidBase64::new()
+            .with_message(format!("Invalid base64 {}: {}", field, err))
+            .with_details(errors::enrich(context, &details))
+            .into()
+    })
+}
+
+/// Names the base64 variant which decodes `data`, to point at an input produced by another encoder. The decoded bytes are wiped.
+#[cfg(feature = "sodium")]
+fn base64_variant(data: &[u8]) -> &'static str {
+    use cdumay_base64::base64::Engine;
+    use cdumay_base64::base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+    let variants = [("url_safe", URL_SAFE), ("url_safe_no_pad", URL_SAFE_NO_PAD), ("standard_no_pad", STANDARD_NO_PAD)];
+    variants
+        .into_iter()
+        .find(|(_, engine)| engine.decode(data).map(Zeroizing).is_ok())
+        .map_or("unknown", |(name, _)| name)
+}
+
+/// A byte buffer wiped with `sodium_memzero` when dropped, used to hold transient plaintexts and keys.
+///
+/// Returned by the functions handing key material out, such as [`env::KeySource::load`]; the bytes are read through `Deref`.
+#[cfg(feature = "sodium")]
+pub struct Zeroizing(pub(crate) Vec<u8>);
+
+#[cfg(feature = "sodium")]
+impl std::ops::Deref for Zeroizing {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "sodium")]
+impl Drop for Zeroizing {
+    fn drop(&mut self) {
+        ffi::memzero(&mut self.0)
+    }
+}
@@ -0,0 +1,181 @@
+//! Guarded memory for long-lived secrets, backed by libsodium's secure allocator.
+//!
+//! A [`SecureBuffer`] is allocated with `sodium_malloc`: the region is surrounded by guard pages, preceded by a canary, locked in
+//! RAM by `sodium_malloc` itself so it is not swapped out nor written to core dumps (where the platform allows it), and wiped when
+//! freed. Locking is best effort: it fails silently when `RLIMIT_MEMLOCK` is exhausted.
+//!
+//! Between accesses the region is made inaccessible with `sodium_mprotect_noaccess`. Reading or writing goes through scoped
+//! guards ([`SecureBuffer::read`] and [`SecureBuffer::write`]) which lift the protection while they are alive and restore it when
+//! dropped, so a stray pointer into the buffer faults instead of leaking the secret.
+//!
+//! [`crate::keyring::Keyring`], [`crate::envelope::SecretBoxKek`], [`crate::envelope::SealedBoxKek`], [`crate::noise::Keypair`] and
+//! the sealed-box keypair of a [`crate::serde::KeyScope`] keep their secret keys in secure buffers.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde_value::Value;
+//! use cdumay_sodium::memory::SecureBuffer;
+//!
+//! let context = BTreeMap::<String, Value>::new();
+//! let mut buffer = SecureBuffer::new(32, context.clone()).unwrap();
+//! buffer.write().copy_from_slice(&[7u8; 32]);
+//! assert_eq!(&*buffer.read(), &[7u8; 32]);
+//!
+//! let key = SecureBuffer::from_base64("llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=", context).unwrap();
+//! assert_eq!(key.len(), 32);
+//! ```
+
+use crate::{Context, SecureMemoryAllocationFailed, Zeroizing, decode_base64, ffi};
+use std::ptr::NonNull;
+use std::sync::Mutex;
+
+/// A fixed-size byte buffer in guarded, locked memory, inaccessible outside of borrow guards.
+///
+/// Read guards can be held concurrently, from several threads: the buffer stays readable until the last one is dropped.
+pub struct SecureBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    readers: Mutex<usize>,
+}
+
+// The buffer owns its allocation. Protection changes of shared (`&self`) access happen under the `readers` lock, and writes
+// require exclusive (`&mut self`) access.
+unsafe impl Send for SecureBuffer {}
+unsafe impl Sync for SecureBuffer {}
+
+impl SecureBuffer {
+    /// Allocates a zero-filled buffer of `len` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SecureMemoryAllocationFailed`] error if `sodium_malloc` fails, or a [`crate::SodiumInitFailed`] error if
+    /// libsodium cannot be initialized.
+    pub fn new(len: usize, context: impl Into<Option<Context>>) -> cdumay_core::Result<SecureBuffer> {
+        let context = crate::errors::operation(context, module_path!(), "SecureBuffer::new");
+        crate::init(context.clone())?;
+        let ptr = ffi::malloc(len).ok_or_else(|| -> cdumay_core::Error {
+            SecureMemoryAllocationFailed::new()
+                .with_message(format!("sodium_malloc failed to allocate {} bytes", len))
+                .with_details(context)
+                .into()
+        })?;
+        unsafe {
+            ffi::memzero(std::slice::from_raw_parts_mut(ptr.as_ptr(), len));
+            ffi::mprotect_noaccess(ptr);
+        }
+        Ok(SecureBuffer { ptr, len, readers: Mutex::new(0) })
+    }
+
+    /// Allocates a buffer holding a copy of `data`. The caller remains responsible for wiping `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SecureMemoryAllocationFailed`] error if `sodium_malloc` fails.
+    pub fn from_slice(data: &[u8], context: impl Into<Option<Context>>) -> cdumay_core::Result<SecureBuffer> {
+        let context = crate::errors::operation(context, module_path!(), "SecureBuffer::from_slice");
+        let mut buffer = SecureBuffer::new(data.len(), context)?;
+        buffer.write().copy_from_slice(data);
+        Ok(buffer)
+    }
+
+    /// Allocates a buffer holding the decoded bytes of a base64 string; the intermediate decoded copy is wiped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string cannot be base64-decoded, or a [`SecureMemoryAllocationFailed`] error if `sodium_malloc`
+    /// fails.
+    pub fn from_base64(data_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<SecureBuffer> {
+        let context = crate::errors::operation(context, module_path!(), "SecureBuffer::from_base64");
+        let decoded = Zeroizing(decode_base64(data_b64, "data", context.clone())?);
+        SecureBuffer::from_slice(&decoded, context)
+    }
+
+    /// Returns the length of the buffer, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Makes the buffer readable until the returned guard, and every other read guard, are dropped.
+    pub fn read(&self) -> SecureRead<'_> {
+        let mut readers = self.readers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if *readers == 0 {
+            unsafe { ffi::mprotect_readonly(self.ptr) };
+        }
+        *readers += 1;
+        SecureRead { buffer: self }
+    }
+
+    /// Makes the buffer readable and writable until the returned guard is dropped.
+    pub fn write(&mut self) -> SecureWrite<'_> {
+        unsafe { ffi::mprotect_readwrite(self.ptr) };
+        SecureWrite { buffer: self }
+    }
+}
+
+impl Drop for SecureBuffer {
+    fn drop(&mut self) {
+        // sodium_free lifts the protection, wipes, unlocks and releases the region.
+        unsafe { ffi::free(self.ptr) }
+    }
+}
+
+impl std::fmt::Debug for SecureBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecureBuffer").field("len", &self.len).finish()
+    }
+}
+
+/// Read access to a [`SecureBuffer`]; the buffer is made inaccessible again when the last read guard is dropped.
+pub struct SecureRead<'a> {
+    buffer: &'a SecureBuffer,
+}
+
+impl std::ops::Deref for SecureRead<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.buffer.ptr.as_ptr(), self.buffer.len) }
+    }
+}
+
+impl Drop for SecureRead<'_> {
+    fn drop(&mut self) {
+        let mut readers = self.buffer.readers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *readers -= 1;
+        if *readers == 0 {
+            unsafe { ffi::mprotect_noaccess(self.buffer.ptr) };
+        }
+    }
+}
+
+/// Write access to a [`SecureBuffer`]; the buffer is made inaccessible again when the guard is dropped.
+pub struct SecureWrite<'a> {
+    buffer: &'a mut SecureBuffer,
+}
+
+impl std::ops::Deref for SecureWrite<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.buffer.ptr.as_ptr(), self.buffer.len) }
+    }
+}
+
+impl std::ops::DerefMut for SecureWrite<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.buffer.ptr.as_ptr(), self.buffer.len) }
+    }
+}
+
+impl Drop for SecureWrite<'_> {
+    fn drop(&mut self) {
+        unsafe { ffi::mprotect_noaccess(self.buffer.ptr) };
+    }
+}
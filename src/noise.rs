@@ -0,0 +1,762 @@
+//! The [Noise Protocol Framework](https://noiseprotocol.org/noise.html) describes how two parties run a Diffie-Hellman based
+//! handshake to agree on transport keys while authenticating each other's static keys.
+//!
+//! This module implements the handshake state machine for the `25519_ChaChaPoly_BLAKE2b` suite on top of libsodium:
+//!
+//! * X25519 (`crypto_scalarmult`) for Diffie-Hellman.
+//! * ChaCha20-Poly1305 IETF (`crypto_aead_chacha20poly1305_ietf`) for the cipher.
+//! * BLAKE2b-512 (`crypto_generichash`) for hashing, HMAC and HKDF.
+//!
+//! The following interactive patterns are supported:
+//!
+//! * [`HandshakePattern::NK`]: the initiator knows the responder's static key in advance, the initiator stays anonymous.
+//! * [`HandshakePattern::IK`]: the initiator knows the responder's static key in advance and sends its own static key encrypted in the
+//!   first message.
+//! * [`HandshakePattern::XX`]: neither side knows the other's static key, both are transmitted during the handshake.
+//!
+//! X25519 keys are the same as the `crypto_box` keys used by [`crate::sealedbox`], so a sealed-box keypair can be used as a Noise
+//! static keypair.
+
+use crate::memory::{SecureBuffer, SecureRead};
+use crate::{Context, FailedToOpenNoiseMessage, InvalidNoiseHandshakeState, InvalidNoiseKeyLength, decode_base64, ffi};
+use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use cdumay_base64::base64::Engine;
+use std::collections::BTreeMap;
+
+/// Length of X25519 public and private keys.
+pub const DHLEN: usize = ffi::SCALARMULT_BYTES;
+/// Length of the BLAKE2b-512 digest used as handshake hash.
+pub const HASHLEN: usize = 64;
+/// Length of the Poly1305 authentication tag appended to each encrypted payload.
+pub const TAGLEN: usize = ffi::AEAD_ABYTES;
+/// Maximum size of a Noise message, as set by the specification.
+pub const MAX_MESSAGE_LEN: usize = 65535;
+
+const BLOCKLEN: usize = 128;
+const KEYLEN: usize = ffi::AEAD_KEYBYTES;
+const NONCELEN: usize = ffi::AEAD_NPUBBYTES;
+
+/// Supported handshake patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakePattern {
+    /// `<- s ... -> e, es / <- e, ee`
+    NK,
+    /// `<- s ... -> e, es, s, ss / <- e, ee, se`
+    IK,
+    /// `-> e / <- e, ee, s, es / -> s, se`
+    XX,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    E,
+    S,
+    EE,
+    ES,
+    SE,
+    SS,
+}
+
+impl HandshakePattern {
+    /// Returns the full protocol name, e.g. `Noise_XX_25519_ChaChaPoly_BLAKE2b`.
+    pub fn protocol_name(&self) -> String {
+        format!("Noise_{}_25519_ChaChaPoly_BLAKE2b", self.name())
+    }
+
+    /// Returns the pattern name, e.g. `XX`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HandshakePattern::NK => "NK",
+            HandshakePattern::IK => "IK",
+            HandshakePattern::XX => "XX",
+        }
+    }
+
+    /// Whether the responder's static key is a pre-message (must be known in advance by the initiator).
+    fn responder_static_premessage(&self) -> bool {
+        matches!(self, HandshakePattern::NK | HandshakePattern::IK)
+    }
+
+    /// Whether the initiator owns a static key in this pattern.
+    fn initiator_has_static(&self) -> bool {
+        matches!(self, HandshakePattern::IK | HandshakePattern::XX)
+    }
+
+    fn messages(&self) -> &'static [&'static [Token]] {
+        use Token::*;
+        match self {
+            HandshakePattern::NK => &[&[E, ES], &[E, EE]],
+            HandshakePattern::IK => &[&[E, ES, S, SS], &[E, EE, SE]],
+            HandshakePattern::XX => &[&[E], &[E, EE, S, ES], &[S, SE]],
+        }
+    }
+}
+
+/// An X25519 keypair used as static or ephemeral Noise key.
+///
+/// The private key is kept in a [`SecureBuffer`], wiped from memory when the keypair is dropped.
+pub struct Keypair {
+    private: SecureBuffer,
+    public: [u8; DHLEN],
+}
+
+impl Keypair {
+    /// Generates a new random keypair.
+    ///
+    /// # Panics
+    ///
+    /// Panics if libsodium cannot be initialized (see [`crate::init`]) or if the secure buffer of the private key cannot be
+    /// allocated.
+    pub fn generate() -> Keypair {
+        crate::init_or_panic();
+        let mut private = [0u8; DHLEN];
+        ffi::random_bytes(&mut private);
+        Keypair::from_private_array(private, BTreeMap::new()).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Builds a keypair from a raw private key, deriving the public key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidNoiseKeyLength`] error if `private_key` is not [`DHLEN`] bytes long, or a
+    /// [`crate::SecureMemoryAllocationFailed`] error if the secure buffer cannot be allocated.
+    pub fn from_private_key(private_key: &[u8], context: impl Into<Option<Context>>) -> cdumay_core::Result<Keypair> {
+        let context = crate::errors::operation(context, module_path!(), "Keypair::from_private_key");
+        crate::init(context.clone())?;
+        Keypair::from_private_array(into_dh_key(private_key, context.clone())?, context)
+    }
+
+    /// Builds a keypair from a base64-encoded private key, such as a sealed-box private key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be base64-decoded or does not have the expected length.
+    pub fn from_base64(private_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Keypair> {
+        let context = crate::errors::operation(context, module_path!(), "Keypair::from_base64");
+        let decoded = crate::Zeroizing(decode_base64(private_key_b64, "private_key", context.clone())?);
+        Keypair::from_private_key(&decoded, context)
+    }
+
+    /// Moves the private key into a secure buffer, wiping `private`.
+    fn from_private_array(mut private: [u8; DHLEN], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Keypair> {
+        let public = ffi::scalarmult_base(&private);
+        let buffer = SecureBuffer::from_slice(&private, context);
+        ffi::memzero(&mut private);
+        Ok(Keypair { private: buffer?, public })
+    }
+
+    /// Returns the public key.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public
+    }
+
+    /// Returns the private key, for key export. The key stays readable while the guard is alive.
+    pub(crate) fn private_key(&self) -> SecureRead<'_> {
+        self.private.read()
+    }
+
+    /// Returns the base64-encoded public key.
+    pub fn public_key_b64(&self) -> String {
+        BASE64_STANDARD.encode(self.public)
+    }
+
+    /// Returns the fingerprint of the public key. It matches [`crate::sealedbox::fingerprint`] for the same key.
+    pub fn fingerprint(&self) -> crate::fingerprint::Fingerprint {
+        crate::fingerprint::Fingerprint::public_key(&self.public)
+    }
+}
+
+impl std::fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keypair").field("public", &self.public_key_b64()).finish_non_exhaustive()
+    }
+}
+
+impl Clone for Keypair {
+    /// Copies the private key into a new secure buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the secure buffer cannot be allocated.
+    fn clone(&self) -> Keypair {
+        let private = SecureBuffer::from_slice(&self.private.read(), BTreeMap::new()).unwrap_or_else(|err| panic!("{}", err));
+        Keypair { private, public: self.public }
+    }
+}
+
+fn into_dh_key(v: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<[u8; DHLEN]> {
+    v.try_into().map_err(|_| {
+        InvalidNoiseKeyLength::new()
+            .with_message(format!("Invalid noise key length required: {}", DHLEN))
+            .with_details(context)
+            .into()
+    })
+}
+
+fn handshake_error(message: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Error {
+    InvalidNoiseHandshakeState::new()
+        .with_message(message.to_string())
+        .with_details(context)
+        .into()
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; HASHLEN] {
+    let mut out = [0u8; HASHLEN];
+    ffi::generichash(&mut out, &crate::Zeroizing(parts.concat()), None);
+    out
+}
+
+/// HMAC-BLAKE2b, wiping the padded keys and the inner hash.
+fn hmac(key: &[u8], data: &[&[u8]]) -> [u8; HASHLEN] {
+    let mut ipad = [0x36u8; BLOCKLEN];
+    let mut opad = [0x5cu8; BLOCKLEN];
+    for (i, byte) in key.iter().enumerate() {
+        ipad[i] ^= byte;
+        opad[i] ^= byte;
+    }
+    let mut inner_parts: Vec<&[u8]> = vec![&ipad];
+    inner_parts.extend_from_slice(data);
+    let mut inner = hash(&inner_parts);
+    let out = hash(&[&opad, &inner]);
+    ffi::memzero(&mut ipad);
+    ffi::memzero(&mut opad);
+    ffi::memzero(&mut inner);
+    out
+}
+
+/// HKDF with two outputs, wiping the temporary key.
+fn hkdf(chaining_key: &[u8], input_key_material: &[u8]) -> ([u8; HASHLEN], [u8; HASHLEN]) {
+    let mut temp_key = hmac(chaining_key, &[input_key_material]);
+    let output1 = hmac(&temp_key, &[&[0x01]]);
+    let output2 = hmac(&temp_key, &[&output1, &[0x02]]);
+    ffi::memzero(&mut temp_key);
+    (output1, output2)
+}
+
+fn dh(keypair: &Keypair, public_key: &[u8; DHLEN], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<[u8; DHLEN]> {
+    let private = keypair.private.read();
+    let private = (&*private).try_into().expect("keypair private keys are DHLEN bytes");
+    ffi::scalarmult(private, public_key).ok_or_else(|| {
+        FailedToOpenNoiseMessage::new()
+            .with_message("Invalid remote public key".to_string())
+            .with_details(context)
+            .into()
+    })
+}
+
+/// A cipher key and its nonce counter, as defined by the `CipherState` object of the specification.
+struct CipherState {
+    key: Option<[u8; KEYLEN]>,
+    nonce: u64,
+}
+
+impl CipherState {
+    fn new(key: Option<[u8; KEYLEN]>) -> CipherState {
+        CipherState { key, nonce: 0 }
+    }
+
+    fn from_hkdf_output(output: &[u8; HASHLEN]) -> CipherState {
+        let mut key = [0u8; KEYLEN];
+        key.copy_from_slice(&output[..KEYLEN]);
+        CipherState::new(Some(key))
+    }
+
+    fn has_key(&self) -> bool {
+        self.key.is_some()
+    }
+
+    fn nonce_bytes(&self) -> [u8; NONCELEN] {
+        let mut nonce = [0u8; NONCELEN];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        nonce
+    }
+
+    fn encrypt_with_ad(&mut self, ad: &[u8], plaintext: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        let Some(key) = self.key.as_ref() else {
+            return Ok(plaintext.to_vec());
+        };
+        if self.nonce == u64::MAX {
+            return Err(handshake_error("Nonce space exhausted, a new session is required", context));
+        }
+        let ciphertext = ffi::aead_encrypt(plaintext, ad, &self.nonce_bytes(), key);
+        self.nonce += 1;
+        Ok(ciphertext)
+    }
+
+    fn decrypt_with_ad(&mut self, ad: &[u8], ciphertext: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        let Some(key) = self.key.as_ref() else {
+            return Ok(ciphertext.to_vec());
+        };
+        if self.nonce == u64::MAX {
+            return Err(handshake_error("Nonce space exhausted, a new session is required", context));
+        }
+        if ciphertext.len() < TAGLEN {
+            return Err(FailedToOpenNoiseMessage::new()
+                .with_message("Ciphertext too short for noise message".to_string())
+                .with_details(context)
+                .into());
+        }
+        match ffi::aead_decrypt(ciphertext, ad, &self.nonce_bytes(), key) {
+            Some(plaintext) => {
+                self.nonce += 1;
+                Ok(plaintext)
+            }
+            None => Err(FailedToOpenNoiseMessage::new()
+                .with_message("Decryption failed".to_string())
+                .with_details(context)
+                .into()),
+        }
+    }
+}
+
+impl Drop for CipherState {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.as_mut() {
+            ffi::memzero(key)
+        }
+    }
+}
+
+/// The chaining key, handshake hash and current cipher, as defined by the `SymmetricState` object of the specification.
+struct SymmetricState {
+    cipher: CipherState,
+    chaining_key: [u8; HASHLEN],
+    hash: [u8; HASHLEN],
+}
+
+impl SymmetricState {
+    fn new(protocol_name: &str) -> SymmetricState {
+        let hash = match protocol_name.len() <= HASHLEN {
+            true => {
+                let mut h = [0u8; HASHLEN];
+                h[..protocol_name.len()].copy_from_slice(protocol_name.as_bytes());
+                h
+            }
+            false => self::hash(&[protocol_name.as_bytes()]),
+        };
+        SymmetricState {
+            cipher: CipherState::new(None),
+            chaining_key: hash,
+            hash,
+        }
+    }
+
+    fn mix_key(&mut self, input_key_material: &[u8]) {
+        let (chaining_key, mut temp_key) = hkdf(&self.chaining_key, input_key_material);
+        self.chaining_key = chaining_key;
+        self.cipher = CipherState::from_hkdf_output(&temp_key);
+        ffi::memzero(&mut temp_key);
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.hash = hash(&[&self.hash, data]);
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        let ciphertext = self.cipher.encrypt_with_ad(&self.hash, plaintext, context)?;
+        self.mix_hash(&ciphertext);
+        Ok(ciphertext)
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        let plaintext = self.cipher.decrypt_with_ad(&self.hash, ciphertext, context)?;
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    fn split(&self) -> (CipherState, CipherState) {
+        let (mut temp_key1, mut temp_key2) = hkdf(&self.chaining_key, &[]);
+        let ciphers = (CipherState::from_hkdf_output(&temp_key1), CipherState::from_hkdf_output(&temp_key2));
+        ffi::memzero(&mut temp_key1);
+        ffi::memzero(&mut temp_key2);
+        ciphers
+    }
+}
+
+impl Drop for SymmetricState {
+    fn drop(&mut self) {
+        ffi::memzero(&mut self.chaining_key)
+    }
+}
+
+/// State of a Noise handshake in progress.
+///
+/// Both parties create a `HandshakeState` with the same pattern and prologue, then alternately call
+/// [`HandshakeState::write_message`] and [`HandshakeState::read_message`] until [`HandshakeState::is_finished`] returns `true`.
+/// The state is then converted into a [`TransportState`] with [`HandshakeState::into_transport`].
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::noise::{HandshakePattern, HandshakeState, Keypair};
+///
+/// let context = BTreeMap::<String, Value>::new();
+/// let server_static = Keypair::generate();
+/// let mut initiator = HandshakeState::new(
+///     HandshakePattern::NK, true, b"demo", None, Some(server_static.public_key()), context.clone(),
+/// ).unwrap();
+/// let mut responder = HandshakeState::new(
+///     HandshakePattern::NK, false, b"demo", Some(server_static), None, context.clone(),
+/// ).unwrap();
+///
+/// let message = initiator.write_message(b"hello", context.clone()).unwrap();
+/// assert_eq!(responder.read_message(&message, context.clone()).unwrap(), b"hello");
+/// let message = responder.write_message(b"", context.clone()).unwrap();
+/// initiator.read_message(&message, context.clone()).unwrap();
+///
+/// let mut initiator = initiator.into_transport(context.clone()).unwrap();
+/// let mut responder = responder.into_transport(context.clone()).unwrap();
+/// let message = initiator.write_message(b"secret", context.clone()).unwrap();
+/// assert_eq!(responder.read_message(&message, context).unwrap(), b"secret");
+/// ```
+pub struct HandshakeState {
+    pattern: HandshakePattern,
+    initiator: bool,
+    symmetric: SymmetricState,
+    s: Option<Keypair>,
+    e: Option<Keypair>,
+    rs: Option<[u8; DHLEN]>,
+    re: Option<[u8; DHLEN]>,
+    message_index: usize,
+    failed: bool,
+}
+
+impl HandshakeState {
+    /// Initializes a handshake.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The handshake pattern, which must be the same on both sides.
+    /// * `initiator` - `true` for the party sending the first message.
+    /// * `prologue` - Data both parties must agree on, mixed into the handshake hash (may be empty).
+    /// * `s` - The local static keypair, required for the responder of every pattern and for the initiator of `IK` and `XX`.
+    /// * `rs` - The remote static public key, required for the initiator of `NK` and `IK`.
+    /// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidNoiseHandshakeState`] error if a key required by the pattern is missing, or an
+    /// [`InvalidNoiseKeyLength`] error if `rs` does not have the expected length.
+    pub fn new(
+        pattern: HandshakePattern,
+        initiator: bool,
+        prologue: &[u8],
+        s: Option<Keypair>,
+        rs: Option<&[u8]>,
+        context: impl Into<Option<Context>>,
+    ) -> cdumay_core::Result<HandshakeState> {
+        let context = crate::errors::operation(context, module_path!(), "HandshakeState::new");
+        crate::init(context.clone())?;
+        let rs = match rs {
+            Some(rs) => Some(into_dh_key(rs, context.clone())?),
+            None => None,
+        };
+        let needs_static = !initiator || pattern.initiator_has_static();
+        if needs_static && s.is_none() {
+            return Err(handshake_error(
+                &format!("Pattern {} requires a local static key", pattern.name()),
+                context,
+            ));
+        }
+        if initiator && pattern.responder_static_premessage() && rs.is_none() {
+            return Err(handshake_error(
+                &format!("Pattern {} requires the remote static key", pattern.name()),
+                context,
+            ));
+        }
+
+        let mut symmetric = SymmetricState::new(&pattern.protocol_name());
+        symmetric.mix_hash(prologue);
+        if pattern.responder_static_premessage() {
+            match (initiator, &rs, &s) {
+                (true, Some(rs), _) => symmetric.mix_hash(rs),
+                (false, _, Some(s)) => symmetric.mix_hash(&s.public),
+                _ => unreachable!(),
+            }
+        }
+        Ok(HandshakeState {
+            pattern,
+            initiator,
+            symmetric,
+            s,
+            e: None,
+            rs,
+            re: None,
+            message_index: 0,
+            failed: false,
+        })
+    }
+
+    /// Sets the local ephemeral keypair instead of generating a random one.
+    ///
+    /// This is only meant for reproducing test vectors: reusing an ephemeral key breaks the security of the protocol.
+    pub fn with_ephemeral(mut self, e: Keypair) -> HandshakeState {
+        self.e = Some(e);
+        self
+    }
+
+    /// Returns the handshake pattern.
+    pub fn pattern(&self) -> HandshakePattern {
+        self.pattern
+    }
+
+    /// Returns `true` once every handshake message has been sent or received.
+    pub fn is_finished(&self) -> bool {
+        self.message_index >= self.pattern.messages().len()
+    }
+
+    /// Returns `true` if the next handshake message must be written by this party.
+    pub fn is_my_turn(&self) -> bool {
+        !self.is_finished() && self.message_index.is_multiple_of(2) == self.initiator
+    }
+
+    /// Returns the handshake hash, which uniquely identifies the session and can be used for channel binding.
+    pub fn handshake_hash(&self) -> &[u8] {
+        &self.symmetric.hash
+    }
+
+    /// Returns the remote static public key, once known.
+    pub fn remote_static(&self) -> Option<&[u8]> {
+        self.rs.as_ref().map(|rs| rs.as_slice())
+    }
+
+    fn check_usable(&self, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<()> {
+        match self.failed {
+            true => Err(handshake_error("Handshake failed, a new handshake is required", context)),
+            false => Ok(()),
+        }
+    }
+
+    fn local_static(&self, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<&Keypair> {
+        self.s.as_ref().ok_or_else(|| handshake_error("Missing local static key", context))
+    }
+
+    fn local_ephemeral(&self, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<&Keypair> {
+        self.e.as_ref().ok_or_else(|| handshake_error("Missing local ephemeral key", context))
+    }
+
+    fn remote_key(key: &Option<[u8; DHLEN]>, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<&[u8; DHLEN]> {
+        key.as_ref().ok_or_else(|| handshake_error("Missing remote key", context))
+    }
+
+    fn mix_dh(&mut self, token: Token, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<()> {
+        let mut shared = match (token, self.initiator) {
+            (Token::EE, _) => dh(
+                self.local_ephemeral(context.clone())?,
+                Self::remote_key(&self.re, context.clone())?,
+                context,
+            )?,
+            (Token::ES, true) | (Token::SE, false) => dh(
+                self.local_ephemeral(context.clone())?,
+                Self::remote_key(&self.rs, context.clone())?,
+                context,
+            )?,
+            (Token::ES, false) | (Token::SE, true) => dh(self.local_static(context.clone())?, Self::remote_key(&self.re, context.clone())?, context)?,
+            (Token::SS, _) => dh(self.local_static(context.clone())?, Self::remote_key(&self.rs, context.clone())?, context)?,
+            (Token::E, _) | (Token::S, _) => unreachable!(),
+        };
+        self.symmetric.mix_key(&shared);
+        ffi::memzero(&mut shared);
+        Ok(())
+    }
+
+    /// Returns the length of the next message written with a payload of `payload_len` bytes.
+    fn message_len(&self, payload_len: usize) -> usize {
+        let mut has_key = self.symmetric.cipher.has_key();
+        let mut len = payload_len;
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => len += DHLEN,
+                Token::S => len += DHLEN + if has_key { TAGLEN } else { 0 },
+                _ => has_key = true,
+            }
+        }
+        len + if has_key { TAGLEN } else { 0 }
+    }
+
+    /// Marks the handshake as failed if `result` is an error: the state was partly updated, so the handshake cannot go on.
+    fn fail_on_error<T>(&mut self, result: cdumay_core::Result<T>) -> cdumay_core::Result<T> {
+        self.failed |= result.is_err();
+        result
+    }
+
+    /// Writes the next handshake message carrying `payload`.
+    ///
+    /// The payload is encrypted as soon as a shared key has been established; payloads of the first `NK` or `XX` message are sent
+    /// in clear text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidNoiseHandshakeState`] error if it is not this party's turn, if the handshake is finished or failed, or if
+    /// the resulting message would exceed [`MAX_MESSAGE_LEN`]; the handshake state is then left unchanged. Any other error fails
+    /// the handshake.
+    pub fn write_message(&mut self, payload: &[u8], context: impl Into<Option<Context>>) -> cdumay_core::Result<Vec<u8>> {
+        let context = crate::errors::operation(context, module_path!(), "HandshakeState::write_message");
+        self.check_usable(context.clone())?;
+        if !self.is_my_turn() {
+            return Err(handshake_error("Not expected to write a handshake message", context));
+        }
+        if self.message_len(payload.len()) > MAX_MESSAGE_LEN {
+            return Err(handshake_error("Noise message too long", context));
+        }
+        let result = self.write_tokens(payload, context);
+        self.fail_on_error(result)
+    }
+
+    fn write_tokens(&mut self, payload: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        let mut message = Vec::new();
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let e = self.e.get_or_insert_with(Keypair::generate);
+                    let public = e.public;
+                    message.extend_from_slice(&public);
+                    self.symmetric.mix_hash(&public);
+                }
+                Token::S => {
+                    let public = self.local_static(context.clone())?.public;
+                    message.extend(self.symmetric.encrypt_and_hash(&public, context.clone())?);
+                }
+                token => self.mix_dh(*token, context.clone())?,
+            }
+        }
+        message.extend(self.symmetric.encrypt_and_hash(payload, context)?);
+        self.message_index += 1;
+        Ok(message)
+    }
+
+    /// Reads the next handshake message and returns its decrypted payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidNoiseHandshakeState`] error if a message is not expected from the remote party or the handshake failed,
+    /// or a [`FailedToOpenNoiseMessage`] error if the message is truncated or fails authentication, which fails the handshake.
+    pub fn read_message(&mut self, message: &[u8], context: impl Into<Option<Context>>) -> cdumay_core::Result<Vec<u8>> {
+        let context = crate::errors::operation(context, module_path!(), "HandshakeState::read_message");
+        self.check_usable(context.clone())?;
+        if self.is_finished() || self.is_my_turn() {
+            return Err(handshake_error("Not expected to read a handshake message", context));
+        }
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(handshake_error("Noise message too long", context));
+        }
+        let result = self.read_tokens(message, context);
+        self.fail_on_error(result)
+    }
+
+    fn read_tokens(&mut self, message: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+        let too_short = |context| -> cdumay_core::Error {
+            FailedToOpenNoiseMessage::new()
+                .with_message("Noise message too short".to_string())
+                .with_details(context)
+                .into()
+        };
+        let mut remaining = message;
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    if remaining.len() < DHLEN {
+                        return Err(too_short(context));
+                    }
+                    let (re, rest) = remaining.split_at(DHLEN);
+                    let re = into_dh_key(re, context.clone())?;
+                    self.symmetric.mix_hash(&re);
+                    self.re = Some(re);
+                    remaining = rest;
+                }
+                Token::S => {
+                    let len = match self.symmetric.cipher.has_key() {
+                        true => DHLEN + TAGLEN,
+                        false => DHLEN,
+                    };
+                    if remaining.len() < len {
+                        return Err(too_short(context));
+                    }
+                    let (rs, rest) = remaining.split_at(len);
+                    let rs = self.symmetric.decrypt_and_hash(rs, context.clone())?;
+                    self.rs = Some(into_dh_key(&rs, context.clone())?);
+                    remaining = rest;
+                }
+                token => self.mix_dh(*token, context.clone())?,
+            }
+        }
+        let payload = self.symmetric.decrypt_and_hash(remaining, context)?;
+        self.message_index += 1;
+        Ok(payload)
+    }
+
+    /// Converts a finished handshake into a [`TransportState`] holding the session keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidNoiseHandshakeState`] error if the handshake failed or is not finished yet.
+    pub fn into_transport(self, context: impl Into<Option<Context>>) -> cdumay_core::Result<TransportState> {
+        let context = crate::errors::operation(context, module_path!(), "HandshakeState::into_transport");
+        self.check_usable(context.clone())?;
+        if !self.is_finished() {
+            return Err(handshake_error("Handshake is not finished", context));
+        }
+        let (initiator_to_responder, responder_to_initiator) = self.symmetric.split();
+        let (send, receive) = match self.initiator {
+            true => (initiator_to_responder, responder_to_initiator),
+            false => (responder_to_initiator, initiator_to_responder),
+        };
+        Ok(TransportState {
+            send,
+            receive,
+            handshake_hash: self.symmetric.hash,
+            remote_static: self.rs,
+        })
+    }
+}
+
+/// Session keys obtained after a successful handshake.
+///
+/// Messages must be read in the order they were written: each direction uses an implicit counter as nonce.
+pub struct TransportState {
+    send: CipherState,
+    receive: CipherState,
+    handshake_hash: [u8; HASHLEN],
+    remote_static: Option<[u8; DHLEN]>,
+}
+
+impl TransportState {
+    /// Returns the handshake hash of the session.
+    pub fn handshake_hash(&self) -> &[u8] {
+        &self.handshake_hash
+    }
+
+    /// Returns the remote static public key, if the pattern transmitted or required one.
+    pub fn remote_static(&self) -> Option<&[u8]> {
+        self.remote_static.as_ref().map(|rs| rs.as_slice())
+    }
+
+    /// Encrypts a transport message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidNoiseHandshakeState`] error if the message would exceed [`MAX_MESSAGE_LEN`] or if the nonce space is
+    /// exhausted.
+    pub fn write_message(&mut self, payload: &[u8], context: impl Into<Option<Context>>) -> cdumay_core::Result<Vec<u8>> {
+        let context = crate::errors::operation(context, module_path!(), "TransportState::write_message");
+        if payload.len() + TAGLEN > MAX_MESSAGE_LEN {
+            return Err(handshake_error("Noise message too long", context));
+        }
+        self.send.encrypt_with_ad(&[], payload, context)
+    }
+
+    /// Decrypts a transport message.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FailedToOpenNoiseMessage`] error if the message is truncated, tampered with or received out of order.
+    pub fn read_message(&mut self, message: &[u8], context: impl Into<Option<Context>>) -> cdumay_core::Result<Vec<u8>> {
+        let context = crate::errors::operation(context, module_path!(), "TransportState::read_message");
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(handshake_error("Noise message too long", context));
+        }
+        self.receive.decrypt_with_ad(&[], message, context)
+    }
+}
@@ -0,0 +1,186 @@
+//! Nonce generation and nonce-misuse detection for secretbox.
+//!
+//! Sealing two messages with the same key and nonce breaks both confidentiality and authenticity. Random 24-byte nonces, as used by
+//! [`crate::secretbox::crypt`], make collisions negligible; this module helps when nonces are managed explicitly:
+//!
+//! * [`NonceSequence`] produces monotonic nonces made of a random prefix and a counter incremented with `sodium_increment`.
+//! * [`NonceReuseDetector`] remembers a bounded number of `(key, nonce)` pairs and reports a [`NonceReuse`] error when one repeats.
+//!   In debug builds, [`crate::secretbox`] consults a process-wide detector before sealing.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde_value::Value;
+//! use cdumay_sodium::nonce::{NonceReuseDetector, NonceSequence};
+//!
+//! let context = BTreeMap::<String, Value>::new();
+//! let key = [7u8; 32];
+//! let mut sequence = NonceSequence::new();
+//! let mut detector = NonceReuseDetector::new(1024);
+//!
+//! let first = sequence.next(context.clone()).unwrap();
+//! let second = sequence.next(context.clone()).unwrap();
+//! assert_ne!(first, second);
+//! assert!(detector.observe(&key, &first, context.clone()).is_ok());
+//! assert!(detector.observe(&key, &first, context).is_err());
+//! ```
+
+use crate::fingerprint::Fingerprint;
+use crate::{Context, NonceReuse, NonceSequenceExhausted, ffi};
+use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use cdumay_base64::base64::Engine;
+use std::collections::{BTreeSet, VecDeque};
+
+/// Length of a secretbox nonce.
+pub const NONCEBYTES: usize = ffi::SECRETBOX_NONCEBYTES;
+
+/// Length of the random prefix of a [`NonceSequence`] nonce.
+pub const PREFIXBYTES: usize = 16;
+
+/// Length of the counter of a [`NonceSequence`] nonce.
+pub const COUNTERBYTES: usize = NONCEBYTES - PREFIXBYTES;
+
+/// Length of the fingerprints kept by a [`NonceReuseDetector`].
+const FINGERPRINTBYTES: usize = 16;
+
+/// Monotonic nonces: a random prefix drawn once, followed by a little-endian counter.
+///
+/// Distinct sequences have distinct prefixes with overwhelming probability, so several senders can share a key as long as each
+/// owns its sequence. A sequence yields 2<sup>64</sup> nonces, then fails with [`NonceSequenceExhausted`].
+pub struct NonceSequence {
+    nonce: [u8; NONCEBYTES],
+    exhausted: bool,
+}
+
+impl NonceSequence {
+    /// Creates a sequence with a random prefix and a counter starting at zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if libsodium cannot be initialized (see [`crate::init`]).
+    pub fn new() -> NonceSequence {
+        crate::init_or_panic();
+        let mut nonce = [0u8; NONCEBYTES];
+        ffi::random_bytes(&mut nonce[..PREFIXBYTES]);
+        NonceSequence { nonce, exhausted: false }
+    }
+
+    /// Creates a sequence from a known prefix, for example one agreed with a peer.
+    pub fn with_prefix(prefix: [u8; PREFIXBYTES]) -> NonceSequence {
+        let mut nonce = [0u8; NONCEBYTES];
+        nonce[..PREFIXBYTES].copy_from_slice(&prefix);
+        NonceSequence { nonce, exhausted: false }
+    }
+
+    /// Returns the random prefix of the sequence.
+    pub fn prefix(&self) -> &[u8] {
+        &self.nonce[..PREFIXBYTES]
+    }
+
+    /// Returns the next nonce and increments the counter.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`NonceSequenceExhausted`] error once the counter has wrapped around.
+    pub fn next(&mut self, context: impl Into<Option<Context>>) -> cdumay_core::Result<[u8; NONCEBYTES]> {
+        let context = crate::errors::operation(context, module_path!(), "NonceSequence::next");
+        if self.exhausted {
+            return Err(NonceSequenceExhausted::new()
+                .with_message("Nonce counter exhausted, start a new sequence".to_string())
+                .with_details(context)
+                .into());
+        }
+        let nonce = self.nonce;
+        let counter = &mut self.nonce[PREFIXBYTES..];
+        ffi::increment(counter);
+        self.exhausted = ffi::is_zero(counter);
+        Ok(nonce)
+    }
+
+    /// Returns the next nonce, base64-encoded.
+    ///
+    /// # Errors
+    ///
+    /// See [`NonceSequence::next`].
+    pub fn next_b64(&mut self, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+        let context = crate::errors::operation(context, module_path!(), "NonceSequence::next_b64");
+        Ok(BASE64_STANDARD.encode(self.next(context)?))
+    }
+}
+
+impl Default for NonceSequence {
+    fn default() -> Self {
+        NonceSequence::new()
+    }
+}
+
+impl std::fmt::Debug for NonceSequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NonceSequence")
+            .field("prefix", &BASE64_STANDARD.encode(self.prefix()))
+            .field("exhausted", &self.exhausted)
+            .finish()
+    }
+}
+
+/// Bounded in-memory record of the `(key, nonce)` pairs used to seal, reporting any pair seen twice.
+///
+/// Only a BLAKE2b fingerprint of each pair is kept, never the key. Once `capacity` pairs are recorded the oldest is
+/// forgotten, so reuse is only detected within that window.
+#[derive(Debug)]
+pub struct NonceReuseDetector {
+    capacity: usize,
+    seen: BTreeSet<[u8; FINGERPRINTBYTES]>,
+    order: VecDeque<[u8; FINGERPRINTBYTES]>,
+}
+
+impl NonceReuseDetector {
+    /// Creates a detector remembering up to `capacity` pairs.
+    pub const fn new(capacity: usize) -> NonceReuseDetector {
+        NonceReuseDetector { capacity, seen: BTreeSet::new(), order: VecDeque::new() }
+    }
+
+    /// Returns the number of pairs currently remembered.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Returns `true` if no pair is remembered.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Records that `nonce` is about to be used with `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`NonceReuse`] error, without recording anything, if the pair was already recorded.
+    pub fn observe(&mut self, key: &[u8], nonce: &[u8], context: impl Into<Option<Context>>) -> cdumay_core::Result<()> {
+        let context = crate::errors::operation(context, module_path!(), "NonceReuseDetector::observe");
+        let mut input = crate::Zeroizing(Vec::with_capacity(8 + key.len() + nonce.len()));
+        input.0.extend_from_slice(&(key.len() as u64).to_le_bytes());
+        input.0.extend_from_slice(key);
+        input.0.extend_from_slice(nonce);
+        crate::init(context.clone())?;
+        let mut fingerprint = [0u8; FINGERPRINTBYTES];
+        ffi::generichash(&mut fingerprint, &input, None);
+        if self.seen.contains(&fingerprint) {
+            return Err(NonceReuse::new()
+                .with_message("Nonce already used with this key".to_string())
+                .with_details(crate::errors::with_key_fingerprint(context, Fingerprint::secretbox_key(key)))
+                .into());
+        }
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        if self.order.len() == self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.seen.remove(&oldest);
+        }
+        self.seen.insert(fingerprint);
+        self.order.push_back(fingerprint);
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+//! Selective encryption of `serde_value::Value` documents: a policy lists [JSON pointers](https://www.rfc-editor.org/rfc/rfc6901)
+//! to protect (for example `/user/ssn`), and only the matching nodes are encrypted, the rest of the document stays readable.
+//!
+//! A `*` token matches every element of an array (or every value of a map), so `/payment/*/card` protects the card of every
+//! payment. Paths which do not exist in a document are ignored. Paths must not overlap: a path that may match a node inside (or
+//! equal to) a node matched by another path is rejected, as that node would be encrypted twice.
+//!
+//! Each matching node is replaced by the string form of an [`Encrypted<Value>`](crate::encrypted::Encrypted) sealed with secretbox
+//! (`sb:json:...`): the node is serialized as JSON first, so numbers, booleans and sub-documents are restored on decryption. Integer
+//! widths are not preserved (a `U8` is restored as a `U64`).
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde_value::Value;
+//! use cdumay_sodium::paths::{decrypt_paths, encrypt_paths};
+//!
+//! let key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+//! let context = BTreeMap::<String, Value>::new();
+//! let mut user = BTreeMap::new();
+//! user.insert(Value::String("name".to_string()), Value::String("alice".to_string()));
+//! user.insert(Value::String("ssn".to_string()), Value::String("078-05-1120".to_string()));
+//! let document = Value::Map(user);
+//!
+//! let encrypted = encrypt_paths(document.clone(), &["/ssn"], key_b64, context.clone()).unwrap();
+//! assert_ne!(encrypted, document);
+//! assert_eq!(decrypt_paths(encrypted, &["/ssn"], key_b64, context).unwrap(), document);
+//! ```
+
+use crate::{Context, InvalidEnvelope, InvalidJsonPointer, decode_base64, secretbox};
+use crate::encrypted::{Encrypted, Format};
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+/// Token matching every element of an array or every value of a map.
+pub const WILDCARD: &str = "*";
+
+/// Parses a JSON pointer into unescaped reference tokens.
+fn parse_pointer(pointer: &str, context: BTreeMap<String, Value>) -> cdumay_core::Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    match pointer.strip_prefix('/') {
+        Some(rest) => Ok(rest.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect()),
+        None => {
+            let mut context = context;
+            context.insert("path".to_string(), Value::String(pointer.to_string()));
+            Err(InvalidJsonPointer::new()
+                .with_message("JSON pointer must be empty or start with '/'".to_string())
+                .with_details(context)
+                .into())
+        }
+    }
+}
+
+/// Returns whether a node matched by one of the token lists can be, or be inside, a node matched by the other.
+fn overlap(tokens: &[String], other: &[String]) -> bool {
+    tokens.iter().zip(other).all(|(token, other)| token == other || token == WILDCARD || other == WILDCARD)
+}
+
+fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Calls `apply` on every node matching `tokens`, with the concrete pointer of the node.
+fn walk<F>(value: &mut Value, tokens: &[String], path: &mut String, apply: &mut F) -> cdumay_core::Result<()>
+where
+    F: FnMut(&mut Value, &str) -> cdumay_core::Result<()>,
+{
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return apply(value, path),
+    };
+    let len = path.len();
+    match value {
+        Value::Option(Some(inner)) | Value::Newtype(inner) => return walk(inner, tokens, path, apply),
+        Value::Seq(items) if token == WILDCARD => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push_str(&format!("/{}", index));
+                walk(item, rest, path, apply)?;
+                path.truncate(len);
+            }
+        }
+        Value::Seq(items) => {
+            if let Some(item) = token.parse::<usize>().ok().and_then(|index| items.get_mut(index)) {
+                path.push_str(&format!("/{}", token));
+                walk(item, rest, path, apply)?;
+                path.truncate(len);
+            }
+        }
+        Value::Map(entries) if token == WILDCARD => {
+            for (key, item) in entries.iter_mut() {
+                let key = match key {
+                    Value::String(key) => escape_token(key),
+                    key => format!("{:?}", key),
+                };
+                path.push_str(&format!("/{}", key));
+                walk(item, rest, path, apply)?;
+                path.truncate(len);
+            }
+        }
+        Value::Map(entries) => {
+            if let Some(item) = entries.get_mut(&Value::String(token.clone())) {
+                path.push_str(&format!("/{}", escape_token(token)));
+                walk(item, rest, path, apply)?;
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Applies `apply` to every node matched by one of the `paths`, recording the failing path in the error context.
+fn transform<I, S, F>(mut value: Value, paths: I, context: BTreeMap<String, Value>, mut apply: F) -> cdumay_core::Result<Value>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+    F: FnMut(&mut Value, BTreeMap<String, Value>) -> cdumay_core::Result<()>,
+{
+    let mut pointers: Vec<(String, Vec<String>)> = Vec::new();
+    for pointer in paths {
+        let tokens = parse_pointer(pointer.as_ref(), context.clone())?;
+        if let Some((other, _)) = pointers.iter().find(|(_, other)| overlap(&tokens, other)) {
+            let mut context = context.clone();
+            context.insert("path".to_string(), Value::String(pointer.as_ref().to_string()));
+            context.insert("overlapping_path".to_string(), Value::String(other.clone()));
+            return Err(InvalidJsonPointer::new()
+                .with_message("JSON pointers must not overlap".to_string())
+                .with_details(context)
+                .into());
+        }
+        pointers.push((pointer.as_ref().to_string(), tokens));
+    }
+    for (_, tokens) in pointers {
+        walk(&mut value, &tokens, &mut String::new(), &mut |node, path| {
+            let mut context = context.clone();
+            context.insert("path".to_string(), Value::String(path.to_string()));
+            apply(node, context)
+        })?;
+    }
+    Ok(value)
+}
+
+/// Encrypts every node of `value` matched by one of the JSON pointers in `paths`.
+///
+/// # Arguments
+///
+/// * `value` - The document to protect.
+/// * `paths` - JSON pointers of the nodes to encrypt; `*` matches every array element or map value.
+/// * `sb_key_b64` - The base64-encoded secret key for SecretBox.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok(Value)` with matching nodes replaced by encrypted strings, or an error of type [`cdumay_core::Error`].
+///
+/// # Errors
+///
+/// Returns an [`InvalidJsonPointer`] error if a path is not a valid JSON pointer or overlaps another path, or an error if the key is
+/// invalid. Errors raised while encrypting a node carry its concrete pointer under the `path` key of their details.
+pub fn encrypt_paths<I, S>(value: Value, paths: I, sb_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Value>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let context = crate::errors::operation(context, module_path!(), "encrypt_paths");
+    crate::init(context.clone())?;
+    let key = secretbox::into_secretbox_key(decode_base64(sb_key_b64, "key", context.clone())?, context.clone())?;
+    transform(value, paths, context, |node, context| {
+        let encrypted = Encrypted::seal_secretbox(&*node, Format::Json, &key, context)?;
+        *node = Value::String(encrypted.to_string());
+        Ok(())
+    })
+}
+
+/// Decrypts every node of `value` matched by one of the JSON pointers in `paths`, reversing [`encrypt_paths`].
+///
+/// # Errors
+///
+/// Returns an [`InvalidJsonPointer`] error if a path is not a valid JSON pointer or overlaps another path, or an error if the key is
+/// invalid. Returns an error if a matching node is not an
+/// encrypted string, fails authentication or cannot be deserialized; such errors carry the concrete pointer of the node under the
+/// `path` key of their details.
+pub fn decrypt_paths<I, S>(value: Value, paths: I, sb_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Value>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let context = crate::errors::operation(context, module_path!(), "decrypt_paths");
+    crate::init(context.clone())?;
+    let key = secretbox::into_secretbox_key(decode_base64(sb_key_b64, "key", context.clone())?, context.clone())?;
+    transform(value, paths, context, |node, context| {
+        let encrypted: Encrypted<Value> = match node {
+            Value::String(encrypted) => Encrypted::parse(encrypted, context.clone())?,
+            _ => {
+                return Err(InvalidEnvelope::new()
+                    .with_message("Expected an encrypted string".to_string())
+                    .with_details(context)
+                    .into());
+            }
+        };
+        *node = encrypted.open_secretbox(&key, context)?;
+        Ok(())
+    })
+}
@@ -5,12 +5,18 @@
 //! A message is encrypted using an ephemeral key pair, with the secret key being erased right after the encryption process.
 //!
 //! Without knowing the secret key used for a given message, the sender cannot decrypt the message later. Furthermore, without additional data, a message cannot be correlated with the identity of its sender.
-use crate::{FailedToOpenSealedBox, vec_to_string};
+use crate::errors::{authentication_failed, ciphertext_too_short, encryption_failed, with_key_fingerprint};
+use crate::fingerprint::Fingerprint;
+use crate::lengths::SEALEDBOX_ALGORITHM;
+use crate::secretbox::{self, Algorithm};
+use crate::{Context, InvalidEnvelope, InvalidRecipientCount, Zeroizing, decode_base64, ffi, vec_to_string};
 use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use cdumay_base64::base64::Engine;
-use cdumay_core::ErrorConverter;
 use std::collections::BTreeMap;
 
+const MULTI_ENVELOPE_VERSION: u8 = 1;
+const MULTI_SLOT_BYTES: usize = ffi::SECRETBOX_KEYBYTES + ffi::BOX_SEALBYTES;
+
 /// Decrypts data encrypted with a sealed box using libsodium.
 ///
 /// This function attempts to decrypt the given base64-encoded data using the provided
@@ -24,7 +30,7 @@ use std::collections::BTreeMap;
 /// * `data` - The base64-encoded sealed box ciphertext to decrypt.
 /// * `private_key_b64` - The base64-encoded private key.
 /// * `public_key_b64` - The base64-encoded public key.
-/// * `context` - A `BTreeMap` containing additional context information for error reporting.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
 ///
 /// # Returns
 ///
@@ -34,15 +40,15 @@ use std::collections::BTreeMap;
 /// # Errors
 ///
 /// Returns an error if:
+/// - libsodium cannot be initialized ([`crate::SodiumInitFailed`]).
 /// - The input data or keys cannot be base64-decoded.
 /// - The sealed box cannot be opened (decryption fails).
 /// - The decrypted data is not valid UTF-8.
 ///
 /// # Safety
 ///
-/// This function calls `sodium::sodium_init()` and uses unsafe code to interact with
-/// the libsodium C API. The caller must ensure that the provided keys and data are valid
-/// and that libsodium is properly initialized.
+/// This function initializes libsodium once through [`crate::init`]. Key and ciphertext
+/// lengths are checked before any buffer is handed to the libsodium C API.
 ///
 /// # Example
 ///
@@ -62,40 +68,18 @@ pub fn decrypt(
     data: &str,
     private_key_b64: &str,
     public_key_b64: &str,
-    context: BTreeMap<String, serde_value::Value>,
+    context: impl Into<Option<Context>>,
 ) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "decrypt");
+    crate::init(context.clone())?;
     if data.is_empty() {
         return Ok(String::new());
     }
-    let data_decoded = cdumay_base64::convert_decode_result!(BASE64_STANDARD.decode(data), context.clone())?;
-    if data_decoded.len() < sodium::crypto_box_SEALBYTES as usize {
-        return Err(FailedToOpenSealedBox::new()
-            .with_message("Ciphertext too short for sealed box".to_string())
-            .with_details(context)
-            .into());
-    }
-    let priv_key_decoded = cdumay_base64::convert_decode_result!(BASE64_STANDARD.decode(private_key_b64), context.clone())?;
-    let pub_key_decoded = cdumay_base64::convert_decode_result!(BASE64_STANDARD.decode(public_key_b64), context.clone())?;
-
-    let seal_bytes = sodium::crypto_box_SEALBYTES as usize;
-    unsafe {
-        sodium::sodium_init();
-        let mut decrypted = vec![0u8; data_decoded.len() - seal_bytes];
-        let ret = sodium::crypto_box_seal_open(
-            decrypted.as_mut_ptr(),
-            data_decoded.as_ptr(),
-            data_decoded.len() as u64,
-            pub_key_decoded.as_ptr(),
-            priv_key_decoded.as_ptr(),
-        );
-        match ret != 0 {
-            true => Err(FailedToOpenSealedBox::new()
-                .with_message("Decryption failed".to_string())
-                .with_details(context)
-                .into()),
-            false => vec_to_string(decrypted, context),
-        }
-    }
+    let data_decoded = decode_base64(data, "data", context.clone())?;
+    let priv_key_decoded = Zeroizing(decode_base64(private_key_b64, "private_key", context.clone())?);
+    let pub_key_decoded = decode_base64(public_key_b64, "public_key", context.clone())?;
+    let decrypted = open_bytes(&data_decoded, &priv_key_decoded, &pub_key_decoded, context.clone())?;
+    vec_to_string(decrypted, context)
 }
 
 /// Encrypts data using a sealed box with libsodium.
@@ -109,7 +93,7 @@ pub fn decrypt(
 ///
 /// * `data` - The plaintext data to encrypt as a UTF-8 string.
 /// * `public_key_b64` - The base64-encoded public key of the recipient to use for encryption.
-/// * `context` - A `BTreeMap` containing additional context information for error reporting.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
 ///
 /// # Returns
 ///
@@ -119,14 +103,14 @@ pub fn decrypt(
 /// # Errors
 ///
 /// Returns an error if:
+/// - libsodium cannot be initialized ([`crate::SodiumInitFailed`]).
 /// - The provided public key cannot be base64-decoded.
 /// - The encryption operation fails.
 ///
 /// # Safety
 ///
-/// This function calls `sodium::sodium_init()` and uses unsafe code to interact with
-/// the libsodium C API. The caller must ensure that the provided key is valid and that
-/// libsodium is properly initialized.
+/// This function initializes libsodium once through [`crate::init`]. The public key length
+/// is checked before it is handed to the libsodium C API.
 ///
 /// # Example
 ///
@@ -141,26 +125,217 @@ pub fn decrypt(
 /// let ciphertext = crypt(data, public_key, context).unwrap();
 /// println!("Encrypted (base64): {}", ciphertext);
 /// ```
+pub fn crypt(data: &str, public_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "crypt");
+    crate::init(context.clone())?;
+    let pub_key_decoded = decode_base64(public_key_b64, "public_key", context.clone())?;
+    Ok(BASE64_STANDARD.encode(seal_bytes(data.as_bytes(), &pub_key_decoded, context)?))
+}
 
-pub fn crypt(data: &str, public_key_b64: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<String> {
-    let pub_key_decoded = cdumay_base64::convert_decode_result!(BASE64_STANDARD.decode(public_key_b64), context.clone())?;
-    if pub_key_decoded.len() != sodium::crypto_box_PUBLICKEYBYTES as usize {
-        return Err(FailedToOpenSealedBox::new()
-            .with_message("Invalid public key length for sealed box".to_string())
+/// Encrypts data once for several recipients and returns a single base64-encoded envelope.
+///
+/// The payload is encrypted with secretbox under a random data key, and that data key is sealed to each recipient public key
+/// (`crypto_box_seal`), so the payload is stored only once whatever the number of recipients. Key slots are shuffled and sealed
+/// boxes do not carry the recipient identity, so the envelope does not reveal which slot belongs to whom.
+///
+/// Envelope layout (before base64 encoding):
+///
+/// | Field | Size |
+/// |-------|------|
+/// | version (`1`) | 1 byte |
+/// | slot count (big endian) | 2 bytes |
+/// | sealed data keys | `count * (crypto_secretbox_KEYBYTES + crypto_box_SEALBYTES)` bytes |
+/// | secretbox nonce | `crypto_secretbox_NONCEBYTES` bytes |
+/// | secretbox ciphertext | remaining bytes |
+///
+/// # Arguments
+///
+/// * `data` - The plaintext data to encrypt as a UTF-8 string.
+/// * `public_keys_b64` - The base64-encoded public keys of the recipients.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok(String)` containing the base64-encoded envelope if successful,
+/// or an error of type [`cdumay_core::Error`] if any step fails.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - No recipient is given, or more than `u16::MAX` ([`InvalidRecipientCount`]).
+/// - A public key cannot be base64-decoded or has an invalid length.
+/// - The encryption operation fails.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::sealedbox::{crypt_multi, decrypt_multi};
+///
+/// let public_key = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+/// let private_key = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+/// let other_public_key = "ethfgJSvqhrV4yUnLFBv9lkF4C2IZFClcq4jdZ6URFg=";
+/// let context = BTreeMap::<String, Value>::new();
+/// let envelope = crypt_multi("secret message", &[other_public_key, public_key], context.clone()).unwrap();
+/// let plaintext = decrypt_multi(&envelope, private_key, public_key, context).unwrap();
+/// assert_eq!(plaintext, "secret message");
+/// ```
+pub fn crypt_multi(data: &str, public_keys_b64: &[&str], context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "crypt_multi");
+    crate::init(context.clone())?;
+    if public_keys_b64.is_empty() || public_keys_b64.len() > u16::MAX as usize {
+        return Err(InvalidRecipientCount::new()
+            .with_message(format!("Invalid recipient count for sealed envelope: 1 to {} required", u16::MAX))
             .with_details(context)
             .into());
     }
+    let data_key = secretbox::Key::generate();
+    let mut slots = Vec::with_capacity(public_keys_b64.len());
+    for public_key_b64 in public_keys_b64 {
+        let pub_key_decoded = decode_base64(public_key_b64, "public_key", context.clone())?;
+        slots.push(seal_bytes(data_key.as_ref(), &pub_key_decoded, context.clone())?);
+    }
+    shuffle(&mut slots);
 
-    unsafe {
-        sodium::sodium_init();
-        let mut ciphertext = vec![0u8; data.as_bytes().len() + sodium::crypto_box_SEALBYTES as usize];
-        let ret = sodium::crypto_box_seal(ciphertext.as_mut_ptr(), data.as_ptr(), data.len() as u64, pub_key_decoded.as_ptr());
-        match ret != 0 {
-            true => Err(FailedToOpenSealedBox::new()
-                .with_message("Encryption failed".to_string())
-                .with_details(context)
-                .into()),
-            false => Ok(BASE64_STANDARD.encode(ciphertext)),
+    let nonce = secretbox::gen_nonce();
+    let ciphertext = secretbox::seal_with(Algorithm::XSalsa20Poly1305, data.as_bytes(), &nonce, data_key.as_ref());
+    let mut envelope = Vec::with_capacity(3 + slots.len() * MULTI_SLOT_BYTES + ffi::SECRETBOX_NONCEBYTES + ciphertext.len());
+    envelope.push(MULTI_ENVELOPE_VERSION);
+    envelope.extend_from_slice(&(slots.len() as u16).to_be_bytes());
+    slots.iter().for_each(|slot| envelope.extend_from_slice(slot));
+    envelope.extend_from_slice(nonce.as_ref());
+    envelope.extend_from_slice(&ciphertext);
+    Ok(BASE64_STANDARD.encode(envelope))
+}
+
+/// Decrypts an envelope produced by [`crypt_multi`] with one recipient keypair.
+///
+/// Every key slot is tried with the keypair, even after a match, so that the time taken does not reveal the slot position.
+///
+/// # Arguments
+///
+/// * `envelope_b64` - The base64-encoded envelope to decrypt.
+/// * `private_key_b64` - The base64-encoded private key of one recipient.
+/// * `public_key_b64` - The base64-encoded public key of the same recipient.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok(String)` containing the decrypted plaintext if successful, or an error
+/// of type [`cdumay_core::Error`] if any step fails.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The envelope or keys cannot be base64-decoded, or the keys have an invalid length.
+/// - The envelope is malformed or has an unsupported version ([`InvalidEnvelope`]).
+/// - No key slot can be opened with the keypair, or the payload fails authentication.
+/// - The decrypted data is not valid UTF-8.
+pub fn decrypt_multi(
+    envelope_b64: &str,
+    private_key_b64: &str,
+    public_key_b64: &str,
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "decrypt_multi");
+    crate::init(context.clone())?;
+    if envelope_b64.is_empty() {
+        return Ok(String::new());
+    }
+    let envelope = decode_base64(envelope_b64, "envelope", context.clone())?;
+    let priv_key_decoded = Zeroizing(decode_base64(private_key_b64, "private_key", context.clone())?);
+    let pub_key_decoded = decode_base64(public_key_b64, "public_key", context.clone())?;
+    ffi::box_secret_key(&priv_key_decoded, context.clone())?;
+    ffi::box_public_key(&pub_key_decoded, context.clone())?;
+
+    if envelope.len() < 3 {
+        return Err(ciphertext_too_short(SEALEDBOX_ALGORITHM, "envelope", 3, envelope.len(), context));
+    }
+    if envelope[0] != MULTI_ENVELOPE_VERSION {
+        return Err(InvalidEnvelope::new()
+            .with_message("Unsupported envelope version".to_string())
+            .with_details(context)
+            .into());
+    }
+    let count = u16::from_be_bytes([envelope[1], envelope[2]]) as usize;
+    let payload_offset = 3 + count * MULTI_SLOT_BYTES;
+    let minimum = payload_offset + ffi::SECRETBOX_NONCEBYTES + ffi::SECRETBOX_MACBYTES;
+    if envelope.len() < minimum {
+        return Err(ciphertext_too_short(SEALEDBOX_ALGORITHM, "envelope", minimum, envelope.len(), context));
+    }
+
+    let mut data_key: Option<secretbox::Key> = None;
+    for slot in envelope[3..payload_offset].chunks(MULTI_SLOT_BYTES) {
+        if let Ok(key) = open_bytes(slot, &priv_key_decoded, &pub_key_decoded, context.clone()) {
+            // The keys of the slots opened after the first match are wiped with `key`.
+            let mut key = Zeroizing(key);
+            if data_key.is_none() {
+                data_key = secretbox::into_secretbox_key(std::mem::take(&mut key.0), context.clone()).ok();
+            }
         }
     }
+    let data_key = data_key.ok_or_else(|| {
+        authentication_failed(SEALEDBOX_ALGORITHM, "key_slots", with_key_fingerprint(context.clone(), Fingerprint::public_key(&pub_key_decoded)))
+    })?;
+    let (nonce, ciphertext) = envelope[payload_offset..].split_at(ffi::SECRETBOX_NONCEBYTES);
+    let nonce: secretbox::Nonce = nonce.try_into().unwrap_or_else(|_| unreachable!());
+    match secretbox::open_with(Algorithm::XSalsa20Poly1305, ciphertext, &nonce, data_key.as_ref()) {
+        Some(decrypted) => vec_to_string(decrypted, context),
+        None => Err(authentication_failed(Algorithm::XSalsa20Poly1305.id(), "data", context)),
+    }
+}
+
+/// Returns the fingerprint of a public key, to name a recipient in logs (see [`crate::fingerprint`]).
+///
+/// # Errors
+///
+/// Returns an error if the key cannot be base64-decoded or has an invalid length.
+///
+/// # Example
+///
+/// ```
+/// use cdumay_sodium::sealedbox;
+///
+/// let fingerprint = sealedbox::fingerprint("odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=", None).unwrap();
+/// println!("Sealing for recipient {}", fingerprint.to_hex_groups());
+/// ```
+pub fn fingerprint(public_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Fingerprint> {
+    let context = crate::errors::operation(context, module_path!(), "fingerprint");
+    let public_key = decode_base64(public_key_b64, "public_key", context.clone())?;
+    crate::init(context.clone())?;
+    Ok(Fingerprint::public_key(ffi::box_public_key(&public_key, context)?))
+}
+
+/// Shuffles key slots (Fisher-Yates) using libsodium's uniform random generator.
+fn shuffle(slots: &mut [Vec<u8>]) {
+    for i in (1..slots.len()).rev() {
+        let j = ffi::random_uniform((i + 1) as u32) as usize;
+        slots.swap(i, j);
+    }
+}
+
+/// Seals raw bytes for the recipient owning `public_key` (`crypto_box_seal`).
+///
+/// The public key length is checked before calling the C API.
+pub(crate) fn seal_bytes(data: &[u8], public_key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+    let public_key = ffi::box_public_key(public_key, context.clone())?;
+
+    crate::init(context.clone())?;
+    ffi::box_seal(data, public_key).ok_or_else(|| encryption_failed(SEALEDBOX_ALGORITHM, context))
+}
+
+/// Opens raw sealed bytes with the recipient keypair (`crypto_box_seal_open`).
+///
+/// Key lengths and the minimal ciphertext length are checked before calling the C API.
+pub(crate) fn open_bytes(data: &[u8], private_key: &[u8], public_key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+    if data.len() < ffi::BOX_SEALBYTES {
+        return Err(ciphertext_too_short(SEALEDBOX_ALGORITHM, "data", ffi::BOX_SEALBYTES, data.len(), context));
+    }
+    let private_key = ffi::box_secret_key(private_key, context.clone())?;
+    let public_key = ffi::box_public_key(public_key, context.clone())?;
+
+    crate::init(context.clone())?;
+    ffi::box_seal_open(data, public_key, private_key)
+        .ok_or_else(|| authentication_failed(SEALEDBOX_ALGORITHM, "data", with_key_fingerprint(context, Fingerprint::public_key(public_key))))
 }
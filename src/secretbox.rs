@@ -15,76 +15,135 @@
 //! data — everything is encrypted and authenticated together.
 //!
 //! This module provides basic secretbox manipulations.
+//!
+//! [`crypt_with_algorithm`] and [`decrypt_with_algorithm`] select the construction: XSalsa20-Poly1305 (`crypto_secretbox`, the
+//! default) or XChaCha20-Poly1305 (`crypto_secretbox_xchacha20poly1305`). Both use 32-byte keys and 24-byte nonces.
+//!
+//! [`crypt_deterministic`] is an opt-in SIV-style mode where identical plaintexts (under the same key and associated data) produce
+//! identical ciphertexts, for joins and unique constraints. It leaks equality of plaintexts, so it must only be used for the columns
+//! which need it; [`crypt`] stays the default for general data.
 
-use crate::{FailedToOpenSecretBox, InvalidBoxKeyLength, InvalidBoxNonceLength, vec_to_string};
+use crate::errors::{Detail, authentication_failed, ciphertext_too_short, enrich, with_key_fingerprint};
+use crate::fingerprint::Fingerprint;
+use crate::{AuthenticationFailed, Context, Zeroizing, decode_base64, ffi, vec_to_string};
 use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use cdumay_base64::base64::Engine;
-use cdumay_core::ErrorConverter;
-use sodiumoxide::crypto::secretbox;
 use std::collections::BTreeMap;
 
-/// Converts a `Vec<u8>` into a `secretbox::Key` if it has the correct length.
+pub use crate::backend::Algorithm;
+
+/// Number of `(key, nonce)` pairs remembered by the process-wide nonce-reuse detector consulted in debug builds.
+pub const DEBUG_NONCE_HISTORY: usize = 4096;
+
+#[cfg(debug_assertions)]
+static NONCE_REUSE_DETECTOR: std::sync::Mutex<crate::nonce::NonceReuseDetector> =
+    std::sync::Mutex::new(crate::nonce::NonceReuseDetector::new(DEBUG_NONCE_HISTORY));
+
+/// Context of the subkeys derived from the secretbox key by the deterministic mode.
+const DETERMINISTIC_KDF_CONTEXT: &[u8; ffi::KDF_CONTEXTBYTES] = b"sbdeterm";
+/// Subkey id of the deterministic mode encryption key.
+const DETERMINISTIC_ENCRYPTION_SUBKEY: u64 = 1;
+/// Subkey id of the deterministic mode nonce derivation key.
+const DETERMINISTIC_NONCE_SUBKEY: u64 = 2;
+
+/// A secretbox key, wiped from memory when dropped.
+pub(crate) struct Key([u8; ffi::SECRETBOX_KEYBYTES]);
+
+impl Key {
+    /// Generates a random key.
+    pub(crate) fn generate() -> Key {
+        let mut key = [0u8; ffi::SECRETBOX_KEYBYTES];
+        ffi::random_bytes(&mut key);
+        Key(key)
+    }
+
+    /// Returns the fingerprint of the key.
+    pub(crate) fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::secretbox_key(&self.0)
+    }
+
+    /// Derives another key bound to `input`, with BLAKE2b keyed by this key.
+    pub(crate) fn derive(&self, input: &[u8]) -> Key {
+        let mut key = [0u8; ffi::SECRETBOX_KEYBYTES];
+        ffi::generichash(&mut key, input, Some(&self.0));
+        Key(key)
+    }
+
+    /// Returns the key as an array, as expected by [`ffi`].
+    pub(crate) fn as_array(&self) -> &[u8; ffi::SECRETBOX_KEYBYTES] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Key {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        ffi::memzero(&mut self.0)
+    }
+}
+
+/// A secretbox nonce.
+pub(crate) type Nonce = [u8; ffi::SECRETBOX_NONCEBYTES];
+
+/// Returns a random nonce.
+pub(crate) fn gen_nonce() -> Nonce {
+    let mut nonce = [0u8; ffi::SECRETBOX_NONCEBYTES];
+    ffi::random_bytes(&mut nonce);
+    nonce
+}
+
+/// Converts a `Vec<u8>` into a secretbox [`Key`] if it has the correct length.
 ///
-/// This function takes ownership of a `Vec<u8>` and attempts to convert it into a
-/// `Box<[u8; secretbox::KEYBYTES]>`. If the input vector does not have the correct
-/// length (`secretbox::KEYBYTES`), an error is returned with a detailed context.
+/// This function takes ownership of a `Vec<u8>` and copies it into a [`Key`], wiping the
+/// vector afterwards. If the input vector does not have the correct length
+/// (`crypto_secretbox_KEYBYTES`), an error is returned with a detailed context.
 ///
 /// # Arguments
 ///
-/// * `v` - A vector of bytes expected to be exactly `secretbox::KEYBYTES` in length.
+/// * `v` - A vector of bytes expected to be exactly `crypto_secretbox_KEYBYTES` in length.
 /// * `context` - A map containing additional contextual information, useful for debugging
 ///   or logging purposes if the conversion fails.
 ///
 /// # Returns
 ///
-/// Returns `Ok(secretbox::Key)` if the conversion is successful. Otherwise, returns
+/// Returns `Ok(Key)` if the conversion is successful. Otherwise, returns
 /// an error of type `cdumay_core::Error` (usually wrapping `InvalidBoxKeyLength`).
 ///
 /// # Errors
 ///
-/// This function will return an error if `v.len() != secretbox::KEYBYTES`.
-fn into_secretbox_key(v: Vec<u8>, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<secretbox::Key> {
-    if v.len() != secretbox::KEYBYTES {
-        return Err(InvalidBoxKeyLength::new()
-            .with_message(format!("Invalid box_key length required: {}", secretbox::KEYBYTES))
-            .with_details(context)
-            .into());
-    }
-    let boxed_slice = v.into_boxed_slice();
-    let boxed_array: Box<[u8; secretbox::KEYBYTES]> = boxed_slice.try_into().unwrap_or_else(|_| unreachable!());
-    Ok(secretbox::Key(*boxed_array))
+/// This function will return an error if `v.len() != crypto_secretbox_KEYBYTES`.
+pub(crate) fn into_secretbox_key(v: Vec<u8>, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Key> {
+    let v = Zeroizing(v);
+    Ok(Key(*ffi::secretbox_key(&v, context)?))
 }
 
-/// Converts a `Vec<u8>` into a `secretbox::Nonce` if it has the correct length.
+/// Converts a `Vec<u8>` into a secretbox [`Nonce`] if it has the correct length.
 ///
 /// This function takes ownership of a `Vec<u8>` and attempts to convert it into a
-/// `Box<[u8; secretbox::NONCEBYTES]>`. If the input vector does not have the correct
-/// length (`secretbox::NONCEBYTES`), an error is returned with contextual details.
+/// `[u8; crypto_secretbox_NONCEBYTES]`. If the input vector does not have the correct
+/// length (`crypto_secretbox_NONCEBYTES`), an error is returned with contextual details.
 ///
 /// # Arguments
 ///
-/// * `v` - A vector of bytes expected to be exactly `secretbox::NONCEBYTES` in length.
+/// * `v` - A vector of bytes expected to be exactly `crypto_secretbox_NONCEBYTES` in length.
 /// * `context` - A BTreeMap containing additional context that will be attached to the error
 ///   in case of failure. Useful for debugging or logging.
 ///
 /// # Returns
 ///
-/// Returns `Ok(secretbox::Nonce)` if the conversion is successful. Otherwise, returns
+/// Returns `Ok(Nonce)` if the conversion is successful. Otherwise, returns
 /// a `cdumay_core::Error` (typically wrapping an `InvalidBoxNonceLength`).
 ///
 /// # Errors
 ///
-/// This function returns an error if `v.len() != secretbox::NONCEBYTES`.
-fn into_secretbox_nonce(v: Vec<u8>, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<secretbox::Nonce> {
-    if v.len() != secretbox::NONCEBYTES {
-        return Err(InvalidBoxNonceLength::new()
-            .with_message(format!("Invalid box_nonce length required: {}", secretbox::NONCEBYTES))
-            .with_details(context)
-            .into());
-    }
-    let boxed_slice = v.into_boxed_slice();
-    let boxed_array: Box<[u8; secretbox::NONCEBYTES]> = boxed_slice.try_into().unwrap_or_else(|_| unreachable!());
-    Ok(secretbox::Nonce(*boxed_array))
+/// This function returns an error if `v.len() != crypto_secretbox_NONCEBYTES`.
+pub(crate) fn into_secretbox_nonce(v: Vec<u8>, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Nonce> {
+    Ok(*ffi::secretbox_nonce(&v, context)?)
 }
 
 /// Decrypts data encrypted with libsodium's SecretBox using a provided key and nonce.
@@ -100,7 +159,7 @@ fn into_secretbox_nonce(v: Vec<u8>, context: BTreeMap<String, serde_value::Value
 /// * `data_b64` - The base64-encoded ciphertext to decrypt.
 /// * `sb_key_b64` - The base64-encoded secret key for SecretBox.
 /// * `nonce_b64` - The base64-encoded nonce for SecretBox.
-/// * `context` - A `BTreeMap` containing additional context information for error reporting.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
 ///
 /// # Returns
 ///
@@ -129,25 +188,22 @@ fn into_secretbox_nonce(v: Vec<u8>, context: BTreeMap<String, serde_value::Value
 /// let plaintext = decrypt(data_b64, sb_key_b64, nonce_b64, context).unwrap();
 /// println!("Decrypted: {}", plaintext);
 /// ```
-pub fn decrypt(data_b64: &str, sb_key_b64: &str, nonce_b64: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<String> {
+pub fn decrypt(data_b64: &str, sb_key_b64: &str, nonce_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "decrypt");
+    crate::init(context.clone())?;
     if data_b64.is_empty() {
         return Ok(String::new());
     }
 
-    let data_decoded = cdumay_base64::convert_decode_result!(BASE64_STANDARD.decode(data_b64), context.clone())?;
-    let sb_key_b64_decoded = cdumay_base64::convert_decode_result!(BASE64_STANDARD.decode(sb_key_b64), context.clone())?;
-    let nonce_b64_decoded = cdumay_base64::convert_decode_result!(BASE64_STANDARD.decode(nonce_b64), context.clone())?;
-
-    match secretbox::open(
-        data_decoded.as_slice(),
-        &into_secretbox_nonce(nonce_b64_decoded, context.clone())?,
-        &into_secretbox_key(sb_key_b64_decoded, context.clone())?,
-    ) {
-        Ok(decrypted) => vec_to_string(decrypted, context.clone()),
-        Err(_) => Err(FailedToOpenSecretBox::new()
-            .with_message("Decryption failed".to_string())
-            .with_details(context)
-            .into()),
+    let data_decoded = decode_base64(data_b64, "data", context.clone())?;
+    let sb_key_b64_decoded = decode_base64(sb_key_b64, "key", context.clone())?;
+    let nonce_b64_decoded = decode_base64(nonce_b64, "nonce", context.clone())?;
+
+    let nonce = into_secretbox_nonce(nonce_b64_decoded, context.clone())?;
+    let key = into_secretbox_key(sb_key_b64_decoded, context.clone())?;
+    match open_with(Algorithm::XSalsa20Poly1305, data_decoded.as_slice(), &nonce, key.as_ref()) {
+        Some(decrypted) => vec_to_string(decrypted, context.clone()),
+        None => Err(authentication_failed(Algorithm::XSalsa20Poly1305.id(), "data", with_key_fingerprint(context, key.fingerprint()))),
     }
 }
 
@@ -163,7 +219,7 @@ pub fn decrypt(data_b64: &str, sb_key_b64: &str, nonce_b64: &str, context: BTree
 ///
 /// * `data` - The plaintext data to encrypt as a UTF-8 string.
 /// * `sb_key_b64` - The base64-encoded secret key for SecretBox.
-/// * `context` - A `BTreeMap` containing additional context information for error reporting.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
 ///
 /// # Returns
 ///
@@ -191,12 +247,548 @@ pub fn decrypt(data_b64: &str, sb_key_b64: &str, nonce_b64: &str, context: BTree
 /// println!("Nonce (base64): {}", nonce_b64);
 /// println!("Ciphertext (base64): {}", ciphertext_b64);
 /// ```
-pub fn crypt(data: &str, sb_key_b64: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<(String, String)> {
-    let nonce = secretbox::gen_nonce();
-    let sb_key_b64_decoded = cdumay_base64::convert_decode_result!(BASE64_STANDARD.decode(sb_key_b64), context.clone())?;
-    let ciphertext = secretbox::seal(data.as_bytes(), &nonce, &into_secretbox_key(sb_key_b64_decoded, context.clone())?);
+pub fn crypt(data: &str, sb_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<(String, String)> {
+    let context = crate::errors::operation(context, module_path!(), "crypt");
+    crate::init(context.clone())?;
+    let sb_key_b64_decoded = decode_base64(sb_key_b64, "key", context.clone())?;
+    let key = into_secretbox_key(sb_key_b64_decoded, context.clone())?;
+    let (nonce, ciphertext) = seal_random(Algorithm::XSalsa20Poly1305, data.as_bytes(), key.as_ref(), context)?;
     Ok((
         BASE64_STANDARD.encode(nonce.as_ref()),
         BASE64_STANDARD.encode(ciphertext),
     ))
 }
+
+/// Encrypts data with a caller-supplied nonce, for peers which choose the nonce themselves.
+///
+/// The ciphertext is in the same combined form as [`crypt`] and is opened with [`decrypt`]. The caller is responsible for never
+/// reusing a nonce with the same key, for example by drawing nonces from a [`crate::nonce::NonceSequence`]. In debug builds a
+/// repeated nonce is reported as a [`crate::NonceReuse`] error.
+///
+/// # Arguments
+///
+/// * `data` - The plaintext data to encrypt.
+/// * `sb_key_b64` - The base64-encoded secret key for SecretBox.
+/// * `nonce_b64` - The base64-encoded nonce for SecretBox.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok(String)` containing the base64-encoded ciphertext, or an error of type [`cdumay_core::Error`].
+///
+/// # Errors
+///
+/// Returns an error if the key or nonce cannot be base64-decoded or has an invalid length.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::secretbox::{crypt_with_nonce, decrypt};
+///
+/// let sb_key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+/// let nonce_b64 = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYX";
+/// let context = BTreeMap::<String, Value>::new();
+/// let ciphertext_b64 = crypt_with_nonce("my secret message", sb_key_b64, nonce_b64, context.clone()).unwrap();
+/// assert_eq!(decrypt(&ciphertext_b64, sb_key_b64, nonce_b64, context).unwrap(), "my secret message");
+/// ```
+pub fn crypt_with_nonce(data: &str, sb_key_b64: &str, nonce_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "crypt_with_nonce");
+    crate::init(context.clone())?;
+    let key = decode_key(sb_key_b64, context.clone())?;
+    let nonce = decode_nonce(nonce_b64, context.clone())?;
+    check_nonce(key.as_ref(), &nonce, context)?;
+    Ok(BASE64_STANDARD.encode(seal_with(Algorithm::XSalsa20Poly1305, data.as_bytes(), &nonce, key.as_ref())))
+}
+
+/// Encrypts data with a fresh random nonce and returns the authentication tag separately from the ciphertext, as
+/// `crypto_secretbox_detached` does.
+///
+/// # Returns
+///
+/// Returns `Ok((String, String, String))` containing the base64-encoded nonce, ciphertext and tag, or an error of type
+/// [`cdumay_core::Error`].
+///
+/// # Errors
+///
+/// Returns an error if the key cannot be base64-decoded or has an invalid length.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::secretbox::{crypt_detached, decrypt_detached};
+///
+/// let sb_key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+/// let context = BTreeMap::<String, Value>::new();
+/// let (nonce_b64, ciphertext_b64, tag_b64) = crypt_detached("my secret message", sb_key_b64, context.clone()).unwrap();
+/// let plaintext = decrypt_detached(&ciphertext_b64, &tag_b64, sb_key_b64, &nonce_b64, context).unwrap();
+/// assert_eq!(plaintext, "my secret message");
+/// ```
+pub fn crypt_detached(data: &str, sb_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<(String, String, String)> {
+    let context = crate::errors::operation(context, module_path!(), "crypt_detached");
+    crate::init(context.clone())?;
+    let key = decode_key(sb_key_b64, context.clone())?;
+    let nonce = gen_nonce();
+    check_nonce(key.as_ref(), &nonce, context)?;
+    let mut ciphertext = data.as_bytes().to_vec();
+    let tag = ffi::secretbox_detached(&mut ciphertext, &nonce, key.as_array());
+    Ok((
+        BASE64_STANDARD.encode(nonce.as_ref()),
+        BASE64_STANDARD.encode(ciphertext),
+        BASE64_STANDARD.encode(tag.as_ref()),
+    ))
+}
+
+/// Decrypts a ciphertext whose authentication tag is transmitted separately, as produced by [`crypt_detached`] or
+/// `crypto_secretbox_detached`.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Any of the input strings cannot be base64-decoded.
+/// - The key, nonce or tag has an invalid length ([`crate::InvalidBoxTagLength`] for the tag).
+/// - The ciphertext fails authentication ([`AuthenticationFailed`]).
+/// - The decrypted data is not valid UTF-8.
+pub fn decrypt_detached(
+    data_b64: &str,
+    tag_b64: &str,
+    sb_key_b64: &str,
+    nonce_b64: &str,
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "decrypt_detached");
+    crate::init(context.clone())?;
+    let key = decode_key(sb_key_b64, context.clone())?;
+    let nonce = decode_nonce(nonce_b64, context.clone())?;
+    let tag_decoded = decode_base64(tag_b64, "tag", context.clone())?;
+    let tag = ffi::secretbox_tag(&tag_decoded, context.clone())?;
+    let mut data = decode_base64(data_b64, "data", context.clone())?;
+    match ffi::secretbox_open_detached(&mut data, tag, &nonce, key.as_array()) {
+        true => vec_to_string(data, context),
+        false => Err(authentication_failed(Algorithm::XSalsa20Poly1305.id(), "data", with_key_fingerprint(context, key.fingerprint()))),
+    }
+}
+
+/// Encrypts data with a fresh random nonce using the given secretbox algorithm.
+///
+/// With [`Algorithm::XSalsa20Poly1305`] this is the same as [`crypt`]. The algorithm is not recorded in the output: store it along
+/// with the ciphertext, or use an [`crate::envelope::Envelope`], which records it.
+///
+/// # Returns
+///
+/// Returns `Ok((String, String))` containing the base64-encoded nonce and ciphertext, or an error of type [`cdumay_core::Error`].
+///
+/// # Errors
+///
+/// Returns an error if the key cannot be base64-decoded or has an invalid length.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::secretbox::{crypt_with_algorithm, decrypt_with_algorithm, Algorithm};
+///
+/// let sb_key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+/// let context = BTreeMap::<String, Value>::new();
+/// let algorithm = Algorithm::XChaCha20Poly1305;
+/// let (nonce_b64, ciphertext_b64) = crypt_with_algorithm("my secret message", sb_key_b64, algorithm, context.clone()).unwrap();
+/// let plaintext = decrypt_with_algorithm(&ciphertext_b64, sb_key_b64, &nonce_b64, algorithm, context).unwrap();
+/// assert_eq!(plaintext, "my secret message");
+/// ```
+pub fn crypt_with_algorithm(
+    data: &str,
+    sb_key_b64: &str,
+    algorithm: Algorithm,
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<(String, String)> {
+    let context = crate::errors::operation(context, module_path!(), "crypt_with_algorithm");
+    crate::init(context.clone())?;
+    let key = decode_key(sb_key_b64, context.clone())?;
+    let (nonce, ciphertext) = seal_random(algorithm, data.as_bytes(), key.as_ref(), context)?;
+    Ok((BASE64_STANDARD.encode(nonce.as_ref()), BASE64_STANDARD.encode(ciphertext)))
+}
+
+/// Decrypts data encrypted with the given secretbox algorithm.
+///
+/// With [`Algorithm::XSalsa20Poly1305`] this is the same as [`decrypt`].
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Any of the input strings cannot be base64-decoded.
+/// - The key or nonce has an invalid length.
+/// - The ciphertext fails authentication, for example when it was sealed with another algorithm ([`AuthenticationFailed`]).
+/// - The decrypted data is not valid UTF-8.
+pub fn decrypt_with_algorithm(
+    data_b64: &str,
+    sb_key_b64: &str,
+    nonce_b64: &str,
+    algorithm: Algorithm,
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "decrypt_with_algorithm");
+    crate::init(context.clone())?;
+    if data_b64.is_empty() {
+        return Ok(String::new());
+    }
+    let key = decode_key(sb_key_b64, context.clone())?;
+    let nonce = decode_nonce(nonce_b64, context.clone())?;
+    let data_decoded = decode_base64(data_b64, "data", context.clone())?;
+    match open_with(algorithm, &data_decoded, &nonce, key.as_ref()) {
+        Some(decrypted) => vec_to_string(decrypted, context),
+        None => Err(authentication_failed(algorithm.id(), "data", with_key_fingerprint(context, key.fingerprint()))),
+    }
+}
+
+/// Re-encrypts a SecretBox ciphertext from an old key to a new key.
+///
+/// The ciphertext is opened with the old key and sealed again with the new key under a fresh random nonce. The plaintext only
+/// lives in a buffer which is wiped as soon as it has been re-encrypted, and is never converted to a `String`.
+///
+/// # Arguments
+///
+/// * `data_b64` - The base64-encoded ciphertext to re-encrypt.
+/// * `nonce_b64` - The base64-encoded nonce of the ciphertext.
+/// * `old_key_b64` - The base64-encoded secret key the ciphertext was encrypted with.
+/// * `new_key_b64` - The base64-encoded secret key to encrypt with.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok((String, String))` containing the new base64-encoded nonce and ciphertext, as returned by [`crypt`],
+/// or an error of type [`cdumay_core::Error`] if any step fails.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Any of the input strings cannot be base64-decoded.
+/// - A key or the nonce has an invalid length.
+/// - The ciphertext cannot be opened with the old key.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::secretbox::{decrypt, reencrypt};
+///
+/// let old_key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+/// let new_key_b64 = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+/// let context = BTreeMap::<String, Value>::new();
+/// let (nonce_b64, data_b64) = reencrypt(
+///     "sUm+U20INMw6G4tfovoe4YSPYqzYdhfPhZ2v5U9Mu6tYIQ==", "HZGeSXQLJlFNpQgGyvYkXj+jAL9d/15J", old_key_b64, new_key_b64, context.clone(),
+/// ).unwrap();
+/// let plaintext = decrypt(&data_b64, new_key_b64, &nonce_b64, context).unwrap();
+/// ```
+pub fn reencrypt(
+    data_b64: &str,
+    nonce_b64: &str,
+    old_key_b64: &str,
+    new_key_b64: &str,
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<(String, String)> {
+    let context = crate::errors::operation(context, module_path!(), "reencrypt");
+    crate::init(context.clone())?;
+    let old_key = decode_key(old_key_b64, context.clone())?;
+    let new_key = decode_key(new_key_b64, context.clone())?;
+    let open = |data: &[u8], nonce: &Nonce| open_with(Algorithm::XSalsa20Poly1305, data, nonce, old_key.as_ref());
+    reencrypt_with(data_b64, nonce_b64, open, Some(old_key.fingerprint()), new_key.as_ref(), context)
+}
+
+/// Re-encrypts a batch of SecretBox ciphertexts from an old key to a new key.
+///
+/// Keys are decoded once, before the first item. Each item is a `(nonce_b64, data_b64)` pair as returned by [`crypt`], and yields
+/// the re-encrypted pair or its own error: a failing item does not stop the iteration. Item errors carry the given `context`
+/// along with the `index` of the item in the batch.
+///
+/// # Errors
+///
+/// Returns an error immediately if a key cannot be base64-decoded or has an invalid length.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::secretbox::{crypt, reencrypt_batch};
+///
+/// let old_key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+/// let new_key_b64 = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+/// let context = BTreeMap::<String, Value>::new();
+/// let stored = vec![
+///     crypt("first", old_key_b64, context.clone()).unwrap(),
+///     ("HZGeSXQLJlFNpQgGyvYkXj+jAL9d/15J".to_string(), "Y29ycnVwdGVk".to_string()),
+/// ];
+/// let results: Vec<_> = reencrypt_batch(stored, old_key_b64, new_key_b64, context).unwrap().collect();
+/// assert!(results[0].is_ok());
+/// assert!(results[1].is_err());
+/// ```
+pub fn reencrypt_batch<I, N, D>(
+    items: I,
+    old_key_b64: &str,
+    new_key_b64: &str,
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<impl Iterator<Item = cdumay_core::Result<(String, String)>>>
+where
+    I: IntoIterator<Item = (N, D)>,
+    N: AsRef<str>,
+    D: AsRef<str>,
+{
+    let context = crate::errors::operation(context, module_path!(), "reencrypt_batch");
+    crate::init(context.clone())?;
+    let old_key = decode_key(old_key_b64, context.clone())?;
+    let new_key = decode_key(new_key_b64, context.clone())?;
+    Ok(items.into_iter().enumerate().map(move |(index, (nonce_b64, data_b64))| {
+        let mut item_context = context.clone();
+        item_context.insert("index".to_string(), serde_value::Value::U64(index as u64));
+        reencrypt_with(
+            data_b64.as_ref(),
+            nonce_b64.as_ref(),
+            |data, nonce| open_with(Algorithm::XSalsa20Poly1305, data, nonce, old_key.as_ref()),
+            Some(old_key.fingerprint()),
+            new_key.as_ref(),
+            item_context,
+        )
+    }))
+}
+
+/// Decodes a base64-encoded secretbox key.
+fn decode_key(key_b64: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Key> {
+    let key_decoded = decode_base64(key_b64, "key", context.clone())?;
+    into_secretbox_key(key_decoded, context)
+}
+
+/// Decodes a base64-encoded secretbox nonce.
+fn decode_nonce(nonce_b64: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Nonce> {
+    let nonce_decoded = decode_base64(nonce_b64, "nonce", context.clone())?;
+    into_secretbox_nonce(nonce_decoded, context)
+}
+
+/// Opens a ciphertext with `open` and seals the plaintext again with `new_key`, wiping the plaintext afterwards. The fingerprint of
+/// the old key, when `open` uses a single one, is added to the authentication failure.
+pub(crate) fn reencrypt_with<F>(
+    data_b64: &str,
+    nonce_b64: &str,
+    open: F,
+    old_key: Option<Fingerprint>,
+    new_key: &[u8],
+    context: BTreeMap<String, serde_value::Value>,
+) -> cdumay_core::Result<(String, String)>
+where
+    F: FnOnce(&[u8], &Nonce) -> Option<Vec<u8>>,
+{
+    let data_decoded = decode_base64(data_b64, "data", context.clone())?;
+    let nonce_decoded = decode_base64(nonce_b64, "nonce", context.clone())?;
+    let nonce = into_secretbox_nonce(nonce_decoded, context.clone())?;
+    let plaintext = match open(&data_decoded, &nonce) {
+        Some(plaintext) => Zeroizing(plaintext),
+        None => {
+            let context = match old_key {
+                Some(fingerprint) => with_key_fingerprint(context, fingerprint),
+                None => context,
+            };
+            return Err(authentication_failed(Algorithm::XSalsa20Poly1305.id(), "data", context));
+        }
+    };
+    let (nonce, ciphertext) = seal_random(Algorithm::XSalsa20Poly1305, &plaintext, new_key, context)?;
+    Ok((BASE64_STANDARD.encode(nonce.as_ref()), BASE64_STANDARD.encode(ciphertext)))
+}
+
+/// Reports a [`crate::NonceReuse`] error in debug builds if `nonce` was already used with `key`; does nothing in release builds.
+#[allow(unused_variables)]
+pub(crate) fn check_nonce(key: &[u8], nonce: &Nonce, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<()> {
+    #[cfg(debug_assertions)]
+    NONCE_REUSE_DETECTOR
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .observe(key, nonce.as_ref(), context)?;
+    Ok(())
+}
+
+/// Seals data in combined form (tag followed by ciphertext) with the given algorithm.
+///
+/// `key` must have been validated on construction (a [`Key`] or a keyring key); it is a slice so keys held in a
+/// [`crate::memory::SecureBuffer`] need no copy.
+pub(crate) fn seal_with(algorithm: Algorithm, data: &[u8], nonce: &Nonce, key: &[u8]) -> Vec<u8> {
+    ffi::secretbox_easy(algorithm, data, nonce, key_array(key))
+}
+
+/// Seals data in combined form with a fresh random nonce, checked by the debug nonce-reuse detector, and returns the nonce with
+/// the ciphertext. Every encryption under a long-lived key goes through this function.
+pub(crate) fn seal_random(
+    algorithm: Algorithm,
+    data: &[u8],
+    key: &[u8],
+    context: BTreeMap<String, serde_value::Value>,
+) -> cdumay_core::Result<(Nonce, Vec<u8>)> {
+    let nonce = gen_nonce();
+    check_nonce(key, &nonce, context)?;
+    Ok((nonce, seal_with(algorithm, data, &nonce, key)))
+}
+
+/// Opens data in combined form with the given algorithm, or returns `None` if it fails authentication.
+pub(crate) fn open_with(algorithm: Algorithm, data: &[u8], nonce: &Nonce, key: &[u8]) -> Option<Vec<u8>> {
+    ffi::secretbox_open_easy(algorithm, data, nonce, key_array(key))
+}
+
+/// Views a key validated on construction as the array expected by [`ffi`].
+fn key_array(key: &[u8]) -> &[u8; ffi::SECRETBOX_KEYBYTES] {
+    key.try_into().expect("secretbox keys are validated on construction")
+}
+
+/// Encrypts raw bytes with a fresh random nonce and returns `nonce || ciphertext`.
+///
+/// # Errors
+///
+/// Returns a [`crate::NonceReuse`] error if the debug nonce-reuse detector sees the nonce twice.
+pub(crate) fn seal_bytes(data: &[u8], key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+    let (nonce, ciphertext) = seal_random(Algorithm::XSalsa20Poly1305, data, key, context)?;
+    let mut sealed = nonce.as_ref().to_vec();
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts raw `nonce || ciphertext` bytes produced by [`seal_bytes`].
+///
+/// # Errors
+///
+/// Returns a [`crate::CiphertextTooShort`] error if the input is too short, or an [`AuthenticationFailed`] error if it fails
+/// authentication.
+pub(crate) fn open_bytes(data: &[u8], key: &[u8], context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+    let minimum = ffi::SECRETBOX_NONCEBYTES + ffi::SECRETBOX_MACBYTES;
+    if data.len() < minimum {
+        return Err(ciphertext_too_short(Algorithm::XSalsa20Poly1305.id(), "data", minimum, data.len(), context));
+    }
+    let (nonce, ciphertext) = data.split_at(ffi::SECRETBOX_NONCEBYTES);
+    let nonce: Nonce = nonce.try_into().unwrap_or_else(|_| unreachable!());
+    let algorithm = Algorithm::XSalsa20Poly1305;
+    open_with(algorithm, ciphertext, &nonce, key).ok_or_else(|| authentication_failed(algorithm.id(), "data", context))
+}
+
+/// Encrypts data deterministically: the same plaintext, key and associated data always produce the same ciphertext.
+///
+/// The nonce is not random but derived as a keyed BLAKE2b of the associated data and the plaintext, so equal plaintexts can be
+/// compared without decryption. The nonce derivation and the encryption use two distinct subkeys derived from `sb_key_b64` with
+/// `crypto_kdf`. The associated data (for example the table and column name) is authenticated but not encrypted, and separates
+/// the ciphertexts of different columns.
+///
+/// **Equality of plaintexts is revealed.** Only use this mode for values which must be matched by equality; use [`crypt`]
+/// otherwise.
+///
+/// # Arguments
+///
+/// * `data` - The plaintext data to encrypt.
+/// * `associated_data` - Data bound to the ciphertext, which must be given again on decryption.
+/// * `sb_key_b64` - The base64-encoded secret key for SecretBox.
+/// * `context` - Details added to the errors, or `None`; the crate adds the `module` and `operation` details.
+///
+/// # Returns
+///
+/// Returns `Ok(String)` containing the base64-encoded `nonce || ciphertext`, or an error of type [`cdumay_core::Error`].
+///
+/// # Errors
+///
+/// Returns an error if the key cannot be base64-decoded or has an invalid length.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_value::Value;
+/// use cdumay_sodium::secretbox::{crypt_deterministic, decrypt_deterministic};
+///
+/// let sb_key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+/// let context = BTreeMap::<String, Value>::new();
+/// let first = crypt_deterministic("alice@example.com", "users.email", sb_key_b64, context.clone()).unwrap();
+/// let second = crypt_deterministic("alice@example.com", "users.email", sb_key_b64, context.clone()).unwrap();
+/// assert_eq!(first, second);
+/// assert_eq!(decrypt_deterministic(&first, "users.email", sb_key_b64, context).unwrap(), "alice@example.com");
+/// ```
+pub fn crypt_deterministic(data: &str, associated_data: &str, sb_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "crypt_deterministic");
+    crate::init(context.clone())?;
+    let (encryption_key, nonce_key) = deterministic_subkeys(&decode_key(sb_key_b64, context)?);
+    let nonce = deterministic_nonce(&nonce_key, associated_data, data.as_bytes());
+    let mut sealed = nonce.as_ref().to_vec();
+    sealed.extend(seal_with(Algorithm::XSalsa20Poly1305, data.as_bytes(), &nonce, encryption_key.as_ref()));
+    Ok(BASE64_STANDARD.encode(sealed))
+}
+
+/// Decrypts a ciphertext produced by [`crypt_deterministic`] and verifies that its nonce derives from the plaintext and
+/// associated data.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The data or key cannot be base64-decoded, or the key has an invalid length.
+/// - The ciphertext fails authentication, or its nonce does not match the derivation, which also happens when the associated data
+///   differs ([`AuthenticationFailed`]).
+/// - The decrypted data is not valid UTF-8.
+pub fn decrypt_deterministic(
+    data_b64: &str,
+    associated_data: &str,
+    sb_key_b64: &str,
+    context: impl Into<Option<Context>>,
+) -> cdumay_core::Result<String> {
+    let context = crate::errors::operation(context, module_path!(), "decrypt_deterministic");
+    crate::init(context.clone())?;
+    let key = decode_key(sb_key_b64, context.clone())?;
+    let (encryption_key, nonce_key) = deterministic_subkeys(&key);
+    let data_decoded = decode_base64(data_b64, "data", context.clone())?;
+    // The subkeys are derived from the master key, which is the key the caller knows.
+    let key_context = with_key_fingerprint(context.clone(), key.fingerprint());
+    let plaintext = Zeroizing(open_bytes(&data_decoded, encryption_key.as_ref(), key_context.clone())?);
+    let expected = deterministic_nonce(&nonce_key, associated_data, &plaintext);
+    if !ffi::memcmp(&expected, &data_decoded[..ffi::SECRETBOX_NONCEBYTES]) {
+        return Err(AuthenticationFailed::new()
+            .with_message("Deterministic nonce verification failed".to_string())
+            .with_details(enrich(key_context, &[("algorithm", Detail::Text(Algorithm::XSalsa20Poly1305.id())), ("field", Detail::Text("data"))]))
+            .into());
+    }
+    vec_to_string(plaintext.to_vec(), context)
+}
+
+/// Returns the fingerprint of a secretbox key, to name it in logs without revealing it (see [`crate::fingerprint`]).
+///
+/// # Errors
+///
+/// Returns an error if the key cannot be base64-decoded or has an invalid length.
+///
+/// # Example
+///
+/// ```
+/// use cdumay_sodium::secretbox;
+///
+/// let fingerprint = secretbox::fingerprint("llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=", None).unwrap();
+/// println!("Encrypting with key {}", fingerprint.to_hex_groups());
+/// ```
+pub fn fingerprint(sb_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<Fingerprint> {
+    let context = crate::errors::operation(context, module_path!(), "fingerprint");
+    crate::init(context.clone())?;
+    Ok(decode_key(sb_key_b64, context)?.fingerprint())
+}
+
+/// Derives the encryption and nonce subkeys of the deterministic mode.
+fn deterministic_subkeys(key: &Key) -> (Key, Key) {
+    let derive = |subkey_id: u64| {
+        let mut subkey = Key([0u8; ffi::SECRETBOX_KEYBYTES]);
+        ffi::kdf_derive_from_key(&mut subkey.0, subkey_id, DETERMINISTIC_KDF_CONTEXT, key.as_array());
+        subkey
+    };
+    (derive(DETERMINISTIC_ENCRYPTION_SUBKEY), derive(DETERMINISTIC_NONCE_SUBKEY))
+}
+
+/// Derives the nonce of the deterministic mode as `BLAKE2b-192(nonce_key, len(associated_data) || associated_data || plaintext)`.
+fn deterministic_nonce(nonce_key: &Key, associated_data: &str, plaintext: &[u8]) -> Nonce {
+    let mut input = Zeroizing(Vec::with_capacity(8 + associated_data.len() + plaintext.len()));
+    input.0.extend_from_slice(&(associated_data.len() as u64).to_le_bytes());
+    input.0.extend_from_slice(associated_data.as_bytes());
+    input.0.extend_from_slice(plaintext);
+    let mut nonce = [0u8; ffi::SECRETBOX_NONCEBYTES];
+    ffi::generichash(&mut nonce, &input, Some(nonce_key.as_ref()));
+    nonce
+}
@@ -0,0 +1,295 @@
+//! Field-level encryption with serde: annotate sensitive fields with `#[serde(with = "cdumay_sodium::serde::secretbox")]` (or
+//! [`sealedbox`]) and they are encrypted on serialization and decrypted on deserialization.
+//!
+//! Keys are not passed to serde: they are taken from a [`KeyScope`], which installs keys for the current thread until the scope is
+//! dropped. Scopes can be nested: the most recently installed scope that is still alive provides the keys, so dropping one, in
+//! any order, restores the keys of the enclosing scopes.
+//!
+//! Encrypted fields are serialized as compact envelope strings:
+//!
+//! * secretbox: `sb:<key_id>:<base64(nonce || ciphertext)>`, encrypted with the active key of a [`Keyring`], so that keys can be
+//!   rotated.
+//! * sealedbox: `seal:<base64(sealed box)>`, encrypted to a public key. Serializing only needs the public key, deserializing needs
+//!   the recipient keypair, which suits write-only fields that only a backend can read.
+//!
+//! Fields must implement `AsRef<str>` to be serialized and `From<String>` to be deserialized, as `String` does.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use serde::{Deserialize, Serialize};
+//! use serde_value::Value;
+//! use cdumay_sodium::keyring::Keyring;
+//! use cdumay_sodium::serde::KeyScope;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Account {
+//!     login: String,
+//!     #[serde(with = "cdumay_sodium::serde::secretbox")]
+//!     api_token: String,
+//! }
+//!
+//! let context = BTreeMap::<String, Value>::new();
+//! let keyring = Keyring::new("2024", "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=", context).unwrap();
+//! let _scope = KeyScope::secretbox(keyring);
+//!
+//! let account = Account { login: "alice".to_string(), api_token: "s3cr3t".to_string() };
+//! let value = serde_value::to_value(&account).unwrap();
+//! let account: Account = value.deserialize_into().unwrap();
+//! assert_eq!(account.api_token, "s3cr3t");
+//! ```
+
+use crate::{Context, Zeroizing, decode_base64};
+use crate::keyring::{KEY_ID_SEPARATOR, Keyring};
+use crate::memory::SecureBuffer;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Prefix of secretbox encrypted fields.
+pub const SECRETBOX_PREFIX: &str = "sb:";
+/// Prefix of sealedbox encrypted fields.
+pub const SEALEDBOX_PREFIX: &str = "seal:";
+
+/// Sealed-box keys installed by a [`KeyScope`], the private key in a [`SecureBuffer`].
+struct SealedBoxKeys {
+    public_key: Vec<u8>,
+    private_key: Option<SecureBuffer>,
+}
+
+thread_local! {
+    static SECRETBOX_KEYRINGS: RefCell<Vec<Rc<Keyring>>> = const { RefCell::new(Vec::new()) };
+    static SEALEDBOX_KEYS: RefCell<Vec<Rc<SealedBoxKeys>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keys installed by a scope, removed from their stack by identity when the scope is dropped.
+enum Installed {
+    SecretBox(Rc<Keyring>),
+    SealedBox(Rc<SealedBoxKeys>),
+}
+
+/// Removes `keys` from `stack`, wherever it is: scopes may be dropped in any order.
+fn uninstall<T>(stack: &RefCell<Vec<Rc<T>>>, keys: &Rc<T>) {
+    let mut stack = stack.borrow_mut();
+    if let Some(position) = stack.iter().rposition(|installed| Rc::ptr_eq(installed, keys)) {
+        stack.remove(position);
+    }
+}
+
+/// Installs keys used by the serde adapters on the current thread, until dropped.
+///
+/// A scope only replaces the keys of its own kind: a secretbox scope keeps the sealedbox keys of an enclosing scope and vice versa.
+#[must_use = "keys are uninstalled as soon as the scope is dropped"]
+pub struct KeyScope {
+    installed: Installed,
+}
+
+impl KeyScope {
+    /// Installs a keyring used by the [`secretbox`] adapter: fields are encrypted with its active key and decrypted with the key
+    /// named in the envelope.
+    pub fn secretbox(keyring: Keyring) -> KeyScope {
+        let keyring = Rc::new(keyring);
+        SECRETBOX_KEYRINGS.with(|stack| stack.borrow_mut().push(keyring.clone()));
+        KeyScope { installed: Installed::SecretBox(keyring) }
+    }
+
+    /// Installs the public key used by the [`sealedbox`] adapter to encrypt fields. Fields cannot be decrypted in this scope.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be base64-decoded.
+    pub fn sealedbox_public(public_key_b64: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<KeyScope> {
+        let context = crate::errors::operation(context, module_path!(), "KeyScope::sealedbox_public");
+        let public_key = decode_base64(public_key_b64, "public_key", context)?;
+        Ok(KeyScope::install_sealedbox(SealedBoxKeys { public_key, private_key: None }))
+    }
+
+    /// Installs the recipient keypair used by the [`sealedbox`] adapter to encrypt and decrypt fields.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key cannot be base64-decoded, or a [`crate::SecureMemoryAllocationFailed`] error if the secure buffer
+    /// of the private key cannot be allocated.
+    pub fn sealedbox_keypair(
+        private_key_b64: &str,
+        public_key_b64: &str,
+        context: impl Into<Option<Context>>,
+    ) -> cdumay_core::Result<KeyScope> {
+        let context = crate::errors::operation(context, module_path!(), "KeyScope::sealedbox_keypair");
+        let private_key = Zeroizing(decode_base64(private_key_b64, "private_key", context.clone())?);
+        let private_key = SecureBuffer::from_slice(&private_key, context.clone())?;
+        let public_key = decode_base64(public_key_b64, "public_key", context)?;
+        Ok(KeyScope::install_sealedbox(SealedBoxKeys { public_key, private_key: Some(private_key) }))
+    }
+
+    fn install_sealedbox(keys: SealedBoxKeys) -> KeyScope {
+        let keys = Rc::new(keys);
+        SEALEDBOX_KEYS.with(|stack| stack.borrow_mut().push(keys.clone()));
+        KeyScope { installed: Installed::SealedBox(keys) }
+    }
+}
+
+impl Drop for KeyScope {
+    fn drop(&mut self) {
+        match &self.installed {
+            Installed::SecretBox(keyring) => SECRETBOX_KEYRINGS.with(|stack| uninstall(stack, keyring)),
+            Installed::SealedBox(keys) => SEALEDBOX_KEYS.with(|stack| uninstall(stack, keys)),
+        }
+    }
+}
+
+fn current_keyring() -> Option<Rc<Keyring>> {
+    SECRETBOX_KEYRINGS.with(|stack| stack.borrow().last().cloned())
+}
+
+fn current_sealedbox_keys() -> Option<Rc<SealedBoxKeys>> {
+    SEALEDBOX_KEYS.with(|stack| stack.borrow().last().cloned())
+}
+
+/// Serde adapter encrypting a string field with the secretbox keyring of the current [`KeyScope`].
+///
+/// Use with `#[serde(with = "cdumay_sodium::serde::secretbox")]`.
+pub mod secretbox {
+    use super::{KEY_ID_SEPARATOR, SECRETBOX_PREFIX, current_keyring};
+    use crate::{Context, InvalidEnvelope, KeyNotInScope, decode_base64, secretbox as sb, vec_to_string};
+    use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+    use cdumay_base64::base64::Engine;
+    use std::collections::BTreeMap;
+
+    /// Encrypts a plaintext into a `sb:<key_id>:<base64(nonce || ciphertext)>` envelope with the active key of the scoped keyring.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`KeyNotInScope`] error if no keyring is installed on the current thread.
+    pub fn encrypt(plaintext: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+        let context = crate::errors::operation(context, module_path!(), "encrypt");
+        crate::init(context.clone())?;
+        let keyring = current_keyring().ok_or_else(|| missing_key(context.clone()))?;
+        let sealed = sb::seal_bytes(plaintext.as_bytes(), &keyring.active_key(), context)?;
+        Ok(format!("{}{}{}{}", SECRETBOX_PREFIX, keyring.active_id(), KEY_ID_SEPARATOR, BASE64_STANDARD.encode(sealed)))
+    }
+
+    /// Decrypts an envelope produced by [`encrypt`] with the scoped keyring.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no keyring is installed, if the envelope is malformed or names an unknown key, if it fails authentication
+    /// or if the plaintext is not valid UTF-8.
+    pub fn decrypt(envelope: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+        let context = crate::errors::operation(context, module_path!(), "decrypt");
+        crate::init(context.clone())?;
+        let keyring = current_keyring().ok_or_else(|| missing_key(context.clone()))?;
+        let (key_id, data_b64) = envelope
+            .strip_prefix(SECRETBOX_PREFIX)
+            .and_then(|rest| rest.split_once(KEY_ID_SEPARATOR))
+            .ok_or_else(|| -> cdumay_core::Error {
+                InvalidEnvelope::new()
+                    .with_message("Not a secretbox field envelope".to_string())
+                    .with_details(context.clone())
+                    .into()
+            })?;
+        let key = keyring.key(key_id, context.clone())?;
+        let data_decoded = decode_base64(data_b64, "data", context.clone())?;
+        vec_to_string(sb::open_bytes(&data_decoded, &key, context.clone())?, context)
+    }
+
+    fn missing_key(context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Error {
+        KeyNotInScope::new()
+            .with_message("No secretbox keyring in scope".to_string())
+            .with_details(context)
+            .into()
+    }
+
+    /// Serializes the field as an encrypted envelope string.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<str>,
+        S: ::serde::Serializer,
+    {
+        let envelope = encrypt(value.as_ref(), BTreeMap::new()).map_err(::serde::ser::Error::custom)?;
+        serializer.serialize_str(&envelope)
+    }
+
+    /// Deserializes and decrypts an encrypted envelope string.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: From<String>,
+        D: ::serde::Deserializer<'de>,
+    {
+        let envelope = <String as ::serde::Deserialize>::deserialize(deserializer)?;
+        decrypt(&envelope, BTreeMap::new()).map(T::from).map_err(::serde::de::Error::custom)
+    }
+}
+
+/// Serde adapter sealing a string field to the public key of the current [`KeyScope`].
+///
+/// Use with `#[serde(with = "cdumay_sodium::serde::sealedbox")]`.
+pub mod sealedbox {
+    use super::{SEALEDBOX_PREFIX, current_sealedbox_keys};
+    use crate::{Context, InvalidEnvelope, KeyNotInScope, decode_base64, sealedbox as sealed, vec_to_string};
+    use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+    use cdumay_base64::base64::Engine;
+    use std::collections::BTreeMap;
+
+    /// Seals a plaintext into a `seal:<base64(sealed box)>` envelope with the scoped public key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no sealedbox key is installed on the current thread or if the public key is invalid.
+    pub fn encrypt(plaintext: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+        let context = crate::errors::operation(context, module_path!(), "encrypt");
+        let keys = current_sealedbox_keys().ok_or_else(|| missing_key("No sealedbox public key in scope", context.clone()))?;
+        let sealed = sealed::seal_bytes(plaintext.as_bytes(), &keys.public_key, context)?;
+        Ok(format!("{}{}", SEALEDBOX_PREFIX, BASE64_STANDARD.encode(sealed)))
+    }
+
+    /// Opens an envelope produced by [`encrypt`] with the scoped keypair.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no keypair is installed, if the envelope is malformed or fails authentication, or if the plaintext is not
+    /// valid UTF-8.
+    pub fn decrypt(envelope: &str, context: impl Into<Option<Context>>) -> cdumay_core::Result<String> {
+        let context = crate::errors::operation(context, module_path!(), "decrypt");
+        let keys = current_sealedbox_keys().ok_or_else(|| missing_key("No sealedbox keypair in scope", context.clone()))?;
+        let private_key = keys
+            .private_key
+            .as_ref()
+            .ok_or_else(|| missing_key("Sealedbox fields are write-only in this scope", context.clone()))?;
+        let data_b64 = envelope.strip_prefix(SEALEDBOX_PREFIX).ok_or_else(|| -> cdumay_core::Error {
+            InvalidEnvelope::new()
+                .with_message("Not a sealedbox field envelope".to_string())
+                .with_details(context.clone())
+                .into()
+        })?;
+        let data_decoded = decode_base64(data_b64, "data", context.clone())?;
+        vec_to_string(sealed::open_bytes(&data_decoded, &private_key.read(), &keys.public_key, context.clone())?, context)
+    }
+
+    fn missing_key(message: &str, context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Error {
+        KeyNotInScope::new()
+            .with_message(message.to_string())
+            .with_details(context)
+            .into()
+    }
+
+    /// Serializes the field as a sealed envelope string.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<str>,
+        S: ::serde::Serializer,
+    {
+        let envelope = encrypt(value.as_ref(), BTreeMap::new()).map_err(::serde::ser::Error::custom)?;
+        serializer.serialize_str(&envelope)
+    }
+
+    /// Deserializes and opens a sealed envelope string.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: From<String>,
+        D: ::serde::Deserializer<'de>,
+    {
+        let envelope = <String as ::serde::Deserialize>::deserialize(deserializer)?;
+        decrypt(&envelope, BTreeMap::new()).map(T::from).map_err(::serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,79 @@
+/*
+ * Exercises the C API from C: opens the envelopes given on the command line, which were written by Rust, then prints envelopes
+ * sealed from C, one per line, for Rust to open.
+ *
+ * Usage: harness <secretbox envelope> <sealedbox envelope>
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "cdumay_sodium.h"
+
+#define KEK_ID "kek"
+#define KEK_B64 "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU="
+#define PRIVATE_KEY_B64 "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU="
+#define PUBLIC_KEY_B64 "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4="
+#define FROM_RUST "written by rust"
+#define FROM_C "written by c"
+
+#define CHECK(condition)                                                                           \
+    do {                                                                                           \
+        if (!(condition)) {                                                                        \
+            const char *error = cdumay_sodium_last_error();                                        \
+            fprintf(stderr, "%s:%d: %s (%s)\n", __FILE__, __LINE__, #condition, error ? error : ""); \
+            return 1;                                                                              \
+        }                                                                                          \
+    } while (0)
+
+static int check_opened(const uint8_t *out, size_t written) {
+    return written == strlen(FROM_RUST) && memcmp(out, FROM_RUST, written) == 0;
+}
+
+int main(int argc, char **argv) {
+    uint8_t out[64];
+    char sealed[512];
+    size_t written = 0;
+    size_t len = 0;
+
+    CHECK(argc == 3);
+    CHECK(cdumay_sodium_init() == CDUMAY_SODIUM_OK);
+
+    /* Envelopes written by Rust. */
+    CHECK(cdumay_sodium_opened_len(argv[1], &len) == CDUMAY_SODIUM_OK);
+    CHECK(len == strlen(FROM_RUST));
+    CHECK(cdumay_sodium_secretbox_open(KEK_ID, KEK_B64, argv[1], out, sizeof(out), &written) == CDUMAY_SODIUM_OK);
+    CHECK(check_opened(out, written));
+    CHECK(cdumay_sodium_sealedbox_open(KEK_ID, PUBLIC_KEY_B64, PRIVATE_KEY_B64, argv[2], out, sizeof(out), &written) ==
+          CDUMAY_SODIUM_OK);
+    CHECK(check_opened(out, written));
+
+    /* Length queries are exact, and a buffer one byte short is rejected. */
+    len = cdumay_sodium_secretbox_sealed_len(strlen(KEK_ID), CDUMAY_SODIUM_XCHACHA20POLY1305, strlen(FROM_C));
+    CHECK(len > 0 && len <= sizeof(sealed));
+    CHECK(cdumay_sodium_secretbox_seal(KEK_ID, KEK_B64, CDUMAY_SODIUM_XCHACHA20POLY1305, (const uint8_t *)FROM_C,
+                                       strlen(FROM_C), sealed, len - 1) == CDUMAY_SODIUM_ERR_BUFFER_TOO_SMALL);
+    CHECK(cdumay_sodium_secretbox_seal(KEK_ID, KEK_B64, CDUMAY_SODIUM_XCHACHA20POLY1305, (const uint8_t *)FROM_C,
+                                       strlen(FROM_C), sealed, len) == CDUMAY_SODIUM_OK);
+    CHECK(strlen(sealed) + 1 == len);
+    printf("%s\n", sealed);
+
+    len = cdumay_sodium_sealedbox_sealed_len(strlen(KEK_ID), CDUMAY_SODIUM_XSALSA20POLY1305, strlen(FROM_C));
+    CHECK(len > 0 && len <= sizeof(sealed));
+    CHECK(cdumay_sodium_sealedbox_seal(KEK_ID, PUBLIC_KEY_B64, CDUMAY_SODIUM_XSALSA20POLY1305, (const uint8_t *)FROM_C,
+                                       strlen(FROM_C), sealed, len) == CDUMAY_SODIUM_OK);
+    CHECK(strlen(sealed) + 1 == len);
+    printf("%s\n", sealed);
+
+    /* Errors are reported with the status of their kind. */
+    CHECK(cdumay_sodium_secretbox_open(KEK_ID, KEK_B64, argv[1], out, 1, &written) == CDUMAY_SODIUM_ERR_BUFFER_TOO_SMALL);
+    CHECK(written == strlen(FROM_RUST));
+    CHECK(cdumay_sodium_secretbox_open("other", KEK_B64, argv[1], out, sizeof(out), &written) ==
+          CDUMAY_SODIUM_ERR_INVALID_CONFIGURATION);
+    CHECK(cdumay_sodium_secretbox_open(KEK_ID, KEK_B64, "v1.kek", out, sizeof(out), &written) == CDUMAY_SODIUM_ERR_VALIDATION);
+    CHECK(cdumay_sodium_secretbox_open(KEK_ID, "not base64!", argv[1], out, sizeof(out), &written) == CDUMAY_SODIUM_ERR_BASE64);
+    CHECK(cdumay_sodium_secretbox_open(KEK_ID, KEK_B64, NULL, out, sizeof(out), &written) == CDUMAY_SODIUM_ERR_INVALID_ARGUMENT);
+    CHECK(cdumay_sodium_secretbox_seal(KEK_ID, KEK_B64, 42, NULL, 0, sealed, sizeof(sealed)) == CDUMAY_SODIUM_ERR_INVALID_ARGUMENT);
+    CHECK(cdumay_sodium_secretbox_sealed_len(strlen(KEK_ID), 42, 0) == 0);
+    CHECK(strstr(cdumay_sodium_last_error(), "Unknown algorithm") != NULL);
+    return 0;
+}
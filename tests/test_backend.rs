@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod test {
+    use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+    use cdumay_base64::base64::Engine;
+    use cdumay_sodium::backend::{self, Backend, Sodium};
+    use cdumay_sodium::secretbox::Algorithm;
+    use cdumay_sodium::{sealedbox, secretbox};
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+    const INPUT: &[u8] = br#"{"hello": "world"}"#;
+    const ALGORITHMS: [Algorithm; 2] = [Algorithm::XSalsa20Poly1305, Algorithm::XChaCha20Poly1305];
+
+    fn backends() -> Vec<Box<dyn Backend>> {
+        vec![
+            Box::new(Sodium),
+            #[cfg(feature = "rustcrypto")]
+            Box::new(backend::RustCrypto::default()),
+        ]
+    }
+
+    #[test]
+    fn test_backend_default() {
+        assert_eq!(backend::default().name(), "libsodium");
+    }
+
+    #[test]
+    fn test_backend_cross_secretbox() {
+        for sealer in backends() {
+            for opener in backends() {
+                for algorithm in ALGORITHMS {
+                    let key = sealer.generate_secretbox_key();
+                    let nonce = opener.generate_nonce();
+                    let sealed = sealer.secretbox_seal(algorithm, INPUT, &nonce, &key, None).unwrap();
+                    let opened = opener.secretbox_open(algorithm, &sealed, &nonce, &key, None);
+                    assert_eq!(opened.unwrap(), INPUT, "{} -> {} ({})", sealer.name(), opener.name(), algorithm.id());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_backend_secretbox_outputs_identical() {
+        let key = BASE64_STANDARD.decode(SB_KEY_B64).unwrap();
+        let nonce = [3u8; backend::SECRETBOX_NONCEBYTES];
+        for algorithm in ALGORITHMS {
+            let expected = Sodium.secretbox_seal(algorithm, INPUT, &nonce, &key, None).unwrap();
+            for backend in backends() {
+                assert_eq!(backend.secretbox_seal(algorithm, INPUT, &nonce, &key, None).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_backend_cross_sealedbox() {
+        for sealer in backends() {
+            for opener in backends() {
+                let (public_key, private_key) = opener.generate_keypair();
+                let sealed = sealer.sealedbox_seal(INPUT, &public_key, None).unwrap();
+                let opened = opener.sealedbox_open(&sealed, &private_key, &public_key, None);
+                assert_eq!(opened.unwrap(), INPUT, "{} -> {}", sealer.name(), opener.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_backend_opens_module_ciphertexts() {
+        let context = BTreeMap::new();
+        let key = BASE64_STANDARD.decode(SB_KEY_B64).unwrap();
+        let private_key = BASE64_STANDARD.decode(PRIV_KEY_B64).unwrap();
+        let public_key = BASE64_STANDARD.decode(PUB_KEY_B64).unwrap();
+        let input = std::str::from_utf8(INPUT).unwrap();
+        let (nonce_b64, data_b64) = secretbox::crypt(input, SB_KEY_B64, context.clone()).unwrap();
+        let sealed_b64 = sealedbox::crypt(input, PUB_KEY_B64, context.clone()).unwrap();
+        for backend in backends() {
+            let nonce = BASE64_STANDARD.decode(&nonce_b64).unwrap();
+            let data = BASE64_STANDARD.decode(&data_b64).unwrap();
+            let opened = backend.secretbox_open(Algorithm::XSalsa20Poly1305, &data, &nonce, &key, None);
+            assert_eq!(opened.unwrap(), INPUT);
+
+            let sealed = BASE64_STANDARD.decode(&sealed_b64).unwrap();
+            assert_eq!(backend.sealedbox_open(&sealed, &private_key, &public_key, None).unwrap(), INPUT);
+        }
+    }
+
+    #[test]
+    fn test_backend_errors() {
+        let key = BASE64_STANDARD.decode(SB_KEY_B64).unwrap();
+        let private_key = BASE64_STANDARD.decode(PRIV_KEY_B64).unwrap();
+        let public_key = BASE64_STANDARD.decode(PUB_KEY_B64).unwrap();
+        let nonce = [3u8; backend::SECRETBOX_NONCEBYTES];
+        let algorithm = Algorithm::XSalsa20Poly1305;
+        // Each failure of a backend, as its class, code and details, which must be the same for every backend.
+        let failures = |backend: &dyn Backend| {
+            let mut sealed = backend.secretbox_seal(algorithm, INPUT, &nonce, &key, None).unwrap();
+            sealed[0] ^= 1;
+            let sealed_box = backend.sealedbox_seal(INPUT, &public_key, None).unwrap();
+            [
+                backend.secretbox_seal(algorithm, INPUT, &nonce, &key[..16], None).unwrap_err(),
+                backend.secretbox_seal(algorithm, INPUT, &nonce[..8], &key, None).unwrap_err(),
+                backend.secretbox_open(algorithm, &sealed, &nonce, &key, None).unwrap_err(),
+                backend.sealedbox_seal(INPUT, &public_key[..16], None).unwrap_err(),
+                // Any 32 bytes are a private key, but not the one of the public key.
+                backend.sealedbox_open(&sealed_box, &key, &public_key, None).unwrap_err(),
+                backend.sealedbox_open(&sealed_box[..10], &private_key, &public_key, None).unwrap_err(),
+                backend.sealedbox_open(&sealed_box, &private_key[..16], &public_key, None).unwrap_err(),
+            ]
+            .map(|err| (err.class().to_string(), err.code(), err.details_ref().clone()))
+        };
+        let expected = failures(&Sodium);
+        assert!(expected[4].2.contains_key("key_fingerprint"));
+        for backend in backends() {
+            assert_eq!(failures(backend.as_ref()), expected, "{}", backend.name());
+        }
+    }
+}
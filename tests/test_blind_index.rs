@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::blind_index::{encrypt_with_indexes, exact, Algorithm, BlindIndex};
+    use cdumay_sodium::secretbox;
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const INDEX_KEY_B64: &str = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+
+    #[test]
+    fn test_blind_index_vectors() {
+        let context = BTreeMap::new();
+        let blake2b = BlindIndex::new("email", INDEX_KEY_B64, 256, context.clone()).unwrap();
+        assert_eq!(blake2b.compute("alice@example.com"), "8fb7782ff722ded87bbe7ebaf2920a253581821e0888b912eaed495bd078e69f");
+        let hmac = BlindIndex::new("email", INDEX_KEY_B64, 256, context).unwrap().with_algorithm(Algorithm::HmacSha256);
+        assert_eq!(hmac.compute("alice@example.com"), "8e1320265f3814a525ce32f1a17455fc80ee39ed4a535e4888d1e5266db16f72");
+    }
+
+    #[test]
+    fn test_blind_index_truncation() {
+        let context = BTreeMap::new();
+        assert_eq!(BlindIndex::new("email", INDEX_KEY_B64, 32, context.clone()).unwrap().compute("alice@example.com"), "8fb7782f");
+        assert_eq!(BlindIndex::new("email", INDEX_KEY_B64, 12, context.clone()).unwrap().compute("alice@example.com"), "8fb0");
+        assert_eq!(BlindIndex::new("email", INDEX_KEY_B64, 1, context).unwrap().compute("alice@example.com"), "80");
+    }
+
+    #[test]
+    fn test_blind_index_normalization() {
+        let context = BTreeMap::new();
+        let index = BlindIndex::new("email", INDEX_KEY_B64, 64, context.clone()).unwrap();
+        assert_eq!(index.compute("  Alice@Example.COM "), index.compute("alice@example.com"));
+        let index = index.with_normalizer(exact);
+        assert_ne!(index.compute("Alice@Example.com"), index.compute("alice@example.com"));
+    }
+
+    #[test]
+    fn test_blind_index_name_separates_fields() {
+        let context = BTreeMap::new();
+        let email = BlindIndex::new("email", INDEX_KEY_B64, 64, context.clone()).unwrap();
+        let username = BlindIndex::new("username", INDEX_KEY_B64, 64, context).unwrap();
+        assert_ne!(email.compute("alice"), username.compute("alice"));
+    }
+
+    #[test]
+    fn test_blind_index_invalid_configuration() {
+        let context = BTreeMap::new();
+        assert!(BlindIndex::new("email", INDEX_KEY_B64, 0, context.clone()).is_err());
+        assert!(BlindIndex::new("email", INDEX_KEY_B64, 257, context.clone()).is_err());
+        assert!(BlindIndex::new("email", "AAAA", 32, context.clone()).is_err());
+        assert!(BlindIndex::new("email", "not base64!", 32, context).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_with_indexes() {
+        let context = BTreeMap::new();
+        let full = BlindIndex::new("email", INDEX_KEY_B64, 256, context.clone()).unwrap();
+        let short = BlindIndex::new("email_prefix", INDEX_KEY_B64, 8, context.clone()).unwrap();
+        let first = encrypt_with_indexes("alice@example.com", SB_KEY_B64, &[&full, &short], context.clone()).unwrap();
+        let second = encrypt_with_indexes("ALICE@example.com", SB_KEY_B64, &[&full, &short], context.clone()).unwrap();
+
+        assert_ne!(first.ciphertext, second.ciphertext);
+        assert_eq!(first.indexes, second.indexes);
+        assert_eq!(first.indexes["email"], full.compute("alice@example.com"));
+        assert_eq!(first.indexes["email_prefix"].len(), 2);
+        assert_eq!(secretbox::decrypt(&second.ciphertext, SB_KEY_B64, &second.nonce, context).unwrap(), "ALICE@example.com");
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(all(test, feature = "capi"))]
+mod test {
+    use cdumay_sodium::envelope::{self, Envelope, SealedBoxKek, SecretBoxKek};
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::process::Command;
+
+    const KEK_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+
+    #[test]
+    fn test_capi_c_harness() {
+        let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+        if Command::new(&cc).arg("--version").output().is_err() {
+            eprintln!("skipping: no C compiler ({})", cc);
+            return;
+        }
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi");
+        let status = Command::new(env!("CARGO"))
+            .args(["rustc", "--lib", "--features", "capi", "--crate-type", "cdylib", "--target-dir"])
+            .arg(&target_dir)
+            .current_dir(manifest_dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "cdylib build failed");
+
+        let lib_dir = target_dir.join("debug");
+        let harness = target_dir.join("harness");
+        let status = Command::new(&cc)
+            .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-I"])
+            .arg(manifest_dir.join("include"))
+            .arg(manifest_dir.join("tests/capi/harness.c"))
+            .arg("-L")
+            .arg(&lib_dir)
+            .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+            .args(["-lcdumay_sodium", "-o"])
+            .arg(&harness)
+            .status()
+            .unwrap();
+        assert!(status.success(), "harness compilation failed");
+
+        let context = BTreeMap::new();
+        let secretbox_kek = SecretBoxKek::new("kek", KEK_B64, context.clone()).unwrap();
+        let sealedbox_kek = SealedBoxKek::new("kek", PUB_KEY_B64, context.clone()).unwrap().with_private_key(PRIV_KEY_B64, context.clone()).unwrap();
+        let output = Command::new(&harness)
+            .arg(envelope::encrypt("written by rust", &secretbox_kek, context.clone()).unwrap().to_string())
+            .arg(envelope::encrypt("written by rust", &sealedbox_kek, context.clone()).unwrap().to_string())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "harness failed: {}", String::from_utf8_lossy(&output.stderr));
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let sealed = Envelope::parse(lines[0], context.clone()).unwrap();
+        assert_eq!(envelope::decrypt(&sealed, &secretbox_kek, context.clone()).unwrap(), "written by c");
+        let sealed = Envelope::parse(lines[1], context.clone()).unwrap();
+        assert_eq!(envelope::decrypt(&sealed, &sealedbox_kek, context).unwrap(), "written by c");
+    }
+}
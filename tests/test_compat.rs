@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod test {
+    // Ciphertexts produced by releases built on sodiumoxide; they must keep decrypting, and deterministic outputs must not change.
+    use cdumay_sodium::encrypted::{Encrypted, Key};
+    use cdumay_sodium::envelope::{self, Envelope, SealedBoxKek, SecretBoxKek};
+    use cdumay_sodium::keyring::Keyring;
+    use cdumay_sodium::secretbox::{self, Algorithm};
+    use cdumay_sodium::sealedbox;
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+    const PLAINTEXT: &str = "legacy plaintext";
+
+    #[test]
+    fn test_compat_secretbox() {
+        let context = BTreeMap::new();
+        let nonce_b64 = "C0qwrmXiNifgeZXP3BxwvVDvSD6d1OC8";
+        let data_b64 = "MTnNCKC8JRV0gL9bIBU4MpCDX85YC3PYZGSuTO98YNQ=";
+        assert_eq!(secretbox::decrypt(data_b64, SB_KEY_B64, nonce_b64, context).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_compat_secretbox_detached() {
+        let context = BTreeMap::new();
+        let nonce_b64 = "DTbv4X4HHi0OR7VnuVJcuEhsH3PPqdRf";
+        let data_b64 = "NXw90wmuap0DF4eRcJwwEQ==";
+        let tag_b64 = "XfnQWxmmZ8mXG++Ya1XTGw==";
+        assert_eq!(secretbox::decrypt_detached(data_b64, tag_b64, SB_KEY_B64, nonce_b64, context).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_compat_secretbox_xchacha20poly1305() {
+        let context = BTreeMap::new();
+        let nonce_b64 = "MPhgi2X/w2ZEG4EDF0vqvSAanzNnqNSP";
+        let data_b64 = "XBPwuEdFo3QMwhY6qOWW2kEhnTUjxdxaFhBv13FKEqs=";
+        let plaintext = secretbox::decrypt_with_algorithm(data_b64, SB_KEY_B64, nonce_b64, Algorithm::XChaCha20Poly1305, context);
+        assert_eq!(plaintext.unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_compat_secretbox_outputs_unchanged() {
+        let context = BTreeMap::new();
+        let nonce_b64 = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYX";
+        let data_b64 = "FtFVCD/XwO0povlHaEHoTQTaXKi/prJSEmOt2qdtZYM=";
+        assert_eq!(secretbox::crypt_with_nonce(PLAINTEXT, SB_KEY_B64, nonce_b64, context.clone()).unwrap(), data_b64);
+
+        let deterministic_b64 = "IE6c5y9EjlOhLiqEzpqXn367zomsMr/KxYM+ohdIOA2oUnItWEndFrxNFsUWmjH89G1wDwFlaJg=";
+        assert_eq!(secretbox::crypt_deterministic(PLAINTEXT, "users.email", SB_KEY_B64, context.clone()).unwrap(), deterministic_b64);
+        assert_eq!(secretbox::decrypt_deterministic(deterministic_b64, "users.email", SB_KEY_B64, context).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_compat_sealedbox() {
+        let context = BTreeMap::new();
+        let data_b64 = "4gn2a/9lN8cZwpxS9G/wJlnJHOUF7OtHcoH4HGMXly7QYBZGjMgZ1bJxXqf0idSbZJqwRXx5U+jr1cHszbfOrg==";
+        assert_eq!(sealedbox::decrypt(data_b64, PRIV_KEY_B64, PUB_KEY_B64, context.clone()).unwrap(), PLAINTEXT);
+
+        let multi_b64 = "AQAB3Blqr5IrxnEGmNrgo/5yrlPHSg7pwoM0lbCLPGrau01VzLVp/7Bv3G8QeU6y1N9PQYVC0aO/aHCTtyushvkuhdb5IoK2mbnJA5OYoUJwq/27CgDzBwWf1L6RWnmEkvYFFHTnmPyPupMIS2W7ntEMli13xotnebQnY3vGuBVfZFc6W+Ji1msSaw==";
+        assert_eq!(sealedbox::decrypt_multi(multi_b64, PRIV_KEY_B64, PUB_KEY_B64, context).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_compat_keyring() {
+        let context = BTreeMap::new();
+        let keyring = Keyring::new("v1", SB_KEY_B64, context.clone()).unwrap();
+        let nonce_b64 = "mC5DG2tXG8ezoiJzhVX23pvbIx05g8WR";
+        let data = "v1:q/Qx4frZLc5nTO5Cj5G5gDTroWde1i6Uh0VeL7zper0=";
+        assert_eq!(keyring.decrypt(data, nonce_b64, context).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_compat_envelope() {
+        let context = BTreeMap::new();
+        let kek = SecretBoxKek::new("kek", SB_KEY_B64, context.clone()).unwrap();
+        for encoded in [
+            "v1.kek.3M+OU8QiYvCbpyECiEMCK7P4QwU7WFZMoFM85088P6oBHtgMv3br1vtv1ryr9rQd/Mvn51ehzRXDElI7CeCTOh/OJPyG8oNJ.b4fzIJu4LRRjmg+FKBtnEJyZf647CnNl.aC5va6hPZUpNVhdTdFRHjGVuNNcS3z75mvLLXIO57CY=",
+            "v2.xchacha20poly1305.kek.EGF6yW0OfIM+LYIjBN0AXq46j74gHpLPQCv5Ev1/i96mgtjyWalN4xagugeLULVha9YB+Zz9eJCLGz/17k/s+umbJ51FbAGI.jSZaam0VXbivuWCNnboxePmSotSdtoOP.YhD6vMdxgN/LJP8Z3vt2nFHZGMUmbn9bgMuscpgGkNs=",
+        ] {
+            let envelope = Envelope::parse(encoded, context.clone()).unwrap();
+            assert_eq!(envelope::decrypt(&envelope, &kek, context.clone()).unwrap(), PLAINTEXT);
+        }
+
+        let kek = SealedBoxKek::new("pub", PUB_KEY_B64, context.clone()).unwrap().with_private_key(PRIV_KEY_B64, context.clone()).unwrap();
+        let encoded = "v1.pub.B2piZdsbcx1HJxlvRLI+Q1gajRCZgmlpcyLOyiHnESWRNuj2ItMmMWt1lKMfcl/aYfu6lQPj75jSiuJzzsqIthKRWJBOfmuHH6e0tZTfYY0=.9ZPvIPNxHUDiMP/8K1TVKUDmwLvHwtbq.ZoLyLAn8OxqZ/cfOtmAjXXULvtCxlEqZoa6CgAm46fk=";
+        let envelope = Envelope::parse(encoded, context.clone()).unwrap();
+        assert_eq!(envelope::decrypt(&envelope, &kek, context).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_compat_encrypted() {
+        let context = BTreeMap::new();
+        let encrypted = Encrypted::<String>::parse("sb:json:QOXKXZZwGsxhm1gsrGSbF26Y8EyXQRdm9GhQ06GxouGNkJgU54yb8bTbuzFCJ14/aKq43vWZ1/gEtg==", context.clone()).unwrap();
+        assert_eq!(encrypted.open(Key::SecretBox(SB_KEY_B64), context.clone()).unwrap(), PLAINTEXT);
+
+        let keypair = Key::SealedBoxKeypair { private_key_b64: PRIV_KEY_B64, public_key_b64: PUB_KEY_B64 };
+        let encoded = "seal:json:tF/dL9MWNL2BH8uKyj0ns64EfD1L3yb7oRyOl6uSOSDTGsdmm9VdhaJLhC6GCCVDzSO6m0O4HZ69rZ8gaTiqwkL5";
+        let encrypted = Encrypted::<String>::parse(encoded, context.clone()).unwrap();
+        assert_eq!(encrypted.open(keypair, context).unwrap(), PLAINTEXT);
+    }
+}
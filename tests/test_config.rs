@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::config::{decrypt_document, encrypt_document, is_marker, ConfigKey};
+    use cdumay_sodium::keyring::Keyring;
+    use serde_value::Value;
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const NEW_KEY_B64: &str = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+
+    fn document() -> serde_json::Value {
+        serde_json::json!({
+            "database": {"host": "db.local", "port": 5432, "password_secret": "hunter2"},
+            "tls_secret": {"cert": "-----BEGIN CERTIFICATE-----", "verify": true, "ttl": -1, "ratio": 0.5},
+            "tokens": [{"name": "ci", "token_secret": "abc"}]
+        })
+    }
+
+    fn to_value(json: serde_json::Value) -> Value {
+        serde_value::to_value(json).unwrap()
+    }
+
+    fn to_json(value: Value) -> serde_json::Value {
+        value.deserialize_into().unwrap()
+    }
+
+    fn is_secret(name: &str) -> bool {
+        name.ends_with("_secret")
+    }
+
+    fn keyring(context: BTreeMap<String, Value>) -> Keyring {
+        Keyring::new("2024", SB_KEY_B64, context).unwrap()
+    }
+
+    #[test]
+    fn test_config_keyring_round_trip() {
+        let context = BTreeMap::new();
+        let keyring = keyring(context.clone());
+        let key = ConfigKey::Keyring(&keyring);
+        let encrypted = to_json(encrypt_document(to_value(document()), is_secret, key, context.clone()).unwrap());
+
+        assert_eq!(encrypted["database"]["host"], "db.local");
+        assert_eq!(encrypted["database"]["port"], 5432);
+        let password = encrypted["database"]["password_secret"].as_str().unwrap();
+        assert!(password.starts_with("ENC[secretbox,key:2024,nonce:"));
+        assert!(password.ends_with(",type:str]"));
+        assert!(encrypted["tls_secret"]["verify"].as_str().unwrap().ends_with(",type:bool]"));
+        assert!(encrypted["tls_secret"]["ttl"].as_str().unwrap().ends_with(",type:int]"));
+        assert!(encrypted["tls_secret"]["ratio"].as_str().unwrap().ends_with(",type:float]"));
+        assert!(is_marker(encrypted["tokens"][0]["token_secret"].as_str().unwrap()));
+        assert_eq!(encrypted["tokens"][0]["name"], "ci");
+
+        let decrypted = decrypt_document(to_value(encrypted), &[key], context).unwrap();
+        assert_eq!(to_json(decrypted), document());
+    }
+
+    #[test]
+    fn test_config_encrypt_is_idempotent() {
+        let context = BTreeMap::new();
+        let keyring = keyring(context.clone());
+        let key = ConfigKey::Keyring(&keyring);
+        let encrypted = encrypt_document(to_value(document()), is_secret, key, context.clone()).unwrap();
+        let twice = encrypt_document(encrypted.clone(), is_secret, key, context).unwrap();
+        assert_eq!(twice, encrypted);
+    }
+
+    #[test]
+    fn test_config_sealedbox_round_trip() {
+        let context = BTreeMap::new();
+        let encrypted = encrypt_document(to_value(document()), is_secret, ConfigKey::SealedBoxPublic(PUB_KEY_B64), context.clone()).unwrap();
+        let password = to_json(encrypted.clone())["database"]["password_secret"].as_str().unwrap().to_string();
+        assert!(password.starts_with("ENC[sealedbox,data:"));
+
+        let err = decrypt_document(encrypted.clone(), &[ConfigKey::SealedBoxPublic(PUB_KEY_B64)], context.clone()).unwrap_err();
+        assert_eq!(err.details_ref().get("path"), Some(&Value::String("/database/password_secret".to_string())));
+
+        let keypair = ConfigKey::SealedBoxKeypair { private_key_b64: PRIV_KEY_B64, public_key_b64: PUB_KEY_B64 };
+        let decrypted = decrypt_document(encrypted, &[keypair], context).unwrap();
+        assert_eq!(to_json(decrypted), document());
+    }
+
+    #[test]
+    fn test_config_rotated_keyring() {
+        let context = BTreeMap::new();
+        let old = keyring(context.clone());
+        let encrypted = encrypt_document(to_value(document()), is_secret, ConfigKey::Keyring(&old), context.clone()).unwrap();
+
+        let mut rotated = Keyring::new("2025", NEW_KEY_B64, context.clone()).unwrap();
+        assert!(decrypt_document(encrypted.clone(), &[ConfigKey::Keyring(&rotated)], context.clone()).is_err());
+        rotated.add_key("2024", SB_KEY_B64, context.clone()).unwrap();
+        let decrypted = decrypt_document(encrypted, &[ConfigKey::Keyring(&rotated)], context).unwrap();
+        assert_eq!(to_json(decrypted), document());
+    }
+
+    #[test]
+    fn test_config_markers_are_bound_to_their_key() {
+        let context = BTreeMap::new();
+        let keyring = keyring(context.clone());
+        let sealedbox = ConfigKey::SealedBoxKeypair { private_key_b64: PRIV_KEY_B64, public_key_b64: PUB_KEY_B64 };
+        for key in [ConfigKey::Keyring(&keyring), sealedbox] {
+            let mut encrypted = to_json(encrypt_document(to_value(document()), is_secret, key, context.clone()).unwrap());
+            let password = encrypted["database"]["password_secret"].clone();
+            encrypted["database"]["password_secret"] = encrypted["tokens"][0]["token_secret"].clone();
+            encrypted["tokens"][0]["token_secret"] = password;
+            let err = decrypt_document(to_value(encrypted.clone()), &[key], context.clone()).unwrap_err();
+            assert!(err.class().ends_with("::InvalidEnvelope"));
+            assert!(!format!("{:?}", err).contains("hunter2"));
+
+            // A marker given another type is rejected too.
+            let retyped = encrypted["tls_secret"]["ratio"].as_str().unwrap().replace(",type:float]", ",type:str]");
+            let document = to_value(serde_json::json!({"tls_secret": {"ratio": retyped}}));
+            assert!(decrypt_document(document, &[key], context.clone()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_config_malformed_marker() {
+        let context = BTreeMap::new();
+        let keyring = keyring(context.clone());
+        let key = ConfigKey::Keyring(&keyring);
+        for marker in ["ENC[aes,data:AAAA,type:str]", "ENC[secretbox,key:2024,data:AAAA,type:str]", "ENC[secretbox,garbage]"] {
+            let document = to_value(serde_json::json!({"api_secret": marker}));
+            let err = decrypt_document(document, &[key], context.clone()).unwrap_err();
+            assert_eq!(err.details_ref().get("path"), Some(&Value::String("/api_secret".to_string())));
+        }
+    }
+
+    #[test]
+    fn test_config_error_hides_value() {
+        let context = BTreeMap::new();
+        let encrypted = encrypt_document(to_value(document()), is_secret, ConfigKey::SealedBoxPublic(PUB_KEY_B64), context.clone()).unwrap();
+        let keyring = keyring(context.clone());
+        let err = decrypt_document(encrypted, &[ConfigKey::Keyring(&keyring)], context).unwrap_err();
+        assert!(!format!("{:?}", err).contains("hunter2"));
+        assert!(!format!("{:?}", err).contains("ENC["));
+    }
+}
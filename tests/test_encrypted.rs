@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::encrypted::{Algorithm, Encrypted, Format, Key};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const NEW_KEY_B64: &str = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+    const KEYPAIR: Key<'static> = Key::SealedBoxKeypair { private_key_b64: PRIV_KEY_B64, public_key_b64: PUB_KEY_B64 };
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        street: String,
+        zip: u32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Profile {
+        name: String,
+        addresses: Vec<Address>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Document {
+        id: u64,
+        profile: Encrypted<Profile>,
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            name: "alice".to_string(),
+            addresses: vec![Address { street: "1 main st".to_string(), zip: 75001 }],
+        }
+    }
+
+    #[test]
+    fn test_encrypted_secretbox_json() {
+        let context = BTreeMap::new();
+        let sealed = Encrypted::seal(&profile(), Key::SecretBox(SB_KEY_B64), context.clone()).unwrap();
+        assert_eq!(sealed.algorithm(), Algorithm::SecretBox);
+        assert_eq!(sealed.format(), Format::Json);
+        assert_eq!(sealed.open(Key::SecretBox(SB_KEY_B64), context).unwrap(), profile());
+    }
+
+    #[test]
+    fn test_encrypted_sealedbox_cbor() {
+        let context = BTreeMap::new();
+        let sealed = Encrypted::seal_as(&profile(), Format::Cbor, Key::SealedBoxPublic(PUB_KEY_B64), context.clone()).unwrap();
+        assert!(sealed.to_string().starts_with("seal:cbor:"));
+        assert!(sealed.open(Key::SealedBoxPublic(PUB_KEY_B64), context.clone()).is_err());
+        assert_eq!(sealed.open(KEYPAIR, context).unwrap(), profile());
+    }
+
+    #[test]
+    fn test_encrypted_nested_in_document() {
+        let context = BTreeMap::new();
+        let document = Document { id: 7, profile: Encrypted::seal(&profile(), Key::SecretBox(SB_KEY_B64), context.clone()).unwrap() };
+        let json = serde_json::to_string(&document).unwrap();
+        assert!(json.contains(r#""profile":"sb:json:"#));
+        assert!(!json.contains("alice"));
+
+        let decoded: Document = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.id, 7);
+        assert_eq!(decoded.profile, document.profile);
+        assert_eq!(decoded.profile.open(Key::SecretBox(SB_KEY_B64), context).unwrap(), profile());
+    }
+
+    #[test]
+    fn test_encrypted_wrong_key() {
+        let context = BTreeMap::new();
+        let sealed = Encrypted::seal(&profile(), Key::SecretBox(SB_KEY_B64), context.clone()).unwrap();
+        assert!(sealed.open(Key::SecretBox(NEW_KEY_B64), context.clone()).is_err());
+        assert!(sealed.open(KEYPAIR, context.clone()).is_err());
+        assert!(Encrypted::seal(&profile(), Key::SecretBox("AAAA"), context).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_wrong_type() {
+        let context = BTreeMap::new();
+        let sealed = Encrypted::seal(&"just a string".to_string(), Key::SecretBox(SB_KEY_B64), context.clone()).unwrap();
+        let retyped: Encrypted<Profile> = Encrypted::parse(&sealed.to_string(), context.clone()).unwrap();
+        assert!(retyped.open(Key::SecretBox(SB_KEY_B64), context).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_errors_hold_no_plaintext() {
+        for format in [Format::Json, Format::Cbor] {
+            let sealed = Encrypted::seal_as(&"just a string".to_string(), format, Key::SecretBox(SB_KEY_B64), None).unwrap();
+            let retyped: Encrypted<Profile> = Encrypted::parse(&sealed.to_string(), None).unwrap();
+            let err = retyped.open(Key::SecretBox(SB_KEY_B64), None).unwrap_err();
+            assert!(!err.to_string().contains("just a string"), "{}", err);
+            assert!(!format!("{:?}", err.details_ref()).contains("just a string"));
+            assert!(err.details_ref().contains_key("category"));
+            assert!(err.details_ref().contains_key("format"));
+        }
+    }
+
+    #[test]
+    fn test_encrypted_parse_invalid() {
+        let context = BTreeMap::new();
+        assert!(Encrypted::<Profile>::parse("xx:json:AAAA", context.clone()).is_err());
+        assert!(Encrypted::<Profile>::parse("sb:yaml:AAAA", context.clone()).is_err());
+        assert!(Encrypted::<Profile>::parse("sb:json", context.clone()).is_err());
+        assert!(Encrypted::<Profile>::parse("sb:json:not-valid-base64!!!", context).is_err());
+        assert!(serde_json::from_str::<Document>(r#"{"id":1,"profile":"plain"}"#).is_err());
+    }
+}
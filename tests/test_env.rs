@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::env::{encrypt_sealedbox, encrypt_secretbox, get_decrypted, EnvKeys, KeySource};
+    use serde_value::Value;
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+
+    fn set_var(name: &str, value: &str) {
+        unsafe { std::env::set_var(name, value) }
+    }
+
+    fn details(err: &cdumay_core::Error, key: &str) -> Option<Value> {
+        err.details_ref().get(key).cloned()
+    }
+
+    #[test]
+    fn test_env_plain_value_passes_through() {
+        set_var("TEST_ENV_PLAIN", "postgres://localhost");
+        assert_eq!(get_decrypted("TEST_ENV_PLAIN", BTreeMap::new()).unwrap(), "postgres://localhost");
+    }
+
+    #[test]
+    fn test_env_secretbox_from_env_key() {
+        let context = BTreeMap::new();
+        set_var("TEST_ENV_SB_KEY", SB_KEY_B64);
+        set_var("TEST_ENV_SB_VALUE", &encrypt_secretbox("hunter2", SB_KEY_B64, context.clone()).unwrap());
+        let keys = EnvKeys::default().with_secretbox_key(KeySource::Env("TEST_ENV_SB_KEY".to_string()));
+        assert_eq!(keys.get_decrypted("TEST_ENV_SB_VALUE", context).unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn test_env_sealedbox_from_key_files() {
+        let context = BTreeMap::new();
+        let dir = std::env::temp_dir();
+        let private_path = dir.join(format!("cdumay_sodium_test_{}.key", std::process::id()));
+        std::fs::write(&private_path, format!("{}\n", PRIV_KEY_B64)).unwrap();
+        set_var("TEST_ENV_SEAL_PUB_FILE", dir.join("missing").to_str().unwrap());
+        set_var("TEST_ENV_SEAL_PUB", PUB_KEY_B64);
+        set_var("TEST_ENV_SEAL_VALUE", &encrypt_sealedbox("hunter2", PUB_KEY_B64, context.clone()).unwrap());
+
+        let keys = EnvKeys::default().with_sealedbox_keypair(KeySource::File(private_path.clone()), KeySource::Env("TEST_ENV_SEAL_PUB".to_string()));
+        let decrypted = keys.get_decrypted("TEST_ENV_SEAL_VALUE", context);
+        std::fs::remove_file(private_path).unwrap();
+        assert_eq!(decrypted.unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn test_env_key_from_file_variable() {
+        let context = BTreeMap::new();
+        let path = std::env::temp_dir().join(format!("cdumay_sodium_test_{}.sb", std::process::id()));
+        std::fs::write(&path, format!("{}\n", SB_KEY_B64)).unwrap();
+        set_var("TEST_ENV_FILE_KEY_FILE", path.to_str().unwrap());
+        set_var("TEST_ENV_FILE_VALUE", &encrypt_secretbox("hunter2", SB_KEY_B64, context.clone()).unwrap());
+        let source = KeySource::Env("TEST_ENV_FILE_KEY".to_string());
+        assert_eq!(&*source.load(context.clone()).unwrap(), SB_KEY_B64.as_bytes());
+        let keys = EnvKeys::default().with_secretbox_key(source);
+        let decrypted = keys.get_decrypted("TEST_ENV_FILE_VALUE", context);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(decrypted.unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn test_env_missing_variable() {
+        let err = get_decrypted("TEST_ENV_NOT_SET", BTreeMap::new()).unwrap_err();
+        assert_eq!(details(&err, "variable"), Some(Value::String("TEST_ENV_NOT_SET".to_string())));
+    }
+
+    #[test]
+    fn test_env_missing_key() {
+        let context = BTreeMap::new();
+        set_var("TEST_ENV_NO_KEY_VALUE", &encrypt_secretbox("hunter2", SB_KEY_B64, context.clone()).unwrap());
+        let keys = EnvKeys::default().with_secretbox_key(KeySource::Env("TEST_ENV_NO_KEY".to_string()));
+        let err = keys.get_decrypted("TEST_ENV_NO_KEY_VALUE", context).unwrap_err();
+        assert_eq!(details(&err, "variable"), Some(Value::String("TEST_ENV_NO_KEY_VALUE".to_string())));
+        assert_eq!(details(&err, "key_variable"), Some(Value::String("TEST_ENV_NO_KEY".to_string())));
+
+        let keys = EnvKeys::default().with_secretbox_key(KeySource::File("/nonexistent/sodium.key".into()));
+        let err = keys.get_decrypted("TEST_ENV_NO_KEY_VALUE", BTreeMap::new()).unwrap_err();
+        assert_eq!(details(&err, "path"), Some(Value::String("/nonexistent/sodium.key".to_string())));
+    }
+
+    #[test]
+    fn test_env_errors_never_contain_value() {
+        set_var("TEST_ENV_BAD_KEY", "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=");
+        let keys = EnvKeys::default().with_secretbox_key(KeySource::Env("TEST_ENV_BAD_KEY".to_string()));
+        for value in ["sodium:sb:malformed", &encrypt_secretbox("hunter2", SB_KEY_B64, BTreeMap::new()).unwrap()] {
+            set_var("TEST_ENV_BAD_VALUE", value);
+            let err = keys.get_decrypted("TEST_ENV_BAD_VALUE", BTreeMap::new()).unwrap_err();
+            assert_eq!(details(&err, "variable"), Some(Value::String("TEST_ENV_BAD_VALUE".to_string())));
+            assert!(!format!("{:?}", err).contains(value));
+        }
+    }
+}
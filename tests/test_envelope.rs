@@ -0,0 +1,189 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::envelope::{self, Envelope, KeyEncryptionProvider, SealedBoxKek, SecretBoxKek};
+    use cdumay_sodium::secretbox::Algorithm;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    const KEK_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+    const INPUT: &str = r#"{"hello": "world"}"#;
+
+    /// Stand-in for a remote KMS: wrapped keys are opaque handles resolved server-side.
+    struct FakeKms {
+        keys: RefCell<Vec<Vec<u8>>>,
+    }
+
+    impl KeyEncryptionProvider for FakeKms {
+        fn kek_id(&self) -> &str {
+            "arn:fake:kms:key/1"
+        }
+
+        fn wrap_key(&self, data_key: &[u8], _context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+            let mut keys = self.keys.borrow_mut();
+            keys.push(data_key.to_vec());
+            Ok(((keys.len() - 1) as u32).to_be_bytes().to_vec())
+        }
+
+        fn unwrap_key(&self, wrapped_key: &[u8], _context: BTreeMap<String, serde_value::Value>) -> cdumay_core::Result<Vec<u8>> {
+            let handle = u32::from_be_bytes(wrapped_key.try_into().unwrap()) as usize;
+            Ok(self.keys.borrow()[handle].clone())
+        }
+    }
+
+    #[test]
+    fn test_envelope_secretbox_kek() {
+        let context = BTreeMap::new();
+        let kek = SecretBoxKek::new("kek-1", KEK_B64, context.clone()).unwrap();
+        let sealed = envelope::encrypt(INPUT, &kek, context.clone()).unwrap();
+        assert_eq!(sealed.kek_id, "kek-1");
+        let result = envelope::decrypt(&sealed, &kek, context);
+        assert_eq!(INPUT, result.unwrap());
+    }
+
+    #[test]
+    fn test_envelope_sealedbox_kek() {
+        let context = BTreeMap::new();
+        let writer = SealedBoxKek::new("ops.seal.1", PUB_KEY_B64, context.clone()).unwrap();
+        let sealed = envelope::encrypt(INPUT, &writer, context.clone()).unwrap();
+        // A wrap-only provider cannot decrypt.
+        assert!(envelope::decrypt(&sealed, &writer, context.clone()).is_err());
+
+        let reader = SealedBoxKek::new("ops.seal.1", PUB_KEY_B64, context.clone())
+            .unwrap()
+            .with_private_key(PRIV_KEY_B64, context.clone())
+            .unwrap();
+        let result = envelope::decrypt(&sealed, &reader, context.clone());
+        assert_eq!(INPUT, result.unwrap());
+
+        // Any 32 bytes are a private key, but not the one of PUB_KEY_B64.
+        let err = SealedBoxKek::new("ops.seal.1", PUB_KEY_B64, context.clone()).unwrap().with_private_key(KEK_B64, context.clone()).err();
+        assert!(err.unwrap().class().starts_with("Client::InvalidConfiguration::"));
+        let err = SealedBoxKek::new("ops.seal.1", PUB_KEY_B64, context.clone()).unwrap().with_private_key("AAAA", context).err();
+        assert!(err.unwrap().class().ends_with("::InvalidPrivateKeyLength"));
+    }
+
+    #[test]
+    fn test_envelope_custom_provider() {
+        let context = BTreeMap::new();
+        let kms = FakeKms { keys: RefCell::new(Vec::new()) };
+        let sealed = envelope::encrypt(INPUT, &kms, context.clone()).unwrap();
+        assert_eq!(sealed.kek_id, "arn:fake:kms:key/1");
+        let result = envelope::decrypt(&sealed, &kms, context);
+        assert_eq!(INPUT, result.unwrap());
+    }
+
+    #[test]
+    fn test_envelope_string_round_trip() {
+        let context = BTreeMap::new();
+        let kek = SecretBoxKek::new("tenant.a.kek", KEK_B64, context.clone()).unwrap();
+        let sealed = envelope::encrypt(INPUT, &kek, context.clone()).unwrap();
+        let stored = sealed.to_string();
+        assert!(stored.starts_with(&format!("v3.xsalsa20poly1305.{}.tenant.a.kek.", kek.fingerprint())));
+        let parsed = Envelope::parse(&stored, context.clone()).unwrap();
+        assert_eq!(parsed, sealed);
+        assert_eq!(INPUT, envelope::decrypt(&parsed, &kek, context).unwrap());
+    }
+
+    #[test]
+    fn test_envelope_xchacha20poly1305() {
+        let context = BTreeMap::new();
+        let kek = SecretBoxKek::new("tenant.a.kek", KEK_B64, context.clone()).unwrap();
+        let sealed = envelope::encrypt_with_algorithm(INPUT, &kek, Algorithm::XChaCha20Poly1305, context.clone()).unwrap();
+        let stored = sealed.to_string();
+        assert!(stored.starts_with(&format!("v3.xchacha20poly1305.{}.tenant.a.kek.", kek.fingerprint())));
+        let parsed = Envelope::parse(&stored, context.clone()).unwrap();
+        assert_eq!(parsed, sealed);
+        assert_eq!(parsed.algorithm, Algorithm::XChaCha20Poly1305);
+        assert_eq!(INPUT, envelope::decrypt(&parsed, &kek, context.clone()).unwrap());
+
+        // A mixed store decrypts each envelope with its own algorithm.
+        let legacy = envelope::encrypt(INPUT, &kek, context.clone()).unwrap().to_string();
+        for stored in [stored, legacy] {
+            let parsed = Envelope::parse(&stored, context.clone()).unwrap();
+            assert_eq!(INPUT, envelope::decrypt(&parsed, &kek, context.clone()).unwrap());
+        }
+
+        let mut mislabeled = sealed;
+        mislabeled.algorithm = Algorithm::XSalsa20Poly1305;
+        assert!(envelope::decrypt(&mislabeled, &kek, context).is_err());
+    }
+
+    #[test]
+    fn test_envelope_parse_invalid() {
+        let context = BTreeMap::new();
+        assert!(Envelope::parse("", context.clone()).is_err());
+        assert!(Envelope::parse("v1.a.b", context.clone()).is_err());
+        assert!(Envelope::parse("v2.kek.a.b.c", context.clone()).is_err());
+        assert!(Envelope::parse("v1kek.a.b.c", context.clone()).is_err());
+        assert!(Envelope::parse("v2.aes256gcm.kek.a.b.c", context.clone()).is_err());
+        assert!(Envelope::parse("v3.xsalsa20poly1305.kek.a.b.c", context.clone()).is_err());
+        assert!(Envelope::parse("v3.xsalsa20poly1305.0011223344556677", context).is_err());
+    }
+
+    #[test]
+    fn test_envelope_kek_mismatch() {
+        let context = BTreeMap::new();
+        let kek = SecretBoxKek::new("kek-1", KEK_B64, context.clone()).unwrap();
+        let other = SecretBoxKek::new("kek-2", KEK_B64, context.clone()).unwrap();
+        let sealed = envelope::encrypt(INPUT, &kek, context.clone()).unwrap();
+        assert!(envelope::decrypt(&sealed, &other, context).is_err());
+    }
+
+    #[test]
+    fn test_envelope_header_is_authenticated() {
+        let kek = SecretBoxKek::new("kek-1", KEK_B64, None).unwrap();
+        let relabeled_kek = SecretBoxKek::new("kek-2", KEK_B64, None).unwrap();
+        let sealed = envelope::encrypt(INPUT, &kek, None).unwrap();
+
+        let mut relabeled = sealed.clone();
+        relabeled.kek_id = "kek-2".to_string();
+        let err = envelope::decrypt(&relabeled, &relabeled_kek, None).unwrap_err();
+        assert!(err.class().ends_with("::AuthenticationFailed"));
+
+        let mut downgraded = sealed.clone();
+        downgraded.authenticated_header = false;
+        assert!(downgraded.to_string().starts_with("v1.kek-1."));
+        assert!(envelope::decrypt(&downgraded, &kek, None).is_err());
+
+        // Providers without a fingerprint write v3 envelopes with an empty fingerprint.
+        let kms = FakeKms { keys: RefCell::new(Vec::new()) };
+        let stored = envelope::encrypt(INPUT, &kms, None).unwrap().to_string();
+        assert!(stored.starts_with("v3.xsalsa20poly1305..arn:fake:kms:key/1."));
+        let parsed = Envelope::parse(&stored, None).unwrap();
+        assert_eq!(parsed.kek_fingerprint, None);
+        assert_eq!(INPUT, envelope::decrypt(&parsed, &kms, None).unwrap());
+        let mut changed = parsed;
+        changed.algorithm = Algorithm::XChaCha20Poly1305;
+        assert!(envelope::decrypt(&changed, &kms, None).is_err());
+    }
+
+    #[test]
+    fn test_envelope_wrong_kek_key() {
+        let context = BTreeMap::new();
+        let kek = SecretBoxKek::new("kek-1", KEK_B64, context.clone()).unwrap();
+        let impostor = SecretBoxKek::new("kek-1", "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=", context.clone()).unwrap();
+        let sealed = envelope::encrypt(INPUT, &kek, context.clone()).unwrap();
+        assert!(envelope::decrypt(&sealed, &impostor, context).is_err());
+    }
+
+    #[test]
+    fn test_envelope_tampered_ciphertext() {
+        let context = BTreeMap::new();
+        let kek = SecretBoxKek::new("kek-1", KEK_B64, context.clone()).unwrap();
+        let mut sealed = envelope::encrypt(INPUT, &kek, context.clone()).unwrap();
+        let other = envelope::encrypt("another message", &kek, context.clone()).unwrap();
+        sealed.ciphertext = other.ciphertext;
+        assert!(envelope::decrypt(&sealed, &kek, context).is_err());
+    }
+
+    #[test]
+    fn test_envelope_invalid_kek() {
+        let context = BTreeMap::new();
+        assert!(SecretBoxKek::new("kek-1", "not-valid-base64!!!", context.clone()).is_err());
+        assert!(SecretBoxKek::new("kek-1", "AAAA", context.clone()).is_err());
+        let err = SealedBoxKek::new("kek-1", "AAAA", context).err().unwrap();
+        assert!(err.class().ends_with("::InvalidPublicKeyLength"));
+    }
+}
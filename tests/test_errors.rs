@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::keyring::Keyring;
+    use cdumay_sodium::{sealedbox, secretbox};
+    use serde_value::Value;
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const NONCE_B64: &str = "HZGeSXQLJlFNpQgGyvYkXj+jAL9d/15J";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+
+    fn details(err: &cdumay_core::Error, key: &str) -> Option<Value> {
+        err.details_ref().get(key).cloned()
+    }
+
+    fn text(value: &str) -> Option<Value> {
+        Some(Value::String(value.to_string()))
+    }
+
+    #[test]
+    fn test_invalid_public_key_length() {
+        let err = sealedbox::crypt("data", "AAAA", BTreeMap::new()).unwrap_err();
+        assert!(err.class().ends_with("::InvalidPublicKeyLength"));
+        assert_eq!(err.code(), 4001);
+        assert_eq!(details(&err, "field"), text("public_key"));
+        assert_eq!(details(&err, "algorithm"), text("curve25519xsalsa20poly1305"));
+        assert_eq!(details(&err, "expected_length"), Some(Value::U64(32)));
+        assert_eq!(details(&err, "actual_length"), Some(Value::U64(3)));
+    }
+
+    #[test]
+    fn test_invalid_private_key_length() {
+        let ciphertext = sealedbox::crypt("data", PUB_KEY_B64, BTreeMap::new()).unwrap();
+        let err = sealedbox::decrypt(&ciphertext, "AAAA", PUB_KEY_B64, BTreeMap::new()).unwrap_err();
+        assert!(err.class().ends_with("::InvalidPrivateKeyLength"));
+        assert_eq!(err.code(), 4002);
+        assert_eq!(details(&err, "field"), text("private_key"));
+    }
+
+    #[test]
+    fn test_ciphertext_too_short() {
+        let err = sealedbox::decrypt("AAAA", PRIV_KEY_B64, PUB_KEY_B64, BTreeMap::new()).unwrap_err();
+        assert!(err.class().ends_with("::CiphertextTooShort"));
+        assert_eq!(err.code(), 4003);
+        assert_eq!(details(&err, "field"), text("data"));
+        assert_eq!(details(&err, "algorithm"), text("curve25519xsalsa20poly1305"));
+        assert_eq!(details(&err, "expected_length"), Some(Value::U64(48)));
+        assert_eq!(details(&err, "actual_length"), Some(Value::U64(3)));
+    }
+
+    #[test]
+    fn test_authentication_failed() {
+        let mut context = BTreeMap::new();
+        context.insert("request_id".to_string(), Value::String("42".to_string()));
+        let (nonce_b64, ciphertext) = secretbox::crypt("data", SB_KEY_B64, context.clone()).unwrap();
+        let other_key = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+        let err = secretbox::decrypt(&ciphertext, other_key, &nonce_b64, context).unwrap_err();
+        assert!(err.class().ends_with("::AuthenticationFailed"));
+        assert_eq!(err.code(), 4004);
+        assert_eq!(details(&err, "algorithm"), text("xsalsa20poly1305"));
+        assert_eq!(details(&err, "field"), text("data"));
+        assert_eq!(details(&err, "request_id"), text("42"));
+    }
+
+    #[test]
+    fn test_invalid_base64() {
+        let err = secretbox::decrypt("AAAA", SB_KEY_B64, "not base64!", BTreeMap::new()).unwrap_err();
+        assert!(err.class().ends_with("::InvalidBase64"));
+        assert_eq!(err.code(), 4005);
+        assert_eq!(details(&err, "field"), text("nonce"));
+    }
+
+    #[test]
+    fn test_length_details() {
+        let err = secretbox::crypt("data", "AAAA", BTreeMap::new()).unwrap_err();
+        assert!(err.class().ends_with("::InvalidBoxKeyLength"));
+        assert_eq!(details(&err, "field"), text("key"));
+        assert_eq!(details(&err, "expected_length"), Some(Value::U64(32)));
+        assert_eq!(details(&err, "actual_length"), Some(Value::U64(3)));
+    }
+
+    #[test]
+    fn test_context_is_optional() {
+        let (nonce_b64, ciphertext) = secretbox::crypt("data", SB_KEY_B64, None).unwrap();
+        assert_eq!(secretbox::decrypt(&ciphertext, SB_KEY_B64, &nonce_b64, None).unwrap(), "data");
+        let err = secretbox::decrypt(&ciphertext, SB_KEY_B64, "AAAA", None).unwrap_err();
+        assert_eq!(details(&err, "operation"), text("decrypt"));
+    }
+
+    #[test]
+    fn test_operation_details() {
+        let mut context = BTreeMap::new();
+        context.insert("operation".to_string(), Value::String("rotate".to_string()));
+        let keyring = Keyring::new("2024", SB_KEY_B64, None).unwrap();
+        let err = keyring.decrypt("2023:AAAA", NONCE_B64, None).unwrap_err();
+        assert_eq!(details(&err, "module"), text("cdumay_sodium::keyring"));
+        assert_eq!(details(&err, "operation"), text("Keyring::decrypt"));
+
+        let err = keyring.decrypt("2023:AAAA", NONCE_B64, context).unwrap_err();
+        assert_eq!(details(&err, "operation"), text("rotate"));
+    }
+
+    #[test]
+    fn test_key_fingerprint() {
+        let other_key = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+        let (nonce_b64, ciphertext) = secretbox::crypt("data", SB_KEY_B64, None).unwrap();
+        let err = secretbox::decrypt(&ciphertext, other_key, &nonce_b64, None).unwrap_err();
+        let fingerprint = details(&err, "key_fingerprint").unwrap();
+        match &fingerprint {
+            Value::String(fingerprint) => assert!(fingerprint.len() == 16 && fingerprint.chars().all(|c| c.is_ascii_hexdigit())),
+            other => panic!("unexpected fingerprint {:?}", other),
+        }
+        let again = secretbox::decrypt_with_algorithm(&ciphertext, other_key, &nonce_b64, secretbox::Algorithm::XSalsa20Poly1305, None);
+        assert_eq!(details(&again.unwrap_err(), "key_fingerprint"), Some(fingerprint.clone()));
+        let reencrypted = secretbox::reencrypt(&ciphertext, &nonce_b64, other_key, SB_KEY_B64, None);
+        assert_eq!(details(&reencrypted.unwrap_err(), "key_fingerprint"), Some(fingerprint.clone()));
+        let deterministic = secretbox::crypt_deterministic("data", "users.email", SB_KEY_B64, None).unwrap();
+        let err = secretbox::decrypt_deterministic(&deterministic, "users.email", other_key, None).unwrap_err();
+        assert_eq!(details(&err, "key_fingerprint"), Some(fingerprint.clone()));
+        // Opened with the right key but other associated data, the nonce verification fails.
+        let err = secretbox::decrypt_deterministic(&deterministic, "users.phone", SB_KEY_B64, None).unwrap_err();
+        let key_fingerprint = secretbox::fingerprint(SB_KEY_B64, None).unwrap().to_string();
+        assert_eq!(details(&err, "key_fingerprint"), Some(Value::String(key_fingerprint)));
+
+        let (nonce_b64, other_ciphertext) = secretbox::crypt("data", other_key, None).unwrap();
+        let err = secretbox::decrypt(&other_ciphertext, SB_KEY_B64, &nonce_b64, None).unwrap_err();
+        assert_ne!(details(&err, "key_fingerprint"), Some(fingerprint));
+    }
+
+    #[test]
+    fn test_base64_details() {
+        let err = secretbox::decrypt("AAAA", SB_KEY_B64, "HZGeSXQLJlFNpQgGyvYkXj-jAL9d_15J", None).unwrap_err();
+        assert_eq!(details(&err, "field"), text("nonce"));
+        assert_eq!(details(&err, "input_length"), Some(Value::U64(32)));
+        assert_eq!(details(&err, "base64_variant"), text("url_safe"));
+
+        let err = secretbox::decrypt("AAAA", "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU", NONCE_B64, None).unwrap_err();
+        assert_eq!(details(&err, "base64_variant"), text("standard_no_pad"));
+    }
+
+    #[test]
+    fn test_context_holds_no_secret() {
+        let plaintext = "correct horse battery staple";
+        let (nonce_b64, ciphertext) = secretbox::crypt(plaintext, SB_KEY_B64, None).unwrap();
+        let sealed = sealedbox::crypt(plaintext, PUB_KEY_B64, None).unwrap();
+        let other_key = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+        let errors = [
+            secretbox::decrypt(&ciphertext, other_key, &nonce_b64, None).unwrap_err(),
+            secretbox::decrypt(&ciphertext, "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJS", &nonce_b64, None).unwrap_err(),
+            sealedbox::decrypt(&sealed, "1XyU8ACjhWXEwY8oiL7Vmn43fzxcUU8hNd5UUVLeZRY=", PUB_KEY_B64, None).unwrap_err(),
+            Keyring::new("2024", other_key, None).unwrap().decrypt(&format!("2024:{}", ciphertext), &nonce_b64, None).unwrap_err(),
+        ];
+        for err in errors {
+            let rendered = format!("{:?} {}", err.details(), err);
+            for secret in [SB_KEY_B64, other_key, PRIV_KEY_B64, "1XyU8ACjhWXEwY8oiL7Vmn43fzxcUU8hNd5UUVLeZRY=", plaintext] {
+                assert!(!rendered.contains(secret), "{} leaks into {}", secret, rendered);
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::envelope::{self, Envelope, SealedBoxKek, SecretBoxKek};
+    use cdumay_sodium::fingerprint::Fingerprint;
+    use cdumay_sodium::keyring::Keyring;
+    use cdumay_sodium::noise::Keypair;
+    use cdumay_sodium::{sealedbox, secretbox};
+    use serde_value::Value;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const OTHER_KEY_B64: &str = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+    const INPUT: &str = r#"{"hello": "world"}"#;
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        let fingerprint = secretbox::fingerprint(SB_KEY_B64, None).unwrap();
+        assert_eq!(secretbox::fingerprint(SB_KEY_B64, None).unwrap(), fingerprint);
+        assert_ne!(secretbox::fingerprint(OTHER_KEY_B64, None).unwrap(), fingerprint);
+        assert_eq!(fingerprint.to_string().len(), 16);
+        assert!(!fingerprint.to_string().contains(SB_KEY_B64));
+        // Pinned values: a change of label, hash or truncation would rename every key in logs and envelopes.
+        assert_eq!(fingerprint.to_string(), "cf8671909cde9bdc");
+        assert_eq!(sealedbox::fingerprint(PUB_KEY_B64, None).unwrap().to_string(), "3a1b5e4cb9a65efd");
+    }
+
+    #[test]
+    fn test_fingerprint_labels_differ() {
+        // The same 32 bytes fingerprinted as a secret key and as a public key.
+        assert_ne!(secretbox::fingerprint(PUB_KEY_B64, None).unwrap(), sealedbox::fingerprint(PUB_KEY_B64, None).unwrap());
+    }
+
+    #[test]
+    fn test_fingerprint_parse() {
+        let fingerprint = secretbox::fingerprint(SB_KEY_B64, None).unwrap();
+        let grouped = fingerprint.to_hex_groups();
+        assert_eq!(grouped.matches('-').count(), 3);
+        assert_eq!(Fingerprint::parse(&grouped, None).unwrap(), fingerprint);
+        assert_eq!(Fingerprint::parse(&grouped.to_uppercase(), None).unwrap(), fingerprint);
+        assert_eq!(Fingerprint::parse(&fingerprint.to_string(), None).unwrap(), fingerprint);
+
+        for invalid in ["", "0011", "00112233445566778899", "001122334455667g"] {
+            let err = Fingerprint::parse(invalid, None).unwrap_err();
+            assert!(err.class().ends_with("::InvalidFingerprint"));
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_invalid_key() {
+        assert!(secretbox::fingerprint("AAAA", None).is_err());
+        assert!(sealedbox::fingerprint("AAAA", None).is_err());
+    }
+
+    #[test]
+    fn test_keyring_fingerprint() {
+        let mut keyring = Keyring::new("2023", SB_KEY_B64, None).unwrap();
+        keyring.add_key("2024", OTHER_KEY_B64, None).unwrap();
+        let fingerprint = keyring.fingerprint("2024", None).unwrap();
+        assert_eq!(fingerprint, secretbox::fingerprint(OTHER_KEY_B64, None).unwrap());
+        assert_eq!(keyring.key_id_by_fingerprint(&fingerprint), Some("2024"));
+
+        let unknown = sealedbox::fingerprint(PUB_KEY_B64, None).unwrap();
+        assert_eq!(keyring.key_id_by_fingerprint(&unknown), None);
+        assert!(keyring.fingerprint("2025", None).is_err());
+    }
+
+    #[test]
+    fn test_keypair_fingerprint() {
+        let keypair = Keypair::from_base64(PRIV_KEY_B64, None).unwrap();
+        assert_eq!(keypair.fingerprint(), sealedbox::fingerprint(PUB_KEY_B64, None).unwrap());
+        let kek = SealedBoxKek::new("pub", PUB_KEY_B64, None).unwrap();
+        assert_eq!(kek.fingerprint(), keypair.fingerprint());
+    }
+
+    #[test]
+    fn test_envelope_embeds_fingerprint() {
+        let kek = SecretBoxKek::new("kek", SB_KEY_B64, None).unwrap();
+        let sealed = envelope::encrypt(INPUT, &kek, None).unwrap();
+        assert_eq!(sealed.kek_fingerprint, Some(secretbox::fingerprint(SB_KEY_B64, None).unwrap()));
+        let parsed = Envelope::parse(&sealed.to_string(), None).unwrap();
+        assert_eq!(parsed.kek_fingerprint, sealed.kek_fingerprint);
+        assert_eq!(INPUT, envelope::decrypt(&parsed, &kek, None).unwrap());
+
+        // The fingerprint is bound to the payload: removing it does not skip the check.
+        let mut stripped = sealed;
+        stripped.kek_fingerprint = None;
+        let stripped = Envelope::parse(&stripped.to_string(), None).unwrap();
+        assert!(stripped.to_string().starts_with("v3.xsalsa20poly1305..kek."));
+        let err = envelope::decrypt(&stripped, &kek, None).unwrap_err();
+        assert!(err.class().ends_with("::AuthenticationFailed"));
+    }
+
+    #[test]
+    fn test_envelope_fingerprint_mismatch() {
+        let kek = SecretBoxKek::new("kek", SB_KEY_B64, None).unwrap();
+        let replaced = SecretBoxKek::new("kek", OTHER_KEY_B64, None).unwrap();
+        let sealed = envelope::encrypt(INPUT, &kek, None).unwrap();
+        let err = envelope::decrypt(&sealed, &replaced, None).unwrap_err();
+        assert!(err.class().ends_with("::KeyEncryptionKeyMismatch"));
+        let details = err.details_ref();
+        assert_eq!(details.get("kek_id"), Some(&Value::String("kek".to_string())));
+        assert_eq!(details.get("kek_fingerprint"), Some(&Value::String(kek.fingerprint().to_string())));
+        assert_eq!(details.get("key_fingerprint"), Some(&Value::String(replaced.fingerprint().to_string())));
+    }
+}
@@ -0,0 +1,32 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::secretbox;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_init_is_idempotent() {
+        let context = BTreeMap::new();
+        for _ in 0..3 {
+            assert!(cdumay_sodium::init(context.clone()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_init_from_concurrent_entry_points() {
+        let key_b64 = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let context = BTreeMap::new();
+                    let data = format!("message {}", i);
+                    let (nonce, ciphertext) = secretbox::crypt(&data, key_b64, context.clone()).unwrap();
+                    assert_eq!(secretbox::decrypt(&ciphertext, key_b64, &nonce, context.clone()).unwrap(), data);
+                    cdumay_sodium::init(context).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
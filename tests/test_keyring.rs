@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::keyring::Keyring;
+    use cdumay_sodium::secretbox;
+    use std::collections::{BTreeMap, HashMap};
+
+    const OLD_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const NEW_KEY_B64: &str = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+    const INPUT: &str = r#"{"hello": "world"}"#;
+
+    fn keyring() -> Keyring {
+        let mut config = BTreeMap::new();
+        config.insert("v1".to_string(), OLD_KEY_B64.to_string());
+        config.insert("v2".to_string(), NEW_KEY_B64.to_string());
+        Keyring::from_config(&config, "v2", BTreeMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_keyring() {
+        let context = BTreeMap::new();
+        let keyring = keyring();
+        assert_eq!(keyring.active_id(), "v2");
+        assert_eq!(keyring.key_ids().collect::<Vec<_>>(), vec!["v1", "v2"]);
+        let (nonce_b64, data) = keyring.crypt(INPUT, context.clone()).unwrap();
+        assert!(data.starts_with("v2:"));
+        assert_eq!(INPUT, keyring.decrypt(&data, &nonce_b64, context).unwrap());
+    }
+
+    #[test]
+    fn test_keyring_previous_active_key() {
+        let context = BTreeMap::new();
+        let mut keyring = keyring();
+        keyring.set_active("v1", context.clone()).unwrap();
+        let (nonce_b64, data) = keyring.crypt(INPUT, context.clone()).unwrap();
+        keyring.set_active("v2", context.clone()).unwrap();
+        assert!(data.starts_with("v1:"));
+        assert_eq!(INPUT, keyring.decrypt(&data, &nonce_b64, context).unwrap());
+    }
+
+    #[test]
+    fn test_keyring_legacy_ciphertext() {
+        let context = BTreeMap::new();
+        let keyring = keyring();
+        let (nonce_b64, data) = secretbox::crypt(INPUT, OLD_KEY_B64, context.clone()).unwrap();
+        assert_eq!(INPUT, keyring.decrypt(&data, &nonce_b64, context).unwrap());
+    }
+
+    #[test]
+    fn test_keyring_from_hashmap() {
+        let context = BTreeMap::new();
+        let config = HashMap::from([("a", OLD_KEY_B64)]);
+        let keyring = Keyring::from_config(&config, "a", context.clone()).unwrap();
+        let (nonce_b64, data) = keyring.crypt(INPUT, context.clone()).unwrap();
+        assert_eq!(INPUT, keyring.decrypt(&data, &nonce_b64, context).unwrap());
+    }
+
+    #[test]
+    fn test_keyring_unknown_key_id() {
+        let context = BTreeMap::new();
+        let keyring = keyring();
+        let other = Keyring::new("v3", OLD_KEY_B64, context.clone()).unwrap();
+        let (nonce_b64, data) = other.crypt(INPUT, context.clone()).unwrap();
+        assert!(keyring.decrypt(&data, &nonce_b64, context).is_err());
+    }
+
+    #[test]
+    fn test_keyring_wrong_stamped_key() {
+        let context = BTreeMap::new();
+        let keyring = keyring();
+        let (nonce_b64, data) = keyring.crypt(INPUT, context.clone()).unwrap();
+        let restamped = data.replacen("v2:", "v1:", 1);
+        assert!(keyring.decrypt(&restamped, &nonce_b64, context).is_err());
+    }
+
+    #[test]
+    fn test_keyring_legacy_no_matching_key() {
+        let context = BTreeMap::new();
+        let keyring = Keyring::new("v2", NEW_KEY_B64, context.clone()).unwrap();
+        let (nonce_b64, data) = secretbox::crypt(INPUT, OLD_KEY_B64, context.clone()).unwrap();
+        assert!(keyring.decrypt(&data, &nonce_b64, context).is_err());
+    }
+
+    #[test]
+    fn test_keyring_invalid_config() {
+        let context = BTreeMap::new();
+        assert!(Keyring::new("", OLD_KEY_B64, context.clone()).is_err());
+        assert!(Keyring::new("v:1", OLD_KEY_B64, context.clone()).is_err());
+        assert!(Keyring::new("v1", "not-valid-base64!!!", context.clone()).is_err());
+        assert!(Keyring::new("v1", "AAAA", context.clone()).is_err());
+        let config = BTreeMap::from([("v1", OLD_KEY_B64)]);
+        assert!(Keyring::from_config(&config, "v2", context.clone()).is_err());
+        let mut keyring = keyring();
+        assert!(keyring.set_active("v9", context).is_err());
+        assert_eq!(keyring.active_id(), "v2");
+    }
+
+    #[test]
+    fn test_keyring_empty_data() {
+        let context = BTreeMap::new();
+        let keyring = keyring();
+        assert_eq!(String::new(), keyring.decrypt("", "HZGeSXQLJlFNpQgGyvYkXj+jAL9d/15J", context).unwrap());
+    }
+
+    #[test]
+    fn test_keyring_reencrypt() {
+        let context = BTreeMap::new();
+        let current = keyring();
+        let (legacy_nonce, legacy_data) = secretbox::crypt(INPUT, OLD_KEY_B64, context.clone()).unwrap();
+        let (nonce_b64, data) = current.reencrypt(&legacy_data, &legacy_nonce, context.clone()).unwrap();
+        assert!(data.starts_with("v2:"));
+        assert_eq!(INPUT, current.decrypt(&data, &nonce_b64, context.clone()).unwrap());
+
+        let mut old = keyring();
+        old.set_active("v1", context.clone()).unwrap();
+        let (old_nonce, old_data) = old.crypt(INPUT, context.clone()).unwrap();
+        let (nonce_b64, data) = current.reencrypt(&old_data, &old_nonce, context.clone()).unwrap();
+        assert!(data.starts_with("v2:"));
+        assert_eq!(INPUT, current.decrypt(&data, &nonce_b64, context).unwrap());
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::envelope::{self, SecretBoxKek};
+    use cdumay_sodium::keyring::Keyring;
+    use cdumay_sodium::memory::SecureBuffer;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    const KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+
+    #[test]
+    fn test_secure_buffer_round_trip() {
+        let context = BTreeMap::new();
+        let mut buffer = SecureBuffer::new(64, context).unwrap();
+        assert_eq!(buffer.len(), 64);
+        assert!(!buffer.is_empty());
+        assert_eq!(&*buffer.read(), &[0u8; 64]);
+
+        buffer.write()[..4].copy_from_slice(b"abcd");
+        assert_eq!(&buffer.read()[..5], b"abcd\0");
+        assert_eq!(format!("{:?}", buffer), "SecureBuffer { len: 64 }");
+    }
+
+    #[test]
+    fn test_secure_buffer_nested_reads() {
+        let context = BTreeMap::new();
+        let buffer = SecureBuffer::from_slice(&[3u8; 32], context).unwrap();
+        let first = buffer.read();
+        {
+            let second = buffer.read();
+            assert_eq!(&*second, &[3u8; 32]);
+        }
+        // The buffer stays readable while the outer guard is alive.
+        assert_eq!(&*first, &[3u8; 32]);
+    }
+
+    #[test]
+    fn test_secure_buffer_from_base64() {
+        let context = BTreeMap::new();
+        let buffer = SecureBuffer::from_base64("AAECAw==", context.clone()).unwrap();
+        assert_eq!(&*buffer.read(), &[0u8, 1, 2, 3]);
+        assert!(SecureBuffer::from_base64("not base64!", context).is_err());
+    }
+
+    #[test]
+    fn test_secure_buffer_shared_across_threads() {
+        let context = BTreeMap::new();
+        let buffer = Arc::new(SecureBuffer::from_slice(&[5u8; 32], context).unwrap());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let buffer = Arc::clone(&buffer);
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        assert_eq!(&*buffer.read(), &[5u8; 32]);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_keyring_and_kek_use_secure_keys() {
+        let context = BTreeMap::new();
+        let mut keyring = Keyring::new("v1", KEY_B64, context.clone()).unwrap();
+        keyring.add_secure_key("v2", SecureBuffer::from_base64(KEY_B64, context.clone()).unwrap(), context.clone()).unwrap();
+        assert!(keyring.add_secure_key("v3", SecureBuffer::new(16, context.clone()).unwrap(), context.clone()).is_err());
+        keyring.set_active("v2", context.clone()).unwrap();
+        let (nonce, encrypted) = keyring.crypt("secret", context.clone()).unwrap();
+        assert_eq!(keyring.decrypt(&encrypted, &nonce, context.clone()).unwrap(), "secret");
+
+        let kek = SecretBoxKek::from_secure_key("kek", SecureBuffer::from_base64(KEY_B64, context.clone()).unwrap(), context.clone()).unwrap();
+        let sealed = envelope::encrypt("secret", &kek, context.clone()).unwrap();
+        assert_eq!(envelope::decrypt(&sealed, &kek, context.clone()).unwrap(), "secret");
+        assert!(SecretBoxKek::from_secure_key("kek", SecureBuffer::new(31, context.clone()).unwrap(), context).is_err());
+    }
+}
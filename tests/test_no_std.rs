@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::process::Command;
+
+    const TARGET: &str = "thumbv7em-none-eabihf";
+    /// Environment variable making a missing target a failure instead of a skip, set by the CI which installs the target.
+    const REQUIRE_TARGET: &str = "CDUMAY_SODIUM_REQUIRE_NO_STD_TARGET";
+
+    fn target_installed() -> bool {
+        let output = Command::new("rustc").args(["--print", "target-libdir", "--target", TARGET]).output();
+        match output {
+            Ok(output) if output.status.success() => Path::new(String::from_utf8_lossy(&output.stdout).trim()).exists(),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_no_std_core_builds() {
+        if !target_installed() {
+            assert!(std::env::var_os(REQUIRE_TARGET).is_none(), "target {} is not installed (rustup target add {})", TARGET, TARGET);
+            eprintln!("skipping: target {} is not installed (rustup target add {})", TARGET, TARGET);
+            return;
+        }
+        for features in ["", "rustcrypto"] {
+            let status = Command::new(env!("CARGO"))
+                .args(["build", "--lib", "--no-default-features", "--features", features, "--target", TARGET, "--target-dir"])
+                .arg(Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_std"))
+                .current_dir(env!("CARGO_MANIFEST_DIR"))
+                .status()
+                .unwrap();
+            assert!(status.success(), "no_std build failed with features '{}'", features);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::noise::{HandshakePattern, HandshakeState, Keypair, MAX_MESSAGE_LEN, TransportState};
+    use std::collections::BTreeMap;
+
+    const VECTORS: &str = include_str!("fixtures/noise_vectors.json");
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn keypair(vector: &serde_json::Value, field: &str) -> Option<Keypair> {
+        vector[field]
+            .as_str()
+            .map(|k| Keypair::from_private_key(&hex(k), BTreeMap::new()).unwrap())
+    }
+
+    fn pattern(name: &str) -> HandshakePattern {
+        match name {
+            "Noise_NK_25519_ChaChaPoly_BLAKE2b" => HandshakePattern::NK,
+            "Noise_IK_25519_ChaChaPoly_BLAKE2b" => HandshakePattern::IK,
+            "Noise_XX_25519_ChaChaPoly_BLAKE2b" => HandshakePattern::XX,
+            name => panic!("unexpected protocol {}", name),
+        }
+    }
+
+    fn handshake(pattern: HandshakePattern, initiator: HandshakeState, responder: HandshakeState) -> (TransportState, TransportState) {
+        let context = BTreeMap::new();
+        let (mut initiator, mut responder) = (initiator, responder);
+        while !initiator.is_finished() {
+            let (writer, reader) = match initiator.is_my_turn() {
+                true => (&mut initiator, &mut responder),
+                false => (&mut responder, &mut initiator),
+            };
+            let message = writer.write_message(pattern.name().as_bytes(), context.clone()).unwrap();
+            assert_eq!(reader.read_message(&message, context.clone()).unwrap(), pattern.name().as_bytes());
+        }
+        assert!(responder.is_finished());
+        assert_eq!(initiator.handshake_hash(), responder.handshake_hash());
+        (
+            initiator.into_transport(context.clone()).unwrap(),
+            responder.into_transport(context).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_noise_vectors() {
+        let vectors: serde_json::Value = serde_json::from_str(VECTORS).unwrap();
+        let vectors = vectors["vectors"].as_array().unwrap();
+        assert_eq!(vectors.len(), 3);
+        for vector in vectors {
+            let context = BTreeMap::new();
+            let pattern = pattern(vector["protocol_name"].as_str().unwrap());
+            assert_eq!(pattern.protocol_name(), vector["protocol_name"].as_str().unwrap());
+            let init_remote_static = vector["init_remote_static"].as_str().map(hex);
+            let mut initiator = HandshakeState::new(
+                pattern,
+                true,
+                &hex(vector["init_prologue"].as_str().unwrap()),
+                keypair(vector, "init_static"),
+                init_remote_static.as_deref(),
+                context.clone(),
+            )
+            .unwrap()
+            .with_ephemeral(keypair(vector, "init_ephemeral").unwrap());
+            let mut responder = HandshakeState::new(
+                pattern,
+                false,
+                &hex(vector["resp_prologue"].as_str().unwrap()),
+                keypair(vector, "resp_static"),
+                None,
+                context.clone(),
+            )
+            .unwrap()
+            .with_ephemeral(keypair(vector, "resp_ephemeral").unwrap());
+
+            let mut transports: Option<(TransportState, TransportState)> = None;
+            for (index, message) in vector["messages"].as_array().unwrap().iter().enumerate() {
+                let payload = hex(message["payload"].as_str().unwrap());
+                let ciphertext = hex(message["ciphertext"].as_str().unwrap());
+                let initiator_sends = index % 2 == 0;
+                match transports.as_mut() {
+                    None => {
+                        let (writer, reader) = match initiator_sends {
+                            true => (&mut initiator, &mut responder),
+                            false => (&mut responder, &mut initiator),
+                        };
+                        assert_eq!(
+                            writer.write_message(&payload, context.clone()).unwrap(),
+                            ciphertext,
+                            "{} message {}",
+                            pattern.name(),
+                            index
+                        );
+                        assert_eq!(reader.read_message(&ciphertext, context.clone()).unwrap(), payload);
+                        if initiator.is_finished() {
+                            assert_eq!(initiator.handshake_hash(), hex(vector["handshake_hash"].as_str().unwrap()).as_slice());
+                            assert_eq!(responder.handshake_hash(), initiator.handshake_hash());
+                            let initiator = std::mem::replace(
+                                &mut initiator,
+                                HandshakeState::new(HandshakePattern::XX, true, &[], Some(Keypair::generate()), None, context.clone()).unwrap(),
+                            );
+                            let responder = std::mem::replace(
+                                &mut responder,
+                                HandshakeState::new(HandshakePattern::XX, false, &[], Some(Keypair::generate()), None, context.clone()).unwrap(),
+                            );
+                            transports = Some((
+                                initiator.into_transport(context.clone()).unwrap(),
+                                responder.into_transport(context.clone()).unwrap(),
+                            ));
+                        }
+                    }
+                    Some((initiator, responder)) => {
+                        let (writer, reader) = match initiator_sends {
+                            true => (initiator, responder),
+                            false => (responder, initiator),
+                        };
+                        assert_eq!(
+                            writer.write_message(&payload, context.clone()).unwrap(),
+                            ciphertext,
+                            "{} message {}",
+                            pattern.name(),
+                            index
+                        );
+                        assert_eq!(reader.read_message(&ciphertext, context.clone()).unwrap(), payload);
+                    }
+                }
+            }
+            assert!(transports.is_some());
+        }
+    }
+
+    #[test]
+    fn test_noise_xx_exchanges_static_keys() {
+        let context = BTreeMap::new();
+        let initiator_static = Keypair::generate();
+        let responder_static = Keypair::generate();
+        let initiator_public = initiator_static.public_key().to_vec();
+        let responder_public = responder_static.public_key().to_vec();
+        let initiator = HandshakeState::new(HandshakePattern::XX, true, b"prologue", Some(initiator_static), None, context.clone()).unwrap();
+        let responder = HandshakeState::new(HandshakePattern::XX, false, b"prologue", Some(responder_static), None, context.clone()).unwrap();
+        let (mut initiator, mut responder) = handshake(HandshakePattern::XX, initiator, responder);
+        assert_eq!(initiator.remote_static().unwrap(), responder_public.as_slice());
+        assert_eq!(responder.remote_static().unwrap(), initiator_public.as_slice());
+
+        let message = responder.write_message(b"pong", context.clone()).unwrap();
+        assert_eq!(initiator.read_message(&message, context.clone()).unwrap(), b"pong");
+        let message = initiator.write_message(b"ping", context.clone()).unwrap();
+        assert_eq!(responder.read_message(&message, context).unwrap(), b"ping");
+    }
+
+    #[test]
+    fn test_noise_ik_with_sealedbox_keys() {
+        let context = BTreeMap::new();
+        // Sealed-box keys are X25519 keys and can be used as noise static keys.
+        let responder_static = Keypair::from_base64("Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=", context.clone()).unwrap();
+        assert_eq!(responder_static.public_key_b64(), "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=");
+        let responder_public = responder_static.public_key().to_vec();
+        let initiator = HandshakeState::new(
+            HandshakePattern::IK,
+            true,
+            b"",
+            Some(Keypair::generate()),
+            Some(&responder_public),
+            context.clone(),
+        )
+        .unwrap();
+        let responder = HandshakeState::new(HandshakePattern::IK, false, b"", Some(responder_static), None, context.clone()).unwrap();
+        let (mut initiator, mut responder) = handshake(HandshakePattern::IK, initiator, responder);
+        let message = initiator.write_message(b"hello", context.clone()).unwrap();
+        assert_eq!(responder.read_message(&message, context).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_noise_nk_wrong_remote_static() {
+        let context = BTreeMap::new();
+        let responder_static = Keypair::generate();
+        let wrong_public = Keypair::generate().public_key().to_vec();
+        let mut initiator = HandshakeState::new(HandshakePattern::NK, true, b"", None, Some(&wrong_public), context.clone()).unwrap();
+        let mut responder = HandshakeState::new(HandshakePattern::NK, false, b"", Some(responder_static), None, context.clone()).unwrap();
+        let message = initiator.write_message(b"hello", context.clone()).unwrap();
+        assert!(responder.read_message(&message, context).is_err());
+    }
+
+    #[test]
+    fn test_noise_prologue_mismatch() {
+        let context = BTreeMap::new();
+        let mut initiator = HandshakeState::new(HandshakePattern::XX, true, b"v1", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let mut responder = HandshakeState::new(HandshakePattern::XX, false, b"v2", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let message = initiator.write_message(b"", context.clone()).unwrap();
+        responder.read_message(&message, context.clone()).unwrap();
+        let message = responder.write_message(b"", context.clone()).unwrap();
+        assert!(initiator.read_message(&message, context).is_err());
+    }
+
+    #[test]
+    fn test_noise_missing_keys() {
+        let context = BTreeMap::new();
+        assert!(HandshakeState::new(HandshakePattern::NK, true, b"", None, None, context.clone()).is_err());
+        assert!(HandshakeState::new(HandshakePattern::XX, true, b"", None, None, context.clone()).is_err());
+        assert!(HandshakeState::new(HandshakePattern::XX, false, b"", None, None, context.clone()).is_err());
+        assert!(HandshakeState::new(HandshakePattern::NK, true, b"", None, Some(&[0u8; 16]), context).is_err());
+    }
+
+    #[test]
+    fn test_noise_out_of_turn() {
+        let context = BTreeMap::new();
+        let mut initiator = HandshakeState::new(HandshakePattern::XX, true, b"", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let mut responder = HandshakeState::new(HandshakePattern::XX, false, b"", Some(Keypair::generate()), None, context.clone()).unwrap();
+        assert!(responder.write_message(b"", context.clone()).is_err());
+        assert!(initiator.read_message(&[0u8; 32], context.clone()).is_err());
+        let message = initiator.write_message(b"", context.clone()).unwrap();
+        assert!(responder.read_message(&message[..16], context.clone()).is_err());
+        assert!(initiator.into_transport(context).is_err());
+    }
+
+    #[test]
+    fn test_noise_message_too_long_keeps_state() {
+        let context = BTreeMap::new();
+        let mut initiator = HandshakeState::new(HandshakePattern::XX, true, b"", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let mut responder = HandshakeState::new(HandshakePattern::XX, false, b"", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let message = initiator.write_message(b"", context.clone()).unwrap();
+        responder.read_message(&message, context.clone()).unwrap();
+        let hash = responder.handshake_hash().to_vec();
+        // e, ee, s, es and the payload tag: the limit is reached with a payload 96 bytes shorter than MAX_MESSAGE_LEN.
+        assert!(responder.write_message(&vec![0u8; MAX_MESSAGE_LEN - 95], context.clone()).is_err());
+        assert_eq!(responder.handshake_hash(), hash.as_slice());
+
+        let message = responder.write_message(&vec![0u8; MAX_MESSAGE_LEN - 96], context.clone()).unwrap();
+        assert_eq!(message.len(), MAX_MESSAGE_LEN);
+        initiator.read_message(&message, context.clone()).unwrap();
+        let message = initiator.write_message(b"", context.clone()).unwrap();
+        responder.read_message(&message, context.clone()).unwrap();
+        assert_eq!(initiator.handshake_hash(), responder.handshake_hash());
+    }
+
+    #[test]
+    fn test_noise_failed_handshake_is_unusable() {
+        let context = BTreeMap::new();
+        let mut initiator = HandshakeState::new(HandshakePattern::XX, true, b"", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let mut responder = HandshakeState::new(HandshakePattern::XX, false, b"", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let message = initiator.write_message(b"", context.clone()).unwrap();
+        responder.read_message(&message, context.clone()).unwrap();
+        let mut message = responder.write_message(b"", context.clone()).unwrap();
+        message[40] ^= 1;
+        assert!(initiator.read_message(&message, context.clone()).is_err());
+        message[40] ^= 1;
+        assert!(initiator.read_message(&message, context.clone()).is_err());
+        assert!(initiator.write_message(b"", context.clone()).is_err());
+        assert!(initiator.into_transport(context).is_err());
+    }
+
+    #[test]
+    fn test_noise_transport_tampered_and_replayed() {
+        let context = BTreeMap::new();
+        let initiator = HandshakeState::new(HandshakePattern::XX, true, b"", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let responder = HandshakeState::new(HandshakePattern::XX, false, b"", Some(Keypair::generate()), None, context.clone()).unwrap();
+        let (mut initiator, mut responder) = handshake(HandshakePattern::XX, initiator, responder);
+        let mut message = initiator.write_message(b"hello", context.clone()).unwrap();
+        message[0] ^= 1;
+        assert!(responder.read_message(&message, context.clone()).is_err());
+        message[0] ^= 1;
+        assert!(responder.read_message(&message, context.clone()).is_ok());
+        assert!(responder.read_message(&message, context).is_err());
+    }
+}
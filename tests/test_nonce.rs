@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::nonce::{NonceReuseDetector, NonceSequence, COUNTERBYTES, NONCEBYTES, PREFIXBYTES};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_nonce_sequence_is_monotonic() {
+        let context = BTreeMap::new();
+        let mut sequence = NonceSequence::with_prefix([9u8; PREFIXBYTES]);
+        for expected in 0u64..300 {
+            let nonce = sequence.next(context.clone()).unwrap();
+            assert_eq!(nonce.len(), NONCEBYTES);
+            assert_eq!(&nonce[..PREFIXBYTES], &[9u8; PREFIXBYTES]);
+            let counter: [u8; COUNTERBYTES] = nonce[PREFIXBYTES..].try_into().unwrap();
+            assert_eq!(u64::from_le_bytes(counter), expected);
+        }
+    }
+
+    #[test]
+    fn test_nonce_sequences_have_random_prefixes() {
+        let context = BTreeMap::new();
+        let mut first = NonceSequence::new();
+        let mut second = NonceSequence::new();
+        assert_ne!(first.prefix(), second.prefix());
+        assert_ne!(first.next_b64(context.clone()).unwrap(), second.next_b64(context).unwrap());
+    }
+
+    #[test]
+    fn test_nonce_reuse_detector() {
+        let context = BTreeMap::new();
+        let mut detector = NonceReuseDetector::new(16);
+        let mut sequence = NonceSequence::new();
+        let nonce = sequence.next(context.clone()).unwrap();
+
+        assert!(detector.observe(&[1u8; 32], &nonce, context.clone()).is_ok());
+        assert!(detector.observe(&[2u8; 32], &nonce, context.clone()).is_ok());
+        assert!(detector.observe(&[1u8; 32], &sequence.next(context.clone()).unwrap(), context.clone()).is_ok());
+        assert_eq!(detector.len(), 3);
+
+        let mut error_context = context.clone();
+        error_context.insert("table".to_string(), serde_value::Value::String("users".to_string()));
+        let err = detector.observe(&[1u8; 32], &nonce, error_context).unwrap_err();
+        assert_eq!(err.details_ref().get("table"), Some(&serde_value::Value::String("users".to_string())));
+        assert_eq!(detector.len(), 3);
+    }
+
+    #[test]
+    fn test_nonce_reuse_detector_is_bounded() {
+        let context = BTreeMap::new();
+        let mut detector = NonceReuseDetector::new(4);
+        let mut sequence = NonceSequence::new();
+        let nonces: Vec<_> = (0..5).map(|_| sequence.next(context.clone()).unwrap()).collect();
+        for nonce in &nonces {
+            detector.observe(&[1u8; 32], nonce, context.clone()).unwrap();
+        }
+        assert_eq!(detector.len(), 4);
+        assert!(detector.observe(&[1u8; 32], &nonces[0], context.clone()).is_ok());
+        assert!(detector.observe(&[1u8; 32], &nonces[4], context).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::paths::{decrypt_paths, encrypt_paths};
+    use serde_value::Value;
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const NEW_KEY_B64: &str = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+
+    fn document() -> serde_json::Value {
+        serde_json::json!({
+            "user": {"name": "alice", "ssn": "078-05-1120", "age": 42},
+            "payment": [
+                {"card": "4111111111111111", "amount": 10},
+                {"card": "5500000000000004", "amount": 20}
+            ],
+            "a/b": {"~key": true}
+        })
+    }
+
+    fn to_value(json: serde_json::Value) -> Value {
+        serde_value::to_value(json).unwrap()
+    }
+
+    fn to_json(value: Value) -> serde_json::Value {
+        value.deserialize_into().unwrap()
+    }
+
+    #[test]
+    fn test_paths_round_trip() {
+        let context = BTreeMap::new();
+        let paths = ["/user/ssn", "/user/age", "/payment/*/card", "/a~1b/~0key"];
+        let encrypted = to_json(encrypt_paths(to_value(document()), paths, SB_KEY_B64, context.clone()).unwrap());
+
+        assert_eq!(encrypted["user"]["name"], "alice");
+        assert!(encrypted["user"]["ssn"].as_str().unwrap().starts_with("sb:json:"));
+        assert!(encrypted["user"]["age"].as_str().unwrap().starts_with("sb:json:"));
+        assert!(encrypted["a/b"]["~key"].as_str().unwrap().starts_with("sb:json:"));
+        for payment in encrypted["payment"].as_array().unwrap() {
+            assert!(payment["card"].as_str().unwrap().starts_with("sb:json:"));
+            assert!(payment["amount"].is_u64());
+        }
+
+        let decrypted = decrypt_paths(to_value(encrypted), paths, SB_KEY_B64, context).unwrap();
+        assert_eq!(to_json(decrypted), document());
+    }
+
+    #[test]
+    fn test_paths_subtree_and_index() {
+        let context = BTreeMap::new();
+        let paths = ["/user", "/payment/1"];
+        let encrypted = to_json(encrypt_paths(to_value(document()), paths, SB_KEY_B64, context.clone()).unwrap());
+        assert!(encrypted["user"].is_string());
+        assert!(encrypted["payment"][0].is_object());
+        assert!(encrypted["payment"][1].is_string());
+        let decrypted = decrypt_paths(to_value(encrypted), paths, SB_KEY_B64, context).unwrap();
+        assert_eq!(to_json(decrypted), document());
+    }
+
+    #[test]
+    fn test_paths_missing_paths_are_ignored() {
+        let context = BTreeMap::new();
+        let paths = ["/user/email", "/payment/7/card", "/user/name/first"];
+        let encrypted = encrypt_paths(to_value(document()), paths, SB_KEY_B64, context).unwrap();
+        assert_eq!(to_json(encrypted), document());
+    }
+
+    #[test]
+    fn test_paths_invalid_pointer() {
+        let context = BTreeMap::new();
+        let err = encrypt_paths(to_value(document()), ["user/ssn"], SB_KEY_B64, context).unwrap_err();
+        assert_eq!(err.details_ref().get("path"), Some(&Value::String("user/ssn".to_string())));
+    }
+
+    #[test]
+    fn test_paths_overlapping_paths_are_rejected() {
+        let context = BTreeMap::new();
+        for paths in [["/user", "/user/ssn"], ["/payment/0/card", "/payment/*"], ["/user/ssn", "/user/ssn"], ["", "/user"]] {
+            let err = encrypt_paths(to_value(document()), paths, SB_KEY_B64, context.clone()).unwrap_err();
+            assert!(err.class().ends_with("::InvalidJsonPointer"), "{:?}", paths);
+            assert_eq!(err.details_ref().get("overlapping_path"), Some(&Value::String(paths[0].to_string())));
+            assert!(decrypt_paths(to_value(document()), paths, SB_KEY_B64, context.clone()).is_err());
+        }
+
+        let paths = ["/user/ssn", "/user/name", "/payment/*/card"];
+        let encrypted = encrypt_paths(to_value(document()), paths, SB_KEY_B64, context.clone()).unwrap();
+        assert_eq!(to_json(decrypt_paths(encrypted, paths, SB_KEY_B64, context).unwrap()), document());
+    }
+
+    #[test]
+    fn test_paths_decrypt_error_records_path() {
+        let context = BTreeMap::new();
+        let paths = ["/payment/*/card"];
+        let encrypted = encrypt_paths(to_value(document()), paths, SB_KEY_B64, context.clone()).unwrap();
+        let err = decrypt_paths(encrypted, paths, NEW_KEY_B64, context.clone()).unwrap_err();
+        assert_eq!(err.details_ref().get("path"), Some(&Value::String("/payment/0/card".to_string())));
+
+        let err = decrypt_paths(to_value(document()), ["/user/age"], SB_KEY_B64, context).unwrap_err();
+        assert_eq!(err.details_ref().get("path"), Some(&Value::String("/user/age".to_string())));
+    }
+
+    #[test]
+    fn test_paths_invalid_key() {
+        let context = BTreeMap::new();
+        assert!(encrypt_paths(to_value(document()), ["/user/ssn"], "AAAA", context).is_err());
+    }
+}
@@ -90,4 +90,78 @@ mod test {
         let result = cdumay_sodium::sealedbox::decrypt(&ciphertext, PRIV_KEY_B64, "not-valid-base64!!!", context);
         assert!(result.is_err());
     }
+
+    fn gen_keypair_b64() -> (String, String) {
+        use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+        use cdumay_base64::base64::Engine;
+        let mut public_key = [0u8; 32];
+        let mut private_key = [0u8; 32];
+        assert!(cdumay_sodium::init(BTreeMap::new()).is_ok());
+        unsafe { libsodium_sys::crypto_box_keypair(public_key.as_mut_ptr(), private_key.as_mut_ptr()) };
+        (BASE64_STANDARD.encode(private_key), BASE64_STANDARD.encode(public_key))
+    }
+
+    #[test]
+    fn test_sealedbox_multi() {
+        let context = BTreeMap::new();
+        let (other_priv, other_pub) = gen_keypair_b64();
+        let envelope = cdumay_sodium::sealedbox::crypt_multi(INPUT, &[PUB_KEY_B64, &other_pub], context.clone()).unwrap();
+
+        let result = cdumay_sodium::sealedbox::decrypt_multi(&envelope, PRIV_KEY_B64, PUB_KEY_B64, context.clone());
+        assert_eq!(INPUT, result.unwrap());
+        let result = cdumay_sodium::sealedbox::decrypt_multi(&envelope, &other_priv, &other_pub, context);
+        assert_eq!(INPUT, result.unwrap());
+    }
+
+    #[test]
+    fn test_sealedbox_multi_payload_stored_once() {
+        let context = BTreeMap::new();
+        use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+        use cdumay_base64::base64::Engine;
+        let recipients: Vec<String> = (0..5).map(|_| gen_keypair_b64().1).collect();
+        let recipients: Vec<&str> = recipients.iter().map(String::as_str).collect();
+        let one = cdumay_sodium::sealedbox::crypt_multi(INPUT, &recipients[..1], context.clone()).unwrap();
+        let five = cdumay_sodium::sealedbox::crypt_multi(INPUT, &recipients, context).unwrap();
+        let one = BASE64_STANDARD.decode(one).unwrap();
+        let five = BASE64_STANDARD.decode(five).unwrap();
+        // Each additional recipient only costs one sealed data key (32 + 48 bytes).
+        assert_eq!(five.len() - one.len(), 4 * 80);
+    }
+
+    #[test]
+    fn test_sealedbox_multi_not_a_recipient() {
+        let context = BTreeMap::new();
+        let (other_priv, other_pub) = gen_keypair_b64();
+        let envelope = cdumay_sodium::sealedbox::crypt_multi(INPUT, &[PUB_KEY_B64], context.clone()).unwrap();
+        let result = cdumay_sodium::sealedbox::decrypt_multi(&envelope, &other_priv, &other_pub, context);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_sealedbox_multi_no_recipient() {
+        let context = BTreeMap::new();
+        let err = cdumay_sodium::sealedbox::crypt_multi(INPUT, &[], context.clone()).unwrap_err();
+        assert!(err.class().starts_with("Client::InvalidConfiguration::"));
+        assert!(err.class().ends_with("::InvalidRecipientCount"));
+        assert!(cdumay_sodium::sealedbox::crypt_multi(INPUT, &[PUB_KEY_B64, "not-valid-base64!!!"], context).is_err());
+    }
+
+    #[test]
+    fn test_sealedbox_multi_tampered_or_truncated() {
+        let context = BTreeMap::new();
+        use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+        use cdumay_base64::base64::Engine;
+        let envelope = cdumay_sodium::sealedbox::crypt_multi(INPUT, &[PUB_KEY_B64], context.clone()).unwrap();
+        let mut decoded = BASE64_STANDARD.decode(&envelope).unwrap();
+        let last = decoded.len() - 1;
+        decoded[last] ^= 1;
+        let tampered = BASE64_STANDARD.encode(&decoded);
+        assert!(cdumay_sodium::sealedbox::decrypt_multi(&tampered, PRIV_KEY_B64, PUB_KEY_B64, context.clone()).is_err());
+        let truncated = BASE64_STANDARD.encode(&decoded[..40]);
+        assert!(cdumay_sodium::sealedbox::decrypt_multi(&truncated, PRIV_KEY_B64, PUB_KEY_B64, context.clone()).is_err());
+        decoded[0] = 2;
+        let unknown_version = BASE64_STANDARD.encode(&decoded);
+        let err = cdumay_sodium::sealedbox::decrypt_multi(&unknown_version, PRIV_KEY_B64, PUB_KEY_B64, context).unwrap_err();
+        assert!(err.class().ends_with("::InvalidEnvelope"));
+    }
 }
@@ -2,10 +2,12 @@
 mod test {
     use cdumay_base64::base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
     use cdumay_base64::base64::Engine;
-    use cdumay_sodium::secretbox;
-    use sodiumoxide::crypto::secretbox as sb;
+    use cdumay_sodium::secretbox::{self, Algorithm};
     use std::collections::BTreeMap;
 
+    const NONCEBYTES: usize = libsodium_sys::crypto_secretbox_NONCEBYTES as usize;
+    const MACBYTES: usize = libsodium_sys::crypto_secretbox_MACBYTES as usize;
+
     const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
     const INPUT: &str = r#"{"hello": "world"}"#;
 
@@ -63,13 +65,21 @@ mod test {
     fn test_secretbox_decrypt_invalid_utf8() {
         let context = BTreeMap::new();
         let key_decoded = BASE64_STANDARD.decode(SB_KEY_B64).unwrap();
-        let key_arr: [u8; sb::KEYBYTES] = key_decoded.as_slice().try_into().unwrap();
-        let key = sb::Key(key_arr);
-        let nonce = sb::gen_nonce();
-        let plaintext_invalid_utf8 = vec![0xFF, 0xFE];
-        let ciphertext = sb::seal(&plaintext_invalid_utf8, &nonce, &key);
+        let nonce = [7u8; NONCEBYTES];
+        let plaintext_invalid_utf8 = [0xFFu8, 0xFE];
+        let mut ciphertext = vec![0u8; plaintext_invalid_utf8.len() + MACBYTES];
+        assert!(cdumay_sodium::init(context.clone()).is_ok());
+        unsafe {
+            libsodium_sys::crypto_secretbox_easy(
+                ciphertext.as_mut_ptr(),
+                plaintext_invalid_utf8.as_ptr(),
+                plaintext_invalid_utf8.len() as u64,
+                nonce.as_ptr(),
+                key_decoded.as_ptr(),
+            );
+        }
         let data_b64 = BASE64_STANDARD.encode(ciphertext);
-        let nonce_b64 = BASE64_STANDARD.encode(nonce.as_ref());
+        let nonce_b64 = BASE64_STANDARD.encode(nonce);
         let result = secretbox::decrypt(&data_b64, SB_KEY_B64, &nonce_b64, context);
         assert!(result.is_err());
     }
@@ -111,4 +121,149 @@ mod test {
         let result = secretbox::decrypt(&ciphertext_b64, SB_KEY_B64, "!!!", context);
         assert!(result.is_err());
     }
+
+    const NEW_KEY_B64: &str = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+
+    #[test]
+    fn test_secretbox_reencrypt() {
+        let context = BTreeMap::new();
+        let (nonce_b64, data_b64) = secretbox::crypt(INPUT, SB_KEY_B64, context.clone()).unwrap();
+        let (new_nonce_b64, new_data_b64) = secretbox::reencrypt(&data_b64, &nonce_b64, SB_KEY_B64, NEW_KEY_B64, context.clone()).unwrap();
+        assert_ne!(nonce_b64, new_nonce_b64);
+        assert!(secretbox::decrypt(&new_data_b64, SB_KEY_B64, &new_nonce_b64, context.clone()).is_err());
+        let result = secretbox::decrypt(&new_data_b64, NEW_KEY_B64, &new_nonce_b64, context);
+        assert_eq!(INPUT, result.unwrap());
+    }
+
+    #[test]
+    fn test_secretbox_reencrypt_wrong_old_key() {
+        let context = BTreeMap::new();
+        let (nonce_b64, data_b64) = secretbox::crypt(INPUT, SB_KEY_B64, context.clone()).unwrap();
+        let result = secretbox::reencrypt(&data_b64, &nonce_b64, NEW_KEY_B64, SB_KEY_B64, context);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_secretbox_reencrypt_invalid_key() {
+        let context = BTreeMap::new();
+        let (nonce_b64, data_b64) = secretbox::crypt(INPUT, SB_KEY_B64, context.clone()).unwrap();
+        assert!(secretbox::reencrypt(&data_b64, &nonce_b64, SB_KEY_B64, "AAAA", context.clone()).is_err());
+        assert!(secretbox::reencrypt_batch(vec![(nonce_b64, data_b64)], "not-valid-base64!!!", NEW_KEY_B64, context).is_err());
+    }
+
+    #[test]
+    fn test_secretbox_reencrypt_batch_reports_item_failures() {
+        let mut context = BTreeMap::new();
+        context.insert("table".to_string(), serde_value::Value::String("users".to_string()));
+        let mut items: Vec<(String, String)> = (0..3).map(|_| secretbox::crypt(INPUT, SB_KEY_B64, context.clone()).unwrap()).collect();
+        items[1].1 = secretbox::crypt(INPUT, NEW_KEY_B64, context.clone()).unwrap().1;
+
+        let results: Vec<_> = secretbox::reencrypt_batch(items, SB_KEY_B64, NEW_KEY_B64, context.clone()).unwrap().collect();
+        assert_eq!(results.len(), 3);
+        for index in [0, 2] {
+            let (nonce_b64, data_b64) = results[index].as_ref().unwrap();
+            assert_eq!(INPUT, secretbox::decrypt(data_b64, NEW_KEY_B64, nonce_b64, context.clone()).unwrap());
+        }
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.details_ref().get("index"), Some(&serde_value::Value::U64(1)));
+        assert_eq!(err.details_ref().get("table"), Some(&serde_value::Value::String("users".to_string())));
+    }
+
+    #[test]
+    fn test_secretbox_deterministic() {
+        let context = BTreeMap::new();
+        let first = secretbox::crypt_deterministic("alice@example.com", "users.email", SB_KEY_B64, context.clone()).unwrap();
+        assert_eq!(first, secretbox::crypt_deterministic("alice@example.com", "users.email", SB_KEY_B64, context.clone()).unwrap());
+        assert_ne!(first, secretbox::crypt_deterministic("bob@example.com", "users.email", SB_KEY_B64, context.clone()).unwrap());
+        assert_ne!(first, secretbox::crypt_deterministic("alice@example.com", "admins.email", SB_KEY_B64, context.clone()).unwrap());
+        assert_ne!(first, secretbox::crypt_deterministic("alice@example.com", "users.email", NEW_KEY_B64, context.clone()).unwrap());
+
+        // The nonce is BLAKE2b-192 keyed with the crypto_kdf subkey 2 of context "sbdeterm".
+        let decoded = BASE64_STANDARD.decode(&first).unwrap();
+        assert_eq!(BASE64_STANDARD.encode(&decoded[..NONCEBYTES]), "gz2jFm6u2q8uPtqZNLIjby7tZQ8yiNVm");
+        assert_eq!(secretbox::decrypt_deterministic(&first, "users.email", SB_KEY_B64, context).unwrap(), "alice@example.com");
+    }
+
+    #[test]
+    fn test_secretbox_deterministic_verifies_derivation() {
+        let context = BTreeMap::new();
+        let encrypted = secretbox::crypt_deterministic(INPUT, "users.email", SB_KEY_B64, context.clone()).unwrap();
+        assert!(secretbox::decrypt_deterministic(&encrypted, "admins.email", SB_KEY_B64, context.clone()).is_err());
+        assert!(secretbox::decrypt_deterministic(&encrypted, "users.email", NEW_KEY_B64, context.clone()).is_err());
+
+        // A regular random-nonce ciphertext never verifies, even under the right key.
+        let (nonce_b64, data_b64) = secretbox::crypt(INPUT, SB_KEY_B64, context.clone()).unwrap();
+        let mut sealed = BASE64_STANDARD.decode(nonce_b64).unwrap();
+        sealed.extend(BASE64_STANDARD.decode(data_b64).unwrap());
+        assert!(secretbox::decrypt_deterministic(&BASE64_STANDARD.encode(sealed), "users.email", SB_KEY_B64, context.clone()).is_err());
+        assert!(secretbox::decrypt_deterministic("AAAA", "users.email", SB_KEY_B64, context).is_err());
+    }
+
+    #[test]
+    fn test_secretbox_crypt_with_nonce() {
+        let context = BTreeMap::new();
+        let data_b64 = "sUm+U20INMw6G4tfovoe4YSPYqzYdhfPhZ2v5U9Mu6tYIQ==";
+        let nonce_b64 = "HZGeSXQLJlFNpQgGyvYkXj+jAL9d/15J";
+        let plaintext = secretbox::decrypt(data_b64, SB_KEY_B64, nonce_b64, context.clone()).unwrap();
+        assert_eq!(secretbox::crypt_with_nonce(&plaintext, SB_KEY_B64, nonce_b64, context.clone()).unwrap(), data_b64);
+        assert!(secretbox::crypt_with_nonce(INPUT, SB_KEY_B64, "AAAA", context).is_err());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_secretbox_crypt_with_nonce_detects_reuse() {
+        let context = BTreeMap::new();
+        let key_b64 = BASE64_STANDARD.encode([42u8; libsodium_sys::crypto_secretbox_KEYBYTES as usize]);
+        let nonce_b64 = BASE64_STANDARD.encode([42u8; NONCEBYTES]);
+        assert!(secretbox::crypt_with_nonce(INPUT, &key_b64, &nonce_b64, context.clone()).is_ok());
+        assert!(secretbox::crypt_with_nonce("another message", &key_b64, &nonce_b64, context.clone()).is_err());
+        assert!(secretbox::crypt_with_nonce(INPUT, SB_KEY_B64, &nonce_b64, context).is_ok());
+    }
+
+    #[test]
+    fn test_secretbox_detached() {
+        let context = BTreeMap::new();
+        let (nonce_b64, data_b64, tag_b64) = secretbox::crypt_detached(INPUT, SB_KEY_B64, context.clone()).unwrap();
+        let tag = BASE64_STANDARD.decode(&tag_b64).unwrap();
+        assert_eq!(tag.len(), MACBYTES);
+        assert_eq!(BASE64_STANDARD.decode(&data_b64).unwrap().len(), INPUT.len());
+        assert_eq!(INPUT, secretbox::decrypt_detached(&data_b64, &tag_b64, SB_KEY_B64, &nonce_b64, context.clone()).unwrap());
+
+        // The combined form is the tag followed by the ciphertext.
+        let mut combined = tag.clone();
+        combined.extend(BASE64_STANDARD.decode(&data_b64).unwrap());
+        assert_eq!(INPUT, secretbox::decrypt(&BASE64_STANDARD.encode(combined), SB_KEY_B64, &nonce_b64, context.clone()).unwrap());
+
+        let mut forged = tag;
+        forged[0] ^= 1;
+        assert!(secretbox::decrypt_detached(&data_b64, &BASE64_STANDARD.encode(forged), SB_KEY_B64, &nonce_b64, context.clone()).is_err());
+        assert!(secretbox::decrypt_detached(&data_b64, "AAAA", SB_KEY_B64, &nonce_b64, context.clone()).is_err());
+        assert!(secretbox::decrypt_detached(&data_b64, &tag_b64, NEW_KEY_B64, &nonce_b64, context).is_err());
+    }
+
+    #[test]
+    fn test_secretbox_xchacha20poly1305() {
+        let context = BTreeMap::new();
+        let (nonce_b64, data_b64) = secretbox::crypt_with_algorithm(INPUT, SB_KEY_B64, Algorithm::XChaCha20Poly1305, context.clone()).unwrap();
+        assert_eq!(BASE64_STANDARD.decode(&data_b64).unwrap().len(), INPUT.len() + MACBYTES);
+        let decrypted = secretbox::decrypt_with_algorithm(&data_b64, SB_KEY_B64, &nonce_b64, Algorithm::XChaCha20Poly1305, context.clone());
+        assert_eq!(INPUT, decrypted.unwrap());
+        assert!(secretbox::decrypt(&data_b64, SB_KEY_B64, &nonce_b64, context.clone()).is_err());
+        assert!(secretbox::decrypt_with_algorithm(&data_b64, NEW_KEY_B64, &nonce_b64, Algorithm::XChaCha20Poly1305, context.clone()).is_err());
+        assert!(secretbox::decrypt_with_algorithm("AAAA", SB_KEY_B64, &nonce_b64, Algorithm::XChaCha20Poly1305, context.clone()).is_err());
+
+        let (nonce_b64, data_b64) = secretbox::crypt_with_algorithm(INPUT, SB_KEY_B64, Algorithm::default(), context.clone()).unwrap();
+        assert_eq!(INPUT, secretbox::decrypt(&data_b64, SB_KEY_B64, &nonce_b64, context).unwrap());
+    }
+
+    #[test]
+    fn test_secretbox_algorithm_ids() {
+        let context = BTreeMap::new();
+        for algorithm in [Algorithm::XSalsa20Poly1305, Algorithm::XChaCha20Poly1305] {
+            assert_eq!(Algorithm::from_id(algorithm.id(), context.clone()).unwrap(), algorithm);
+        }
+        assert!(Algorithm::from_id("aes256gcm", context).is_err());
+        let err = Algorithm::from_id("aes256gcm", None).unwrap_err();
+        assert_eq!(err.details_ref().get("operation"), Some(&serde_value::Value::String("Algorithm::from_id".to_string())));
+    }
 }
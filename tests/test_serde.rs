@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod test {
+    use cdumay_sodium::keyring::Keyring;
+    use cdumay_sodium::serde::KeyScope;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    const SB_KEY_B64: &str = "llQgXXVGlyQcwvkd78uwNoa2jzKzquFjRrHDwQ/eJSU=";
+    const NEW_KEY_B64: &str = "8Yq3b3n1c7m1Y2xXxgq8m9mS0vZp0l6mC3rQ1oS1sJw=";
+    const PRIV_KEY_B64: &str = "Y+rH6koXiQbMri56PrACMmTWTQ8vjlOgJr/3+IUF1KU=";
+    const PUB_KEY_B64: &str = "odxkRevQOBS/wvrZr9nr6uAsP2is2+frM/6mhCNqsz4=";
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        #[serde(with = "cdumay_sodium::serde::secretbox")]
+        api_token: String,
+        #[serde(with = "cdumay_sodium::serde::sealedbox")]
+        ssn: String,
+    }
+
+    fn user() -> User {
+        User { name: "alice".to_string(), api_token: "tok_123".to_string(), ssn: "078-05-1120".to_string() }
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let context = BTreeMap::new();
+        let _secretbox = KeyScope::secretbox(Keyring::new("k1", SB_KEY_B64, context.clone()).unwrap());
+        let _sealedbox = KeyScope::sealedbox_keypair(PRIV_KEY_B64, PUB_KEY_B64, context).unwrap();
+
+        let json = serde_json::to_string(&user()).unwrap();
+        assert!(json.contains(r#""name":"alice""#));
+        assert!(!json.contains("tok_123"));
+        assert!(!json.contains("078-05-1120"));
+        assert!(json.contains(r#""api_token":"sb:k1:"#));
+        assert!(json.contains(r#""ssn":"seal:"#));
+
+        let decoded: User = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, user());
+    }
+
+    #[test]
+    fn test_serde_sealedbox_write_only() {
+        let context = BTreeMap::new();
+        let _secretbox = KeyScope::secretbox(Keyring::new("k1", SB_KEY_B64, context.clone()).unwrap());
+        let json = {
+            let _writer = KeyScope::sealedbox_public(PUB_KEY_B64, context.clone()).unwrap();
+            let json = serde_json::to_string(&user()).unwrap();
+            assert!(serde_json::from_str::<User>(&json).is_err());
+            json
+        };
+        let _reader = KeyScope::sealedbox_keypair(PRIV_KEY_B64, PUB_KEY_B64, context).unwrap();
+        assert_eq!(serde_json::from_str::<User>(&json).unwrap(), user());
+    }
+
+    #[test]
+    fn test_serde_no_key_in_scope() {
+        assert!(serde_json::to_string(&user()).is_err());
+        let json = r#"{"name":"alice","api_token":"sb:k1:AAAA","ssn":"seal:AAAA"}"#;
+        assert!(serde_json::from_str::<User>(json).is_err());
+    }
+
+    #[test]
+    fn test_serde_nested_scopes_restore_keys() {
+        let context = BTreeMap::new();
+        let _sealedbox = KeyScope::sealedbox_keypair(PRIV_KEY_B64, PUB_KEY_B64, context.clone()).unwrap();
+        let _outer = KeyScope::secretbox(Keyring::new("outer", SB_KEY_B64, context.clone()).unwrap());
+        {
+            let _inner = KeyScope::secretbox(Keyring::new("inner", NEW_KEY_B64, context).unwrap());
+            assert!(serde_json::to_string(&user()).unwrap().contains("sb:inner:"));
+        }
+        let json = serde_json::to_string(&user()).unwrap();
+        assert!(json.contains("sb:outer:"));
+        assert_eq!(serde_json::from_str::<User>(&json).unwrap(), user());
+    }
+
+    #[test]
+    fn test_serde_scopes_dropped_out_of_order() {
+        let context = BTreeMap::new();
+        let _sealedbox = KeyScope::sealedbox_keypair(PRIV_KEY_B64, PUB_KEY_B64, context.clone()).unwrap();
+        let outer = KeyScope::secretbox(Keyring::new("outer", SB_KEY_B64, context.clone()).unwrap());
+        let inner = KeyScope::secretbox(Keyring::new("inner", NEW_KEY_B64, context).unwrap());
+        drop(outer);
+        assert!(serde_json::to_string(&user()).unwrap().contains("sb:inner:"));
+        drop(inner);
+        let err = cdumay_sodium::serde::secretbox::encrypt("hello", None).unwrap_err();
+        assert!(err.class().ends_with("::KeyNotInScope"));
+    }
+
+    #[test]
+    fn test_serde_missing_key_is_configuration_error() {
+        let err = cdumay_sodium::serde::secretbox::decrypt("sb:k1:AAAA", None).unwrap_err();
+        assert!(err.class().contains("::InvalidConfiguration::KeyNotInScope"), "{}", err.class());
+        let err = cdumay_sodium::serde::sealedbox::encrypt("hello", None).unwrap_err();
+        assert!(err.class().ends_with("::KeyNotInScope"));
+        let _writer = KeyScope::sealedbox_public(PUB_KEY_B64, None).unwrap();
+        let envelope = cdumay_sodium::serde::sealedbox::encrypt("hello", None).unwrap();
+        let err = cdumay_sodium::serde::sealedbox::decrypt(&envelope, None).unwrap_err();
+        assert!(err.class().ends_with("::KeyNotInScope"));
+    }
+
+    #[test]
+    fn test_serde_rotated_keyring() {
+        let context = BTreeMap::new();
+        let _sealedbox = KeyScope::sealedbox_keypair(PRIV_KEY_B64, PUB_KEY_B64, context.clone()).unwrap();
+        let json = {
+            let _old = KeyScope::secretbox(Keyring::new("v1", SB_KEY_B64, context.clone()).unwrap());
+            serde_json::to_string(&user()).unwrap()
+        };
+        let keyring = Keyring::from_config([("v1", SB_KEY_B64), ("v2", NEW_KEY_B64)], "v2", context).unwrap();
+        let _new = KeyScope::secretbox(keyring);
+        assert_eq!(serde_json::from_str::<User>(&json).unwrap(), user());
+    }
+
+    #[test]
+    fn test_serde_tampered_or_malformed() {
+        let context = BTreeMap::new();
+        let _secretbox = KeyScope::secretbox(Keyring::new("k1", SB_KEY_B64, context.clone()).unwrap());
+        let _sealedbox = KeyScope::sealedbox_keypair(PRIV_KEY_B64, PUB_KEY_B64, context).unwrap();
+        let json = serde_json::to_string(&user()).unwrap();
+        let other = serde_json::to_string(&User { api_token: "tok_456".to_string(), ..user() }).unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let other: serde_json::Value = serde_json::from_str(&other).unwrap();
+
+        value["ssn"] = other["api_token"].clone();
+        assert!(serde_json::from_value::<User>(value.clone()).is_err());
+        value["ssn"] = serde_json::Value::String("plain".to_string());
+        assert!(serde_json::from_value::<User>(value.clone()).is_err());
+        value["api_token"] = serde_json::Value::String("sb:unknown:AAAA".to_string());
+        assert!(serde_json::from_value::<User>(value).is_err());
+    }
+
+    #[test]
+    fn test_serde_encrypt_decrypt_functions() {
+        let context = BTreeMap::new();
+        let _secretbox = KeyScope::secretbox(Keyring::new("k1", SB_KEY_B64, context.clone()).unwrap());
+        let envelope = cdumay_sodium::serde::secretbox::encrypt("hello", context.clone()).unwrap();
+        assert_eq!(cdumay_sodium::serde::secretbox::decrypt(&envelope, context.clone()).unwrap(), "hello");
+        assert!(cdumay_sodium::serde::secretbox::decrypt("k1:AAAA", context).is_err());
+    }
+}